@@ -1,84 +1,264 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, fmt::Debug, sync::Arc};
 
 use banhammer_grpc::{
     grpc::{
-        validation_control_server::ValidationControl, AddBanRequest, AddBanResponse, BanItem,
-        ListBansRequest, ListBansResponse, RemoveBanRequest, RemoveBanResponse, StateRequest,
-        StateResponse,
+        self, validation_control_server::ValidationControl, AddBanRequest, AddBanResponse,
+        AuditLogRequest, AuditLogResponse, BanItem, DiffRevisionsRequest, DiffRevisionsResponse,
+        ExportNip51Request, ExportNip51Response, ImportNip51Request, ImportNip51Response,
+        ListBansRequest, ListBansResponse, ListRevisionsRequest, ListRevisionsResponse,
+        PruneBansRequest, PruneBansResponse, QueryRejectionsRequest, QueryRejectionsResponse,
+        RemoveBanRequest, RemoveBanResponse, Revision, RollbackRequest, RollbackResponse,
+        RuleCount, SetLockdownRequest, SetLockdownResponse, StateRequest, StateResponse,
+        StatsRequest, StatsResponse, StreamDecisionsRequest,
     },
-    BanTypesEnum,
+    BanTypesEnum, AUTHOR_METADATA,
 };
+use chrono::Utc;
+use num_traits::FromPrimitive;
 use tokio::sync::Mutex;
 use tonic::{Request, Response, Status};
+use tracing::{error, warn};
 
-use crate::hammer::{Ban, BanHammer};
+use crate::audit::{self, Action, AuditLog, AuditQuery};
+use crate::auth::{require, Identity, Role};
+use crate::decisions::{DecisionBroadcast, DecisionFilter, DecisionStream};
+use crate::hammer::{Ban, BanHammer, PolicyMode};
+use crate::metrics::Metrics;
+use crate::nip51;
+use crate::nostr::{from_npub, to_npub, Keys, NostrEvent};
+use crate::policy::Lockdown;
+use crate::rejection_store::{RejectionQuery, RejectionStore};
+use crate::stats::WINDOWS;
 
 // #[derive(Debug, Clone)]
 pub struct Admin {
     pub banhammer: Arc<Mutex<BanHammer>>,
+    /// Key used to sign exported moderation lists
+    pub moderator_key: Option<Keys>,
+    /// Every mutation is recorded there, whatever its result
+    pub audit: Arc<Mutex<AuditLog>>,
+    /// Decisions of the authorization service, reported by `Stats`
+    pub metrics: Arc<Metrics>,
+    pub mode: PolicyMode,
+    /// Decisions of the authorization service, streamed by `StreamDecisions`
+    pub decisions: Arc<DecisionBroadcast>,
+    /// Denied decisions, searched by `QueryRejections` when enabled
+    pub rejection_store: Option<Arc<RejectionStore>>,
+    /// Shared with the authorization service
+    pub lockdown: Arc<Lockdown>,
 }
 
-impl From<&Ban> for BanItem {
-    fn from(value: &Ban) -> Self {
-        Self {
-            content: value.content.clone(),
-            regex: value.regex,
-            reason: value.reason.clone(),
-            ban_type: value.ban_type.clone() as i32,
-        }
+/// Name recorded in the ban history and the audit log for a request.
+/// Authenticated requests use their credential name. Without credentials,
+/// the name clients send in the `AUTHOR_METADATA` metadata is only recorded
+/// as a claim, `unauthenticated:<name>`, since anyone can send any name.
+fn author<T>(request: &Request<T>) -> String {
+    if let Some(identity) = request.extensions().get::<Identity>() {
+        return identity.name.clone();
+    }
+
+    match request
+        .metadata()
+        .get(AUTHOR_METADATA)
+        .and_then(|author| author.to_str().ok())
+        .filter(|author| !author.is_empty())
+    {
+        Some(claim) => format!("unauthenticated:{}", claim),
+        None => "unauthenticated".to_string(),
     }
 }
 
-#[tonic::async_trait]
-impl ValidationControl for Admin {
-    async fn add_ban(
+/// Describes a mutation request, its result is filled in once handled.
+fn action<T: Debug>(name: &str, request: &Request<T>) -> Action {
+    Action {
+        actor: author(request),
+        address: request.remote_addr().map(|address| address.to_string()),
+        action: name.to_string(),
+        payload: format!("{:?}", request.get_ref()),
+        result: String::new(),
+    }
+}
+
+impl Admin {
+    /// Records a mutation and its result in the audit log.
+    async fn audit<T: Debug>(&self, mut action: Action, result: &Result<Response<T>, Status>) {
+        action.result = match result {
+            Ok(response) => format!("ok: {:?}", response.get_ref()),
+            Err(e) => format!("error: {:?}: {}", e.code(), e.message()),
+        };
+
+        if let Err(e) = self.audit.lock().await.record(action) {
+            error!("audit log: {}", e);
+        }
+    }
+
+    async fn apply_add_ban(
         &self,
-        request: Request<AddBanRequest>,
+        author: &str,
+        request: AddBanRequest,
     ) -> Result<Response<AddBanResponse>, Status> {
-        let ban = Ban::from(request.into_inner());
+        let mut ban = Ban::try_from(request).map_err(Status::invalid_argument)?;
+        ban.source = Some("admin".to_string());
 
         let mut banhammer = self.banhammer.lock().await;
 
-        match ban.ban_type {
-            BanTypesEnum::CONTENT => {
-                banhammer.words.append(&mut [ban].to_vec());
-            }
-            BanTypesEnum::USER => {
-                banhammer.users.append(&mut [ban].to_vec());
-            }
-            BanTypesEnum::IP => {
-                banhammer.ips.append(&mut [ban].to_vec());
+        // The audit log records why nothing was added
+        if banhammer.list_mut(ban.ban_type).is_none() {
+            return Err(Status::invalid_argument(format!(
+                "{} bans are not enforced",
+                ban.ban_type
+            )));
+        }
+        if !banhammer.add(ban) {
+            return Err(Status::already_exists("the same rule is already declared"));
+        }
+        banhammer.commit(author);
+
+        Ok(Response::new(AddBanResponse {}))
+    }
+
+    async fn apply_remove_ban(
+        &self,
+        author: &str,
+        request: RemoveBanRequest,
+    ) -> Result<Response<RemoveBanResponse>, Status> {
+        let mut banhammer = self.banhammer.lock().await;
+
+        let ban_type: BanTypesEnum = match FromPrimitive::from_i32(request.ban_type) {
+            Some(ban_type) => ban_type,
+            None => return Err(Status::invalid_argument("unknown ban type")),
+        };
+
+        let index = request.index as usize;
+        let ban = match banhammer.list_mut(ban_type).and_then(|l| l.get(index)) {
+            Some(ban) => ban.clone(),
+            None => return Err(Status::not_found("no ban at this index")),
+        };
+
+        if banhammer.is_read_only(&ban) {
+            return Err(Status::failed_precondition(format!(
+                "ban comes from read-only source {}",
+                ban.source.unwrap_or_default()
+            )));
+        }
+
+        banhammer.remove(&[ban]);
+        banhammer.commit(author);
+
+        Ok(Response::new(RemoveBanResponse { result: true }))
+    }
+
+    async fn apply_import_nip51(
+        &self,
+        author: &str,
+        request: ImportNip51Request,
+    ) -> Result<Response<ImportNip51Response>, Status> {
+        let event = NostrEvent::from_json(&request.event)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let bans = nip51::import(&event).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let mut banhammer = self.banhammer.lock().await;
+        let mut response = ImportNip51Response::default();
+
+        for ban in bans {
+            match banhammer.add(ban) {
+                true => response.imported += 1,
+                false => response.skipped += 1,
             }
-            BanTypesEnum::TAG => {
-                banhammer.tags.append(&mut [ban].to_vec());
+        }
+        banhammer.commit(author);
+
+        Ok(Response::new(response))
+    }
+
+    async fn apply_rollback(
+        &self,
+        author: &str,
+        request: RollbackRequest,
+    ) -> Result<Response<RollbackResponse>, Status> {
+        let mut banhammer = self.banhammer.lock().await;
+
+        match banhammer.rollback(request.revision, author) {
+            Ok(revision) => Ok(Response::new(RollbackResponse { revision })),
+            Err(e) => Err(Status::not_found(e.to_string())),
+        }
+    }
+
+    async fn apply_prune_bans(
+        &self,
+        author: &str,
+        request: PruneBansRequest,
+    ) -> Result<Response<PruneBansResponse>, Status> {
+        let mut banhammer = self.banhammer.lock().await;
+
+        let stale = banhammer.stale(request.days, Utc::now());
+        let bans = stale.iter().map(|ban| banhammer.item(ban)).collect();
+
+        let revision = match request.dry_run {
+            true => None,
+            false => {
+                banhammer.remove(&stale);
+                banhammer.commit(author)
             }
-            _ => {}
+        };
+
+        Ok(Response::new(PruneBansResponse { bans, revision }))
+    }
+
+    async fn apply_set_lockdown(
+        &self,
+        request: SetLockdownRequest,
+    ) -> Result<Response<SetLockdownResponse>, Status> {
+        let was_active = self.lockdown.set(request.active);
+        if was_active != request.active {
+            warn!(
+                "lockdown {}",
+                match request.active {
+                    true => "started",
+                    false => "lifted",
+                }
+            );
         }
-        Ok(Response::new(AddBanResponse {}))
+
+        Ok(Response::new(SetLockdownResponse { was_active }))
+    }
+}
+
+#[tonic::async_trait]
+impl ValidationControl for Admin {
+    async fn add_ban(
+        &self,
+        request: Request<AddBanRequest>,
+    ) -> Result<Response<AddBanResponse>, Status> {
+        let action = action("AddBan", &request);
+        let result = match require(&request, Role::Moderator) {
+            Ok(_) => {
+                self.apply_add_ban(&action.actor, request.into_inner())
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        self.audit(action, &result).await;
+        result
     }
 
     async fn list_bans(
         &self,
         request: Request<ListBansRequest>,
     ) -> Result<Response<ListBansResponse>, Status> {
+        require(&request, Role::Viewer)?;
+
         let banhammer_lock = &self.banhammer.lock().await;
 
-        let bans = match request.into_inner().ban_type {
-            0 => banhammer_lock
-                .words
-                .iter()
-                .map(|b| BanItem::from(b))
-                .collect(),
-            3 => banhammer_lock
-                .ips
-                .iter()
-                .map(|b| BanItem::from(b))
-                .collect(),
-            2 => banhammer_lock
-                .users
-                .iter()
-                .map(|b| BanItem::from(b))
-                .collect(),
+        let item = |ban| banhammer_lock.item(ban);
+        let bans = match FromPrimitive::from_i32(request.into_inner().ban_type) {
+            Some(BanTypesEnum::CONTENT) => banhammer_lock.words.iter().map(item).collect(),
+            Some(BanTypesEnum::TAG) => banhammer_lock.tags.iter().map(item).collect(),
+            Some(BanTypesEnum::USER) => banhammer_lock.users.iter().map(item).collect(),
+            Some(BanTypesEnum::IP) => banhammer_lock.ips.iter().map(item).collect(),
+            Some(BanTypesEnum::ORIGIN) => banhammer_lock.origins.iter().map(item).collect(),
+            Some(BanTypesEnum::USER_AGENT) => banhammer_lock.user_agents.iter().map(item).collect(),
             _ => [].to_vec(),
         };
 
@@ -89,15 +269,385 @@ impl ValidationControl for Admin {
         &self,
         request: Request<RemoveBanRequest>,
     ) -> Result<Response<RemoveBanResponse>, Status> {
-        let banhammer_lock = &self.banhammer.lock().await;
-
-        Ok(Response::new(RemoveBanResponse { result: true }))
+        let action = action("RemoveBan", &request);
+        let result = match require(&request, Role::Moderator) {
+            Ok(_) => {
+                self.apply_remove_ban(&action.actor, request.into_inner())
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        self.audit(action, &result).await;
+        result
     }
 
     async fn state(
         &self,
         request: Request<StateRequest>,
     ) -> Result<Response<StateResponse>, Status> {
+        require(&request, Role::Viewer)?;
+
         Ok(Response::new(StateResponse { state: true }))
     }
+
+    async fn export_nip51(
+        &self,
+        request: Request<ExportNip51Request>,
+    ) -> Result<Response<ExportNip51Response>, Status> {
+        require(&request, Role::Moderator)?;
+
+        let request = request.into_inner();
+
+        let keys = match &self.moderator_key {
+            Some(keys) => keys,
+            None => return Err(Status::failed_precondition("no moderator key configured")),
+        };
+
+        let kind = match request.kind {
+            0 => nip51::MUTE_LIST_KIND,
+            kind => kind,
+        };
+
+        // Temporary bans aren't published
+        let bans = {
+            let banhammer = self.banhammer.lock().await;
+            let mut bans = banhammer.bans();
+            bans.retain(|b| !banhammer.is_temporary(b));
+            bans
+        };
+
+        match nip51::export(&bans, keys, kind, request.identifier) {
+            Ok(event) => Ok(Response::new(ExportNip51Response {
+                event: event.to_json(),
+            })),
+            Err(e) => Err(Status::invalid_argument(e.to_string())),
+        }
+    }
+
+    async fn import_nip51(
+        &self,
+        request: Request<ImportNip51Request>,
+    ) -> Result<Response<ImportNip51Response>, Status> {
+        let action = action("ImportNip51", &request);
+        let result = match require(&request, Role::Moderator) {
+            Ok(_) => {
+                self.apply_import_nip51(&action.actor, request.into_inner())
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        self.audit(action, &result).await;
+        result
+    }
+
+    async fn list_revisions(
+        &self,
+        request: Request<ListRevisionsRequest>,
+    ) -> Result<Response<ListRevisionsResponse>, Status> {
+        require(&request, Role::Viewer)?;
+
+        let banhammer = self.banhammer.lock().await;
+        let revisions = banhammer.history.revisions();
+
+        let skip = match request.into_inner().limit {
+            Some(limit) => revisions.len().saturating_sub(limit as usize),
+            None => 0,
+        };
+
+        let revisions = revisions
+            .iter()
+            .skip(skip)
+            .map(|r| Revision {
+                number: r.number,
+                author: r.author.clone(),
+                timestamp: r.timestamp.clone(),
+                added: r.added.len() as u32,
+                removed: r.removed.len() as u32,
+            })
+            .collect();
+
+        Ok(Response::new(ListRevisionsResponse { revisions }))
+    }
+
+    async fn diff_revisions(
+        &self,
+        request: Request<DiffRevisionsRequest>,
+    ) -> Result<Response<DiffRevisionsResponse>, Status> {
+        require(&request, Role::Viewer)?;
+
+        let request = request.into_inner();
+        let banhammer = self.banhammer.lock().await;
+
+        match banhammer.history.diff(request.from, request.to) {
+            Ok((added, removed)) => Ok(Response::new(DiffRevisionsResponse {
+                added: added.iter().map(BanItem::from).collect(),
+                removed: removed.iter().map(BanItem::from).collect(),
+            })),
+            Err(e) => Err(Status::not_found(e.to_string())),
+        }
+    }
+
+    async fn rollback(
+        &self,
+        request: Request<RollbackRequest>,
+    ) -> Result<Response<RollbackResponse>, Status> {
+        let action = action("Rollback", &request);
+        let result = match require(&request, Role::Admin) {
+            Ok(_) => {
+                self.apply_rollback(&action.actor, request.into_inner())
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        self.audit(action, &result).await;
+        result
+    }
+
+    async fn audit_log(
+        &self,
+        request: Request<AuditLogRequest>,
+    ) -> Result<Response<AuditLogResponse>, Status> {
+        require(&request, Role::Admin)?;
+
+        let request = request.into_inner();
+
+        let date = |value: Option<String>| match value {
+            Some(value) => audit::parse_date(&value)
+                .map(Some)
+                .ok_or(format!("invalid date: {}", value)),
+            None => Ok(None),
+        };
+
+        let query = AuditQuery {
+            since: date(request.since).map_err(Status::invalid_argument)?,
+            until: date(request.until).map_err(Status::invalid_argument)?,
+            actor: request.actor,
+            limit: request.limit.map(|limit| limit as usize),
+        };
+
+        let log = self.audit.lock().await;
+        let verification = log.verify();
+
+        let entries = log
+            .query(&query)
+            .into_iter()
+            .map(|e| grpc::AuditEntry {
+                sequence: e.sequence,
+                timestamp: e.timestamp,
+                actor: e.actor,
+                address: e.address,
+                action: e.action,
+                payload: e.payload,
+                result: e.result,
+                prev_hash: e.prev_hash,
+                hash: e.hash,
+            })
+            .collect();
+
+        Ok(Response::new(AuditLogResponse {
+            entries,
+            verified: verification.is_ok(),
+            error: verification.err().map(|e| e.to_string()),
+        }))
+    }
+
+    async fn prune_bans(
+        &self,
+        request: Request<PruneBansRequest>,
+    ) -> Result<Response<PruneBansResponse>, Status> {
+        // Dry runs only read the ban set and are not audited
+        if request.get_ref().dry_run {
+            require(&request, Role::Viewer)?;
+            return self.apply_prune_bans("", request.into_inner()).await;
+        }
+
+        let action = action("PruneBans", &request);
+        let result = match require(&request, Role::Moderator) {
+            Ok(_) => {
+                self.apply_prune_bans(&action.actor, request.into_inner())
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        self.audit(action, &result).await;
+        result
+    }
+
+    async fn stats(
+        &self,
+        request: Request<StatsRequest>,
+    ) -> Result<Response<StatsResponse>, Status> {
+        require(&request, Role::Viewer)?;
+        // Pubkeys and IPs are only shown to moderators, like rejections
+        let identifiers = require(&request, Role::Moderator).is_ok();
+
+        let top = match request.get_ref().top {
+            0 => 10,
+            top => top as usize,
+        };
+        let now = Utc::now();
+
+        let (rules, top_rules) = {
+            let banhammer = self.banhammer.lock().await;
+
+            let mut counts: BTreeMap<(i32, String), u64> = BTreeMap::new();
+            for ban in banhammer.bans() {
+                let source = ban.source.clone().unwrap_or_default();
+                *counts.entry((ban.ban_type as i32, source)).or_default() += 1;
+            }
+            let rules = counts
+                .into_iter()
+                .map(|((ban_type, source), rules)| RuleCount {
+                    ban_type,
+                    source,
+                    rules,
+                })
+                .collect();
+
+            let top_rules = banhammer
+                .top_rules(top)
+                .iter()
+                .map(|ban| banhammer.item(ban))
+                .collect();
+
+            (rules, top_rules)
+        };
+
+        let windows = WINDOWS
+            .iter()
+            .map(|(window, seconds)| {
+                let (evaluated, denied) = self.metrics.windows.window(*seconds, now);
+                grpc::DecisionWindow {
+                    window: window.to_string(),
+                    evaluated,
+                    denied,
+                }
+            })
+            .collect();
+
+        let offenders = |offenders: Vec<(String, u64)>| {
+            offenders
+                .into_iter()
+                .map(|(key, denials)| grpc::Offender { key, denials })
+                .collect()
+        };
+        let (top_pubkeys, top_ips) = match identifiers {
+            true => self.metrics.windows.top_offenders(top, now),
+            false => (vec![], vec![]),
+        };
+
+        Ok(Response::new(StatsResponse {
+            uptime_seconds: self.metrics.started.elapsed().as_secs(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            rules,
+            windows,
+            top_rules,
+            top_pubkeys: offenders(top_pubkeys),
+            top_ips: offenders(top_ips),
+            policy_mode: self.mode.to_string(),
+            lockdown: self.lockdown.is_active(),
+        }))
+    }
+
+    type StreamDecisionsStream = DecisionStream;
+
+    async fn stream_decisions(
+        &self,
+        request: Request<StreamDecisionsRequest>,
+    ) -> Result<Response<Self::StreamDecisionsStream>, Status> {
+        require(&request, Role::Moderator)?;
+
+        let filter = DecisionFilter::try_from(request.get_ref())?;
+        Ok(Response::new(self.decisions.subscribe(filter)))
+    }
+
+    async fn query_rejections(
+        &self,
+        request: Request<QueryRejectionsRequest>,
+    ) -> Result<Response<QueryRejectionsResponse>, Status> {
+        require(&request, Role::Moderator)?;
+
+        let store = match &self.rejection_store {
+            Some(store) => store.clone(),
+            None => return Err(Status::failed_precondition("rejections are not stored")),
+        };
+        let request = request.into_inner();
+
+        let date = |value: Option<String>| match value {
+            Some(value) => audit::parse_date(&value)
+                .map(Some)
+                .ok_or(format!("invalid date: {}", value)),
+            None => Ok(None),
+        };
+
+        // Pubkeys are stored npub encoded
+        let pubkey = match request.pubkey {
+            Some(pubkey) => {
+                let npub = from_npub(&pubkey)
+                    .or_else(|| hex::decode(&pubkey).ok())
+                    .filter(|bytes| bytes.len() == 32)
+                    .and_then(|bytes| to_npub(&bytes));
+                match npub {
+                    Some(npub) => Some(npub),
+                    None => return Err(Status::invalid_argument("invalid pubkey")),
+                }
+            }
+            None => None,
+        };
+
+        let query = RejectionQuery {
+            pubkey,
+            event_id: request.event_id.map(|id| id.to_lowercase()),
+            ip: request.ip,
+            since: date(request.since).map_err(Status::invalid_argument)?,
+            until: date(request.until).map_err(Status::invalid_argument)?,
+            limit: request.limit.unwrap_or(100) as usize,
+        };
+
+        let rejections = tokio::task::spawn_blocking(move || store.query(&query))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .map_err(|e| {
+                error!("{}", e);
+                Status::internal("could not query the rejections")
+            })?;
+
+        Ok(Response::new(QueryRejectionsResponse { rejections }))
+    }
+
+    async fn set_lockdown(
+        &self,
+        request: Request<SetLockdownRequest>,
+    ) -> Result<Response<SetLockdownResponse>, Status> {
+        let action = action("SetLockdown", &request);
+        let result = match require(&request, Role::Moderator) {
+            Ok(_) => self.apply_set_lockdown(request.into_inner()).await,
+            Err(e) => Err(e),
+        };
+        self.audit(action, &result).await;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_author() {
+        let mut request = Request::new(());
+        assert_eq!(author(&request), "unauthenticated");
+
+        request
+            .metadata_mut()
+            .insert(AUTHOR_METADATA, "alice".parse().unwrap());
+        assert_eq!(author(&request), "unauthenticated:alice");
+
+        // Authenticated requests can't claim another name
+        request.extensions_mut().insert(Identity {
+            name: "bob".to_string(),
+            role: Role::Moderator,
+        });
+        assert_eq!(author(&request), "bob");
+    }
 }
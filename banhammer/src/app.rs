@@ -1,32 +1,100 @@
 use clap::Parser;
-use std::env;
+use std::path::PathBuf;
+
+use crate::hammer::PolicyMode;
+use crate::logging::LogFormat;
 
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
 pub struct App {
     #[arg(long, short)]
+    /// TOML configuration file, defaults to BANHAMMER_CONFIG or `banhammer.toml`
+    pub config: Option<PathBuf>,
+    #[arg(long)]
+    /// Prints the effective settings and exits
+    pub print_config: bool,
+    #[arg(long, short)]
+    /// Authorization service address, `unix:<path>` for a Unix socket, or `off`
     pub address: Option<String>,
+    #[arg(long)]
+    /// Admin service address, `unix:<path>` for a Unix socket, or `off`.
+    /// Shares the authorization listener by default
+    pub admin_address: Option<String>,
+    #[arg(long)]
+    /// Permissions of the authorization Unix socket, in octal
+    pub socket_mode: Option<String>,
+    #[arg(long)]
+    /// Permissions of the admin Unix socket, in octal
+    pub admin_socket_mode: Option<String>,
     #[arg(long, short)]
+    /// Comma separated list of ban list files or directories
     pub banlist: Option<String>,
     #[arg(long, short)]
     /// Save the modifications to config files
     pub save: bool,
+    #[arg(long)]
+    /// YAML file declaring remote ban lists to subscribe to
+    pub remotes: Option<String>,
+    #[arg(long)]
+    /// Directory where the last good copy of remote ban lists is kept
+    pub remote_cache: Option<String>,
+    #[arg(long)]
+    /// Moderator secret key (nsec or hex) used to sign exported NIP-51 lists
+    pub moderator_key: Option<String>,
+    #[arg(long)]
+    /// File where the rule hit counters are saved
+    pub hits_file: Option<String>,
+    #[arg(long)]
+    /// Append-only file recording every moderation action
+    pub audit_log: Option<String>,
+    #[arg(long)]
+    /// YAML file declaring the moderator credentials of the admin API
+    pub credentials: Option<String>,
+    #[arg(long)]
+    /// PEM certificate served over TLS by the admin listener,
+    /// reloaded on SIGHUP
+    pub tls_cert: Option<String>,
+    #[arg(long)]
+    /// PEM private key of the admin TLS certificate
+    pub tls_key: Option<String>,
+    #[arg(long)]
+    /// PEM CA used to verify moderator client certificates, which stay
+    /// optional since moderators can use tokens instead
+    pub tls_client_ca: Option<String>,
+    #[arg(long)]
+    /// PEM certificate served over TLS by the authorization listener,
+    /// reloaded on SIGHUP
+    pub authz_tls_cert: Option<String>,
+    #[arg(long)]
+    /// PEM private key of the authorization TLS certificate
+    pub authz_tls_key: Option<String>,
+    #[arg(long)]
+    /// PEM CA used to verify relay client certificates,
+    /// relays without a valid certificate are rejected
+    pub authz_tls_client_ca: Option<String>,
+    #[arg(long)]
+    /// Log filter, such as `info` or `banhammer=debug,warn`
+    pub log_level: Option<String>,
+    #[arg(long)]
+    /// Log format, `logfmt` or `json`
+    pub log_format: Option<LogFormat>,
+    #[arg(long)]
+    /// Address of the HTTP `/metrics` endpoint, such as `127.0.0.1:9100`
+    pub metrics_address: Option<String>,
+    #[arg(long)]
+    /// `enforce` denies rejected events, `monitor` only logs and counts them
+    pub policy_mode: Option<PolicyMode>,
+    #[arg(long)]
+    /// Denies events whose id or signature is invalid
+    pub verify_events: bool,
+    #[arg(long)]
+    /// SQLite database where denied decisions are kept
+    pub rejection_db: Option<String>,
 }
 
 impl App {
     pub fn new() -> Self {
-        let mut result = Self::parse();
-
-        if result.address.is_none() {
-            result.address =
-                Some(env::var("GRPC_RELAY_ADDRESS").unwrap_or("[::1]:50051".to_string()));
-        }
-
-        if result.banlist.is_none() {
-            result.banlist = Some(env::var("BANLIST").unwrap_or("bans.yaml".to_string()));
-        }
-
-        result
+        Self::parse()
     }
 }
@@ -0,0 +1,333 @@
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
+};
+
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+/// Previous hash of the first entry
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// A moderation action, chained to the previous entry by its hash.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEntry {
+    pub sequence: u64,
+    pub timestamp: String,
+    pub actor: String,
+    pub address: Option<String>,
+    pub action: String,
+    pub payload: String,
+    pub result: String,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    /// SHA-256 of every field but the hash itself.
+    pub fn compute_hash(&self) -> String {
+        let fields = serde_json::to_string(&(
+            self.sequence,
+            &self.timestamp,
+            &self.actor,
+            &self.address,
+            &self.action,
+            &self.payload,
+            &self.result,
+            &self.prev_hash,
+        ))
+        .unwrap_or_default();
+
+        hex::encode(Sha256::digest(fields.as_bytes()))
+    }
+
+    pub fn date(&self) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(&self.timestamp, TIMESTAMP_FORMAT).ok()
+    }
+}
+
+/// Describes a moderation action to record.
+#[derive(Debug, Clone, Default)]
+pub struct Action {
+    pub actor: String,
+    pub address: Option<String>,
+    pub action: String,
+    pub payload: String,
+    pub result: String,
+}
+
+#[derive(Debug)]
+pub enum AuditError {
+    Io(io::Error),
+    Parse(usize, String),
+    /// The entry with this sequence number does not match the chain
+    Tampered(u64),
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuditError::Io(e) => write!(f, "{}", e),
+            AuditError::Parse(line, e) => write!(f, "line {}: {}", line, e),
+            AuditError::Tampered(sequence) => {
+                write!(f, "entry {} does not match the hash chain", sequence)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AuditError {}
+
+impl From<io::Error> for AuditError {
+    fn from(value: io::Error) -> Self {
+        AuditError::Io(value)
+    }
+}
+
+/// Checks that every entry hash is valid and chained to the previous entry.
+pub fn verify(entries: &[AuditEntry]) -> Result<(), AuditError> {
+    let mut prev_hash = GENESIS_HASH.to_string();
+
+    for entry in entries {
+        if entry.prev_hash != prev_hash || entry.compute_hash() != entry.hash {
+            return Err(AuditError::Tampered(entry.sequence));
+        }
+        prev_hash = entry.hash.clone();
+    }
+
+    Ok(())
+}
+
+/// Parses a filter date, either `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM:SS` (UTC).
+pub fn parse_date(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, TIMESTAMP_FORMAT)
+        .ok()
+        .or(NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0)))
+}
+
+/// Filters applied when querying the audit log.
+#[derive(Debug, Clone, Default)]
+pub struct AuditQuery {
+    pub since: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+    pub actor: Option<String>,
+    /// Only returns the latest matching entries
+    pub limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        let date = entry.date();
+
+        if let Some(since) = self.since {
+            if date.is_none_or(|date| date < since) {
+                return false;
+            }
+        }
+
+        if let Some(until) = self.until {
+            if date.is_none_or(|date| date > until) {
+                return false;
+            }
+        }
+
+        match &self.actor {
+            Some(actor) => &entry.actor == actor,
+            None => true,
+        }
+    }
+}
+
+/// Append-only, hash chained log of moderation actions.
+///
+/// Entries are written as JSON lines. Each entry holds the hash of the
+/// previous one, so editing or removing an entry breaks the chain
+/// from that point on.
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    file: Option<File>,
+    entries: Vec<AuditEntry>,
+}
+
+impl AuditLog {
+    /// Opens an audit log file, creating it if needed.
+    /// Existing entries are loaded and new ones are chained to the last one.
+    pub fn open(path: PathBuf) -> Result<Self, AuditError> {
+        let entries = match path.exists() {
+            true => Self::read(&path)?,
+            false => Vec::new(),
+        };
+
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+        Ok(Self {
+            file: Some(file),
+            entries,
+        })
+    }
+
+    fn read(path: &PathBuf) -> Result<Vec<AuditEntry>, AuditError> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut entries = Vec::new();
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let entry =
+                serde_json::from_str(&line).map_err(|e| AuditError::Parse(i + 1, e.to_string()))?;
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+
+    pub fn verify(&self) -> Result<(), AuditError> {
+        verify(&self.entries)
+    }
+
+    /// Appends an action to the log. In memory logs only keep the entry.
+    pub fn record(&mut self, action: Action) -> Result<&AuditEntry, AuditError> {
+        let (sequence, prev_hash) = match self.entries.last() {
+            Some(last) => (last.sequence + 1, last.hash.clone()),
+            None => (1, GENESIS_HASH.to_string()),
+        };
+
+        let mut entry = AuditEntry {
+            sequence,
+            timestamp: Utc::now().format(TIMESTAMP_FORMAT).to_string(),
+            actor: action.actor,
+            address: action.address,
+            action: action.action,
+            payload: action.payload,
+            result: action.result,
+            prev_hash,
+            hash: String::new(),
+        };
+        entry.hash = entry.compute_hash();
+
+        if let Some(file) = &mut self.file {
+            let line = serde_json::to_string(&entry).unwrap_or_default();
+            writeln!(file, "{}", line)?;
+            file.flush()?;
+        }
+
+        self.entries.push(entry);
+        Ok(self.entries.last().unwrap())
+    }
+
+    /// Returns the entries matching a query, oldest first.
+    pub fn query(&self, query: &AuditQuery) -> Vec<AuditEntry> {
+        let entries: Vec<&AuditEntry> = self.entries.iter().filter(|e| query.matches(e)).collect();
+
+        let skip = match query.limit {
+            Some(limit) => entries.len().saturating_sub(limit),
+            None => 0,
+        };
+
+        entries.into_iter().skip(skip).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn action(actor: &str, action: &str) -> Action {
+        Action {
+            actor: actor.to_string(),
+            address: Some("127.0.0.1:4242".to_string()),
+            action: action.to_string(),
+            payload: "{}".to_string(),
+            result: "ok".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_chain_and_tampering() {
+        let mut log = AuditLog::default();
+        log.record(action("alice", "AddBan")).unwrap();
+        log.record(action("bob", "RemoveBan")).unwrap();
+        log.record(action("alice", "Rollback")).unwrap();
+
+        assert!(log.verify().is_ok());
+        assert_eq!(log.entries[0].prev_hash, GENESIS_HASH);
+        assert_eq!(log.entries[1].prev_hash, log.entries[0].hash);
+
+        let mut entries = log.entries.to_vec();
+        entries[1].actor = "mallory".to_string();
+        assert!(matches!(verify(&entries), Err(AuditError::Tampered(2))));
+
+        // Recomputing the hash breaks the next link
+        entries[1].hash = entries[1].compute_hash();
+        assert!(matches!(verify(&entries), Err(AuditError::Tampered(3))));
+
+        let mut entries = log.entries.to_vec();
+        entries.remove(0);
+        assert!(matches!(verify(&entries), Err(AuditError::Tampered(2))));
+    }
+
+    #[test]
+    fn test_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit/audit.jsonl");
+
+        let mut log = AuditLog::open(path.clone()).unwrap();
+        log.record(action("alice", "AddBan")).unwrap();
+        drop(log);
+
+        let mut log = AuditLog::open(path.clone()).unwrap();
+        log.record(action("bob", "AddBan")).unwrap();
+        assert_eq!(log.entries.len(), 2);
+        assert!(log.verify().is_ok());
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), 2);
+        fs::write(&path, content.replace("alice", "mallory")).unwrap();
+
+        let log = AuditLog::open(path).unwrap();
+        assert!(matches!(log.verify(), Err(AuditError::Tampered(1))));
+    }
+
+    #[test]
+    fn test_query() {
+        let mut log = AuditLog::default();
+        log.record(action("alice", "AddBan")).unwrap();
+        log.record(action("bob", "AddBan")).unwrap();
+        log.record(action("alice", "RemoveBan")).unwrap();
+
+        let query = AuditQuery {
+            actor: Some("alice".to_string()),
+            ..Default::default()
+        };
+        let entries = log.query(&query);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].action, "RemoveBan");
+
+        let query = AuditQuery {
+            limit: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(log.query(&query)[0].sequence, 3);
+
+        let query = AuditQuery {
+            since: parse_date("2000-01-01"),
+            until: parse_date("2000-01-02T12:00:00"),
+            ..Default::default()
+        };
+        assert!(log.query(&query).is_empty());
+        assert!(parse_date("yesterday").is_none());
+    }
+}
@@ -0,0 +1,268 @@
+use std::{fmt, fs, path::Path, sync::Arc};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tonic::{metadata::MetadataMap, service::Interceptor, transport::Certificate, Request, Status};
+
+/// Permissions of a moderator credential, each role includes the previous ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Can read bans, history and state
+    Viewer,
+    /// Can add, remove and import bans
+    Moderator,
+    /// Can roll back the ban set and read the audit log
+    Admin,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let role = match self {
+            Role::Viewer => "viewer",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        };
+
+        write!(f, "{}", role)
+    }
+}
+
+/// A named moderator credential.
+///
+/// Moderators authenticate either with a bearer token, stored as its
+/// SHA-256, or with a TLS client certificate, identified by the SHA-256
+/// of its DER encoding.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Credential {
+    pub name: String,
+    pub role: Role,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cert_sha256: Option<String>,
+}
+
+/// Identity of an authenticated request, stored in its extensions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identity {
+    pub name: String,
+    pub role: Role,
+}
+
+#[derive(Debug)]
+pub enum CredentialsError {
+    Io(std::io::Error),
+    Parse(String),
+}
+
+impl fmt::Display for CredentialsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CredentialsError::Io(e) => write!(f, "{}", e),
+            CredentialsError::Parse(e) => write!(f, "invalid credentials: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CredentialsError {}
+
+fn sha256(value: &[u8]) -> String {
+    hex::encode(Sha256::digest(value))
+}
+
+/// Extracts the token of an `authorization: Bearer <token>` header.
+fn bearer_token(metadata: &MetadataMap) -> Option<&str> {
+    metadata
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim())
+        .filter(|token| !token.is_empty())
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Credentials {
+    pub credentials: Vec<Credential>,
+}
+
+impl Credentials {
+    /// Loads credentials from a YAML file.
+    pub fn load(path: &Path) -> Result<Self, CredentialsError> {
+        let content = fs::read_to_string(path).map_err(CredentialsError::Io)?;
+        serde_yaml::from_str(&content).map_err(|e| CredentialsError::Parse(e.to_string()))
+    }
+
+    /// Finds the credential matching a bearer token or a client certificate.
+    pub fn authenticate(
+        &self,
+        token: Option<&str>,
+        certs: Option<&[Certificate]>,
+    ) -> Option<&Credential> {
+        if let Some(token) = token {
+            let hash = sha256(token.as_bytes());
+            return self.credentials.iter().find(|c| match &c.token_sha256 {
+                Some(token) => token.eq_ignore_ascii_case(&hash),
+                None => false,
+            });
+        }
+
+        let fingerprints: Vec<String> = certs
+            .unwrap_or_default()
+            .iter()
+            .map(|cert| sha256(cert.get_ref()))
+            .collect();
+
+        self.credentials.iter().find(|c| match &c.cert_sha256 {
+            Some(cert) => fingerprints.iter().any(|f| f.eq_ignore_ascii_case(cert)),
+            None => false,
+        })
+    }
+}
+
+/// Authenticates admin API requests.
+///
+/// The identity of authenticated requests is added to their extensions, and
+/// unknown credentials are rejected. Without credentials, every request is
+/// let through unauthenticated.
+#[derive(Debug, Clone, Default)]
+pub struct AdminAuth {
+    pub credentials: Option<Arc<Credentials>>,
+}
+
+impl Interceptor for AdminAuth {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let credentials = match &self.credentials {
+            Some(credentials) => credentials,
+            None => return Ok(request),
+        };
+
+        let certs = request.peer_certs();
+        let credential = credentials.authenticate(
+            bearer_token(request.metadata()),
+            certs.as_deref().map(|certs| certs.as_slice()),
+        );
+
+        match credential {
+            Some(credential) => {
+                let identity = Identity {
+                    name: credential.name.clone(),
+                    role: credential.role,
+                };
+                request.extensions_mut().insert(identity);
+                Ok(request)
+            }
+            None => Err(Status::unauthenticated("invalid or missing credentials")),
+        }
+    }
+}
+
+/// Checks that an authenticated request has at least the given role.
+/// Requests let through without authentication are always allowed.
+#[allow(clippy::result_large_err)]
+pub fn require<T>(request: &Request<T>, role: Role) -> Result<(), Status> {
+    match request.extensions().get::<Identity>() {
+        Some(identity) if identity.role < role => Err(Status::permission_denied(format!(
+            "{} role required, {} is {}",
+            role, identity.name, identity.role
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CREDENTIALS: &str = r#"
+credentials:
+  - name: alice
+    role: admin
+    # sha256 of "alice-token"
+    token_sha256: 9c220f200955d76c0a38d308225e0ef10c5f971acaf2f8d1d8f732affa5bd1dc
+  - name: bob
+    role: viewer
+    # sha256 of the "bob-cert" certificate
+    cert_sha256: ea1d0d28fd1797a1e806f55d43ca33bacd4b586d982396d39d763d187acf2e2e
+"#;
+
+    fn credentials() -> Credentials {
+        serde_yaml::from_str(CREDENTIALS).unwrap()
+    }
+
+    fn bearer_request(token: Option<&str>) -> Request<()> {
+        let mut request = Request::new(());
+        if let Some(token) = token {
+            request.metadata_mut().insert(
+                "authorization",
+                format!("Bearer {}", token).parse().unwrap(),
+            );
+        }
+        request
+    }
+
+    #[test]
+    fn test_authenticate() {
+        let credentials = credentials();
+
+        let alice = credentials.authenticate(Some("alice-token"), None).unwrap();
+        assert_eq!(alice.name, "alice");
+        assert!(credentials.authenticate(Some("wrong"), None).is_none());
+
+        let certs = [Certificate::from_pem("bob-cert")];
+        let bob = credentials.authenticate(None, Some(&certs)).unwrap();
+        assert_eq!(bob.role, Role::Viewer);
+
+        // A wrong token is not rescued by a valid certificate
+        assert!(credentials
+            .authenticate(Some("wrong"), Some(&certs))
+            .is_none());
+        assert!(credentials.authenticate(None, None).is_none());
+    }
+
+    #[test]
+    fn test_interceptor() {
+        let mut auth = AdminAuth {
+            credentials: Some(Arc::new(credentials())),
+        };
+
+        let request = auth.call(bearer_request(Some("alice-token"))).unwrap();
+        assert_eq!(
+            request.extensions().get::<Identity>().unwrap().name,
+            "alice"
+        );
+
+        // The bearer scheme is required
+        let mut without_scheme = Request::new(());
+        without_scheme
+            .metadata_mut()
+            .insert("authorization", "alice-token".parse().unwrap());
+        let status = auth.call(without_scheme).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+        let status = auth.call(bearer_request(None)).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+        // Without credentials the admin API is open
+        let mut open = AdminAuth::default();
+        let request = open.call(bearer_request(None)).unwrap();
+        assert!(request.extensions().get::<Identity>().is_none());
+        assert!(require(&request, Role::Admin).is_ok());
+    }
+
+    #[test]
+    fn test_require() {
+        let mut request = Request::new(());
+        request.extensions_mut().insert(Identity {
+            name: "carol".to_string(),
+            role: Role::Moderator,
+        });
+
+        assert!(require(&request, Role::Viewer).is_ok());
+        assert!(require(&request, Role::Moderator).is_ok());
+
+        let status = require(&request, Role::Admin).unwrap_err();
+        assert_eq!(status.code(), tonic::Code::PermissionDenied);
+        assert_eq!(status.message(), "admin role required, carol is moderator");
+    }
+}
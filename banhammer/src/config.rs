@@ -0,0 +1,669 @@
+use std::{
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app::App, created_at::CreatedAtSettings, hammer::PolicyMode, history::DEFAULT_MAX_REVISIONS,
+    limits::SizeLimitSettings, listener::ListenAddress, logging::LogFormat, nip42::Nip42Settings,
+    pow::PowSettings, ratelimit::RateLimitSettings, rejection::RejectionSettings,
+    rejection_store::RejectionStoreSettings, sources::SourceLoader, strikes::StrikeSettings,
+    validators::ConformanceSettings,
+};
+
+/// Configuration file loaded when none is provided, if it exists
+pub const DEFAULT_CONFIG_PATH: &str = "banhammer.toml";
+
+/// Server settings.
+///
+/// Settings are resolved in this order, the first one set wins:
+/// command line arguments, environment variables, the configuration
+/// file, then the defaults below.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub listeners: Listeners,
+    /// TLS of the admin listener, or of the shared listener
+    pub tls: TlsSettings,
+    /// TLS of the authorization listener
+    pub authz_tls: TlsSettings,
+    pub bans: Bans,
+    pub admin: AdminSettings,
+    pub logging: LoggingSettings,
+    pub metrics: MetricsSettings,
+    pub policy: PolicySettings,
+    /// Denied decisions kept for `QueryRejections`
+    pub rejection_store: RejectionStoreSettings,
+    pub rate_limits: RateLimitSettings,
+    pub strikes: StrikeSettings,
+    pub pow: PowSettings,
+    pub created_at: CreatedAtSettings,
+    pub size_limits: SizeLimitSettings,
+    pub conformance: ConformanceSettings,
+    pub nip42: Nip42Settings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Listeners {
+    /// Authorization service address, `unix:<path>` for a Unix socket, or `off`
+    pub address: String,
+    /// Admin service address, shares the authorization listener when unset
+    pub admin_address: Option<String>,
+    /// Permissions of the authorization Unix socket, in octal
+    pub socket_mode: String,
+    /// Permissions of the admin Unix socket, in octal
+    pub admin_socket_mode: String,
+}
+
+impl Default for Listeners {
+    fn default() -> Self {
+        Self {
+            address: "[::1]:50051".to_string(),
+            admin_address: None,
+            socket_mode: "660".to_string(),
+            admin_socket_mode: "600".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsSettings {
+    /// PEM certificate, reloaded on SIGHUP
+    pub cert: Option<String>,
+    /// PEM private key of the certificate
+    pub key: Option<String>,
+    /// PEM CA used to verify client certificates
+    pub client_ca: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Bans {
+    /// Ban list files or directories
+    pub banlist: Vec<String>,
+    /// YAML file declaring remote ban lists to subscribe to
+    pub remotes: Option<String>,
+    /// Directory where the last good copy of remote ban lists is kept
+    pub remote_cache: String,
+    /// Moderator secret key (nsec or hex) used to sign exported NIP-51 lists
+    pub moderator_key: Option<String>,
+    /// File where the rule hit counters are saved
+    pub hits_file: String,
+    /// Seconds between two saves of the hit counters
+    pub hits_save_interval: u64,
+    /// Revisions of the ban set kept in memory
+    pub max_revisions: usize,
+}
+
+impl Default for Bans {
+    fn default() -> Self {
+        Self {
+            banlist: vec!["bans.yaml".to_string()],
+            remotes: None,
+            remote_cache: "cache".to_string(),
+            moderator_key: None,
+            hits_file: "hits.json".to_string(),
+            hits_save_interval: 60,
+            max_revisions: DEFAULT_MAX_REVISIONS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminSettings {
+    /// YAML file declaring the moderator credentials of the admin API
+    pub credentials: Option<String>,
+    /// Append-only file recording every moderation action
+    pub audit_log: String,
+    /// Serves the admin API without credentials on a network address
+    pub insecure_admin: bool,
+}
+
+impl Default for AdminSettings {
+    fn default() -> Self {
+        Self {
+            credentials: None,
+            audit_log: "audit.jsonl".to_string(),
+            insecure_admin: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingSettings {
+    /// Log filter, such as `info` or `banhammer=debug,warn`
+    pub level: String,
+    pub format: LogFormat,
+    pub rejections: RejectionSettings,
+}
+
+impl Default for LoggingSettings {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            format: LogFormat::default(),
+            rejections: RejectionSettings::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsSettings {
+    /// Address of the HTTP `/metrics` endpoint, disabled when unset
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicySettings {
+    /// `enforce` denies rejected events, `monitor` only logs them
+    pub mode: PolicyMode,
+    /// Denies events whose id or signature is invalid
+    pub verify_events: bool,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    /// An environment variable, and why its value is invalid
+    Env(String, String),
+    Serialize(toml::ser::Error),
+    /// Settings that can't be used together
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+            ConfigError::Env(name, e) => write!(f, "{}: {}", name, e),
+            ConfigError::Serialize(e) => write!(f, "could not print the settings: {}", e),
+            ConfigError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Parses an environment variable, when set.
+fn parse<T>(var: &impl Fn(&str) -> Option<String>, name: &str) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    var(name)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|e: T::Err| ConfigError::Env(name.to_string(), e.to_string()))
+        })
+        .transpose()
+}
+
+/// Overrides a setting when a value is provided.
+fn set<T>(setting: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *setting = value;
+    }
+}
+
+impl Config {
+    /// Loads a TOML configuration file. Missing settings keep their default.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let content = fs::read_to_string(path).map_err(|e| ConfigError::Io(path.into(), e))?;
+        toml::from_str(&content).map_err(|e| ConfigError::Parse(path.into(), e))
+    }
+
+    /// Resolves the effective settings from the command line,
+    /// the environment and the configuration file.
+    pub fn resolve(app: &App) -> Result<Self, ConfigError> {
+        let path = app
+            .config
+            .clone()
+            .or(env::var("BANHAMMER_CONFIG").ok().map(PathBuf::from));
+
+        let mut config = match path {
+            Some(path) => Self::load(&path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).is_file() => {
+                Self::load(Path::new(DEFAULT_CONFIG_PATH))?
+            }
+            None => Self::default(),
+        };
+
+        config.apply_env(|name| env::var(name).ok())?;
+        config.apply_app(app);
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Refuses settings that would silently weaken the server.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let rejections = &self.logging.rejections;
+        if rejections.hash_identifiers && rejections.hash_salt.as_deref().unwrap_or("").is_empty() {
+            return Err(ConfigError::Invalid(
+                "logging.rejections.hash_identifiers requires a hash_salt".to_string(),
+            ));
+        }
+
+        // Without credentials, anyone reaching the admin listener can change the bans
+        let exposed = match self.admin_address().parse() {
+            Ok(ListenAddress::Tcp(address)) => !address.ip().is_loopback(),
+            _ => false,
+        };
+        if exposed && self.admin.credentials.is_none() && !self.admin.insecure_admin {
+            return Err(ConfigError::Invalid(format!(
+                "the admin API listens on {} without credentials, set admin.credentials \
+                 or admin.insecure_admin = true",
+                self.admin_address()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Overrides the settings declared in the environment.
+    /// Invalid values are reported rather than ignored.
+    pub fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        let listeners = &mut self.listeners;
+        set(&mut listeners.address, var("GRPC_RELAY_ADDRESS"));
+        set(&mut listeners.admin_address, var("ADMIN_ADDRESS").map(Some));
+        set(&mut listeners.socket_mode, var("SOCKET_MODE"));
+        set(&mut listeners.admin_socket_mode, var("ADMIN_SOCKET_MODE"));
+
+        set(&mut self.tls.cert, var("TLS_CERT").map(Some));
+        set(&mut self.tls.key, var("TLS_KEY").map(Some));
+        set(&mut self.tls.client_ca, var("TLS_CLIENT_CA").map(Some));
+        set(&mut self.authz_tls.cert, var("AUTHZ_TLS_CERT").map(Some));
+        set(&mut self.authz_tls.key, var("AUTHZ_TLS_KEY").map(Some));
+        set(
+            &mut self.authz_tls.client_ca,
+            var("AUTHZ_TLS_CLIENT_CA").map(Some),
+        );
+
+        let bans = &mut self.bans;
+        set(&mut bans.banlist, var("BANLIST").map(|v| split(&v)));
+        set(&mut bans.remotes, var("REMOTE_BANLISTS").map(Some));
+        set(&mut bans.remote_cache, var("REMOTE_CACHE_DIR"));
+        set(&mut bans.moderator_key, var("MODERATOR_KEY").map(Some));
+        set(&mut bans.hits_file, var("HITS_FILE"));
+        set(
+            &mut bans.hits_save_interval,
+            parse(&var, "HITS_SAVE_INTERVAL")?,
+        );
+
+        set(
+            &mut self.admin.credentials,
+            var("ADMIN_CREDENTIALS").map(Some),
+        );
+        set(&mut self.admin.audit_log, var("AUDIT_LOG"));
+        set(
+            &mut self.admin.insecure_admin,
+            parse(&var, "INSECURE_ADMIN")?,
+        );
+
+        let logging = &mut self.logging;
+        set(&mut logging.level, var("LOG_LEVEL"));
+        set(&mut logging.format, parse(&var, "LOG_FORMAT")?);
+
+        set(&mut self.metrics.address, var("METRICS_ADDRESS").map(Some));
+
+        set(&mut self.policy.mode, parse(&var, "POLICY_MODE")?);
+        set(
+            &mut self.policy.verify_events,
+            parse(&var, "VERIFY_EVENTS")?,
+        );
+
+        set(
+            &mut self.rejection_store.path,
+            var("REJECTION_DB").map(Some),
+        );
+
+        Ok(())
+    }
+
+    /// Overrides the settings provided on the command line.
+    pub fn apply_app(&mut self, app: &App) {
+        let listeners = &mut self.listeners;
+        set(&mut listeners.address, app.address.clone());
+        set(
+            &mut listeners.admin_address,
+            app.admin_address.clone().map(Some),
+        );
+        set(&mut listeners.socket_mode, app.socket_mode.clone());
+        set(
+            &mut listeners.admin_socket_mode,
+            app.admin_socket_mode.clone(),
+        );
+
+        set(&mut self.tls.cert, app.tls_cert.clone().map(Some));
+        set(&mut self.tls.key, app.tls_key.clone().map(Some));
+        set(&mut self.tls.client_ca, app.tls_client_ca.clone().map(Some));
+        set(
+            &mut self.authz_tls.cert,
+            app.authz_tls_cert.clone().map(Some),
+        );
+        set(&mut self.authz_tls.key, app.authz_tls_key.clone().map(Some));
+        set(
+            &mut self.authz_tls.client_ca,
+            app.authz_tls_client_ca.clone().map(Some),
+        );
+
+        let bans = &mut self.bans;
+        set(&mut bans.banlist, app.banlist.as_deref().map(split));
+        set(&mut bans.remotes, app.remotes.clone().map(Some));
+        set(&mut bans.remote_cache, app.remote_cache.clone());
+        set(&mut bans.moderator_key, app.moderator_key.clone().map(Some));
+        set(&mut bans.hits_file, app.hits_file.clone());
+
+        set(
+            &mut self.admin.credentials,
+            app.credentials.clone().map(Some),
+        );
+        set(&mut self.admin.audit_log, app.audit_log.clone());
+
+        set(&mut self.logging.level, app.log_level.clone());
+        set(&mut self.logging.format, app.log_format);
+
+        set(
+            &mut self.metrics.address,
+            app.metrics_address.clone().map(Some),
+        );
+
+        set(&mut self.policy.mode, app.policy_mode);
+        set(
+            &mut self.policy.verify_events,
+            app.verify_events.then_some(true),
+        );
+
+        set(
+            &mut self.rejection_store.path,
+            app.rejection_db.clone().map(Some),
+        );
+    }
+
+    /// Admin service address, which defaults to the authorization one.
+    pub fn admin_address(&self) -> &str {
+        self.listeners
+            .admin_address
+            .as_deref()
+            .unwrap_or(&self.listeners.address)
+    }
+
+    /// Effective settings as TOML, with secrets redacted.
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        let mut config = self.clone();
+        if config.bans.moderator_key.is_some() {
+            config.bans.moderator_key = Some("<redacted>".to_string());
+        }
+        if config.logging.rejections.hash_salt.is_some() {
+            config.logging.rejections.hash_salt = Some("<redacted>".to_string());
+        }
+
+        toml::to_string_pretty(&config).map_err(ConfigError::Serialize)
+    }
+}
+
+/// Splits a comma separated list of ban list paths.
+fn split(banlist: &str) -> Vec<String> {
+    SourceLoader::split(banlist)
+        .into_iter()
+        .map(|path| path.display().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use banhammer_grpc::BanTypesEnum;
+    use clap::Parser;
+
+    use crate::nip42::Identity;
+    use crate::rejection::SinkSettings;
+
+    fn app(args: &[&str]) -> App {
+        App::parse_from(std::iter::once("banhammer").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("banhammer.toml");
+        fs::write(
+            &path,
+            r#"
+[listeners]
+address = "0.0.0.0:50051"
+admin_address = "unix:/run/banhammer/admin.sock"
+
+[bans]
+banlist = ["bans.yaml", "bans.d"]
+
+[logging]
+format = "json"
+
+[[logging.rejections.sinks]]
+type = "file"
+path = "rejections.log"
+
+[[logging.rejections.sinks]]
+type = "syslog"
+
+[rejection_store]
+path = "rejections.db"
+store_events = true
+
+[[rate_limits.rules]]
+kinds = [1]
+events = 10
+seconds = 60
+
+[[rate_limits.overrides]]
+key = "10.0.0.0/8"
+
+[strikes]
+ban_types = ["content", "user"]
+
+[[strikes.thresholds]]
+strikes = 3
+seconds = 3600
+
+[pow]
+difficulty = 8
+
+[[pow.kinds]]
+kinds = [4, 1059]
+difficulty = 16
+
+[created_at]
+max_future = 900
+
+[[created_at.kinds]]
+kinds = ["30000-39999"]
+max_age = 31536000
+
+[size_limits]
+max_p_tags = 10
+
+[conformance]
+references = true
+
+[nip42]
+kinds = [4, 1059]
+identity = "auth"
+
+[[size_limits.kinds]]
+kinds = [3]
+max_p_tags = 10000
+"#,
+        )
+        .unwrap();
+
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.listeners.address, "0.0.0.0:50051");
+        assert_eq!(config.admin_address(), "unix:/run/banhammer/admin.sock");
+        assert_eq!(config.bans.banlist, vec!["bans.yaml", "bans.d"]);
+        // Missing settings keep their default
+        assert_eq!(config.listeners.socket_mode, "660");
+        assert_eq!(config.admin.audit_log, "audit.jsonl");
+        assert_eq!(config.logging.format, LogFormat::Json);
+        assert_eq!(
+            config.logging.rejections.sinks,
+            vec![
+                SinkSettings::File {
+                    path: PathBuf::from("rejections.log"),
+                    max_size: 10 * 1024 * 1024,
+                    max_files: 5,
+                },
+                SinkSettings::Syslog {
+                    socket: PathBuf::from("/dev/log"),
+                },
+            ]
+        );
+
+        assert_eq!(
+            config.rejection_store.path.as_deref(),
+            Some("rejections.db")
+        );
+        assert!(config.rejection_store.store_events);
+        assert_eq!(config.rejection_store.max_rows, 100_000);
+        assert!(config.rate_limits.rules[0].kinds[0].contains(1));
+        assert_eq!(config.rate_limits.rules[0].ipv6_prefix, 64);
+        assert_eq!(config.rate_limits.overrides[0].events, None);
+        assert_eq!(
+            config.strikes.ban_types,
+            vec![BanTypesEnum::CONTENT, BanTypesEnum::USER]
+        );
+        assert_eq!(config.strikes.thresholds[0].seconds, 3600);
+        assert_eq!(config.pow.kinds[0].difficulty, 16);
+        assert_eq!(config.pow.lockdown_difficulty, 20);
+        assert_eq!(config.created_at.max_future, Some(900));
+        assert!(config.created_at.kinds[0].kinds[0].contains(30023));
+        assert_eq!(config.size_limits.max_p_tags, Some(10));
+        assert_eq!(config.size_limits.kinds[0].max_p_tags, Some(10000));
+        assert!(config.conformance.references && !config.conformance.metadata);
+        assert!(config.nip42.kinds[1].contains(1059));
+        assert_eq!(config.nip42.identity, Identity::Auth);
+
+        fs::write(&path, "[listeners]\nadress = \"0.0.0.0:50051\"\n").unwrap();
+        assert!(matches!(Config::load(&path), Err(ConfigError::Parse(_, _))));
+        assert!(matches!(
+            Config::load(&dir.path().join("missing.toml")),
+            Err(ConfigError::Io(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_precedence() {
+        let mut config: Config = toml::from_str(
+            r#"
+[listeners]
+address = "127.0.0.1:1"
+socket_mode = "600"
+
+[admin]
+audit_log = "file.jsonl"
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.admin_address(), "127.0.0.1:1");
+
+        config
+            .apply_env(|name| match name {
+                "GRPC_RELAY_ADDRESS" => Some("127.0.0.1:2".to_string()),
+                "AUDIT_LOG" => Some("env.jsonl".to_string()),
+                "BANLIST" => Some("a.yaml, b.toml".to_string()),
+                "POLICY_MODE" => Some("monitor".to_string()),
+                "VERIFY_EVENTS" => Some("true".to_string()),
+                _ => None,
+            })
+            .unwrap();
+        config.apply_app(&app(&["--address", "127.0.0.1:3"]));
+
+        // CLI > env > file > defaults
+        assert_eq!(config.listeners.address, "127.0.0.1:3");
+        assert_eq!(config.admin.audit_log, "env.jsonl");
+        assert_eq!(config.listeners.socket_mode, "600");
+        assert_eq!(config.listeners.admin_socket_mode, "600");
+        assert_eq!(config.bans.banlist, vec!["a.yaml", "b.toml"]);
+        assert_eq!(config.bans.remote_cache, "cache");
+        assert_eq!(config.policy.mode, PolicyMode::Monitor);
+        assert!(config.policy.verify_events);
+    }
+
+    #[test]
+    fn test_invalid_env() {
+        for name in [
+            "HITS_SAVE_INTERVAL",
+            "LOG_FORMAT",
+            "POLICY_MODE",
+            "VERIFY_EVENTS",
+        ] {
+            let mut config = Config::default();
+            let error = config
+                .apply_env(|var| (var == name).then(|| "monitr".to_string()))
+                .unwrap_err();
+            assert!(error.to_string().starts_with(&format!("{}: ", name)));
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = Config::default();
+        assert!(config.validate().is_ok());
+
+        config.logging.rejections.hash_identifiers = true;
+        assert!(config.validate().is_err());
+        config.logging.rejections.hash_salt = Some(String::new());
+        assert!(config.validate().is_err());
+        config.logging.rejections.hash_salt = Some("change me".to_string());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_admin() {
+        let mut config = Config::default();
+        config.listeners.address = "0.0.0.0:50051".to_string();
+        let error = config.validate().unwrap_err();
+        assert!(error.to_string().contains("without credentials"));
+
+        // Loopback, Unix sockets and disabled listeners are safe
+        for address in ["127.0.0.1:50052", "unix:/run/banhammer/admin.sock", "off"] {
+            config.listeners.admin_address = Some(address.to_string());
+            assert!(config.validate().is_ok(), "{}", address);
+        }
+
+        config.listeners.admin_address = Some("[::]:50052".to_string());
+        assert!(config.validate().is_err());
+        config.admin.insecure_admin = true;
+        assert!(config.validate().is_ok());
+        config.admin.insecure_admin = false;
+        config.admin.credentials = Some("credentials.yaml".to_string());
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_to_toml() {
+        let mut config = Config::default();
+        config.bans.moderator_key = Some("nsec1secret".to_string());
+
+        let printed = config.to_toml().unwrap();
+        assert!(!printed.contains("nsec1secret"));
+
+        // The printed settings can be used as a configuration file
+        let parsed: Config = toml::from_str(&printed).unwrap();
+        assert_eq!(parsed.listeners, config.listeners);
+        assert_eq!(parsed.bans.banlist, config.bans.banlist);
+    }
+}
@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use banhammer_grpc::grpc::Event;
+use serde::{Deserialize, Serialize};
+
+use crate::policy::{Clock, KindRange};
+
+/// Limits of some kinds, overriding the default ones.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CreatedAtKindSettings {
+    pub kinds: Vec<KindRange>,
+    /// Falls back to the default limit when unset
+    pub max_future: Option<u64>,
+    /// Falls back to the default limit when unset
+    pub max_age: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CreatedAtSettings {
+    /// Seconds an event can be dated in the future, unlimited when unset
+    pub max_future: Option<u64>,
+    /// Seconds an event can be dated in the past, unlimited when unset
+    pub max_age: Option<u64>,
+    /// The first entry listing the kind of an event applies
+    pub kinds: Vec<CreatedAtKindSettings>,
+}
+
+/// Denies events dated too far in the future or in the past.
+pub struct CreatedAtWindow {
+    settings: CreatedAtSettings,
+    clock: Arc<dyn Clock>,
+}
+
+impl CreatedAtWindow {
+    pub fn new(settings: &CreatedAtSettings, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            settings: settings.clone(),
+            clock,
+        }
+    }
+
+    /// Seconds an event of a kind can be dated in the future and in the past.
+    fn limits(&self, kind: u64) -> (Option<u64>, Option<u64>) {
+        let settings = &self.settings;
+        match settings
+            .kinds
+            .iter()
+            .find(|k| k.kinds.iter().any(|range| range.contains(kind)))
+        {
+            Some(k) => (
+                k.max_future.or(settings.max_future),
+                k.max_age.or(settings.max_age),
+            ),
+            None => (settings.max_future, settings.max_age),
+        }
+    }
+
+    /// Returns why the date of an event is not accepted, if it isn't.
+    pub fn check(&self, event: &Event) -> Option<String> {
+        let (max_future, max_age) = self.limits(event.kind);
+        if max_future.is_none() && max_age.is_none() {
+            return None;
+        }
+
+        // Dates beyond i64 are far enough in the future to be denied
+        let now = self.clock.timestamp() as i128;
+        let offset = event.created_at as i128 - now;
+
+        if let Some(max_future) = max_future {
+            if offset > max_future as i128 {
+                return Some(format!(
+                    "invalid: created_at is more than {} seconds in the future",
+                    max_future
+                ));
+            }
+        }
+        if let Some(max_age) = max_age {
+            if -offset > max_age as i128 {
+                return Some(format!(
+                    "invalid: created_at is more than {} seconds in the past",
+                    max_age
+                ));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Instant;
+
+    const NOW: i64 = 1_700_000_000;
+
+    struct FixedClock;
+
+    impl Clock for FixedClock {
+        fn now(&self) -> Instant {
+            Instant::now()
+        }
+
+        fn timestamp(&self) -> i64 {
+            NOW
+        }
+    }
+
+    fn event(kind: u64, offset: i64) -> Event {
+        Event {
+            kind,
+            created_at: (NOW + offset) as u64,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let settings: CreatedAtSettings = toml::from_str(
+            r#"
+max_future = 60
+max_age = 3600
+
+[[kinds]]
+kinds = ["10000-19999", "30000-39999"]
+max_age = 31536000
+"#,
+        )
+        .unwrap();
+        let window = CreatedAtWindow::new(&settings, Arc::new(FixedClock));
+
+        assert_eq!(window.check(&event(1, 60)), None);
+        assert_eq!(window.check(&event(1, -3600)), None);
+        assert_eq!(
+            window.check(&event(1, 61)),
+            Some("invalid: created_at is more than 60 seconds in the future".to_string())
+        );
+        assert_eq!(
+            window.check(&event(1, -3601)),
+            Some("invalid: created_at is more than 3600 seconds in the past".to_string())
+        );
+
+        // Replaceable events can be old, but not from the future
+        assert_eq!(window.check(&event(10002, -86400 * 30)), None);
+        assert!(window.check(&event(30023, 61)).is_some());
+
+        let far_future = Event {
+            kind: 1,
+            created_at: u64::MAX,
+            ..Default::default()
+        };
+        assert!(window.check(&far_future).is_some());
+    }
+
+    #[test]
+    fn test_disabled() {
+        let window = CreatedAtWindow::new(&CreatedAtSettings::default(), Arc::new(FixedClock));
+        assert_eq!(window.check(&event(1, 86400 * 365)), None);
+        assert_eq!(window.check(&event(1, -NOW)), None);
+    }
+}
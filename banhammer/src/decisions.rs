@@ -0,0 +1,298 @@
+use std::time::Duration;
+
+use banhammer_grpc::{
+    grpc::{DecisionMatch, DecisionTrace, Event, StreamDecisionsRequest},
+    BanTypesEnum,
+};
+use chrono::Utc;
+use num_traits::FromPrimitive;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Status;
+
+use crate::hammer::rule_id;
+use crate::nostr::{from_npub, to_npub};
+use crate::rejection::Rejection;
+
+/// Traces buffered for each subscriber before it is dropped
+const BUFFER: usize = 256;
+
+pub type DecisionStream = ReceiverStream<Result<DecisionTrace, Status>>;
+
+/// Selects the decisions sent to a subscriber. Unset filters match everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecisionFilter {
+    /// `true` for denied events, `false` for permitted ones
+    pub denied: Option<bool>,
+    /// Events rejected by a ban of this type
+    pub ban_type: Option<i32>,
+    /// Events of this author, npub encoded
+    pub pubkey: Option<String>,
+    pub ip: Option<String>,
+    pub kind: Option<u64>,
+}
+
+impl TryFrom<&StreamDecisionsRequest> for DecisionFilter {
+    type Error = Status;
+
+    fn try_from(request: &StreamDecisionsRequest) -> Result<Self, Self::Error> {
+        let denied = match request.decision.as_deref().map(str::to_lowercase) {
+            None => None,
+            Some(decision) if decision == "deny" => Some(true),
+            Some(decision) if decision == "permit" => Some(false),
+            Some(decision) => {
+                return Err(Status::invalid_argument(format!(
+                    "unknown decision: {}, expected permit or deny",
+                    decision
+                )))
+            }
+        };
+
+        if let Some(ban_type) = request.ban_type {
+            if BanTypesEnum::from_i32(ban_type).is_none() {
+                return Err(Status::invalid_argument("unknown ban type"));
+            }
+        }
+
+        // Pubkeys can be given npub or hex encoded
+        let pubkey = match request.pubkey.as_deref() {
+            None => None,
+            Some(pubkey) => {
+                let bytes = from_npub(pubkey)
+                    .or_else(|| hex::decode(pubkey).ok())
+                    .filter(|bytes| bytes.len() == 32);
+                match bytes.as_deref().and_then(to_npub) {
+                    Some(npub) => Some(npub),
+                    None => return Err(Status::invalid_argument("invalid pubkey")),
+                }
+            }
+        };
+
+        Ok(Self {
+            denied,
+            ban_type: request.ban_type,
+            pubkey,
+            ip: request.ip.clone(),
+            kind: request.kind,
+        })
+    }
+}
+
+impl DecisionFilter {
+    pub fn matches(&self, trace: &DecisionTrace) -> bool {
+        self.denied.is_none_or(|denied| trace.denied == denied)
+            && self
+                .ban_type
+                .is_none_or(|ban_type| trace.matches.iter().any(|m| m.ban_type == ban_type))
+            && self
+                .pubkey
+                .as_ref()
+                .is_none_or(|pubkey| &trace.pubkey == pubkey)
+            && self
+                .ip
+                .as_ref()
+                .is_none_or(|ip| trace.ip.as_ref() == Some(ip))
+            && self.kind.is_none_or(|kind| trace.kind == kind)
+    }
+}
+
+/// Describes a decision taken by the authorization service.
+pub fn trace(
+    event: &Event,
+    ip: Option<&str>,
+    rejections: &[Rejection],
+    denied: bool,
+    duration: Duration,
+) -> DecisionTrace {
+    let matches = rejections
+        .iter()
+        .map(|rejection| DecisionMatch {
+            ban_type: rejection.ban_type as i32,
+            rule_id: rejection.ban.as_ref().map(rule_id),
+            content: rejection.ban.as_ref().map(|ban| ban.content.clone()),
+            reason: rejection.ban.as_ref().and_then(|ban| ban.reason.clone()),
+        })
+        .collect();
+
+    DecisionTrace {
+        timestamp: Utc::now().format("%Y-%m-%dT%H:%M:%S%.3f").to_string(),
+        denied,
+        event_id: hex::encode(&event.id),
+        pubkey: to_npub(&event.pubkey).unwrap_or_default(),
+        kind: event.kind,
+        ip: ip.map(str::to_string),
+        matches,
+        duration_us: duration.as_micros() as u64,
+        message: None,
+    }
+}
+
+struct Subscriber {
+    filter: DecisionFilter,
+    sender: mpsc::Sender<Result<DecisionTrace, Status>>,
+}
+
+/// Sends decision traces to the `StreamDecisions` subscribers.
+///
+/// Publishing never waits: a subscriber that doesn't keep up fills its
+/// buffer and is dropped, so slow clients can't slow `event_admit`.
+#[derive(Default)]
+pub struct DecisionBroadcast {
+    subscribers: std::sync::Mutex<Vec<Subscriber>>,
+}
+
+impl DecisionBroadcast {
+    pub fn subscribe(&self, filter: DecisionFilter) -> DecisionStream {
+        let (sender, receiver) = mpsc::channel(BUFFER);
+        self.subscribers
+            .lock()
+            .unwrap()
+            .push(Subscriber { filter, sender });
+        ReceiverStream::new(receiver)
+    }
+
+    /// Whether traces are worth building.
+    pub fn is_empty(&self) -> bool {
+        self.subscribers.lock().unwrap().is_empty()
+    }
+
+    pub fn publish(&self, trace: &DecisionTrace) {
+        self.subscribers.lock().unwrap().retain(|subscriber| {
+            if subscriber.sender.is_closed() {
+                return false;
+            }
+            if !subscriber.filter.matches(trace) {
+                return true;
+            }
+
+            // The last slot is kept to tell the client why it is dropped
+            if subscriber.sender.capacity() <= 1 {
+                let _ = subscriber.sender.try_send(Err(Status::resource_exhausted(
+                    "decisions are not read fast enough, the stream is closed",
+                )));
+                return false;
+            }
+
+            subscriber.sender.try_send(Ok(trace.clone())).is_ok()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio_stream::StreamExt;
+
+    use crate::hammer::Ban;
+
+    fn event(pubkey: u8, kind: u64) -> Event {
+        Event {
+            pubkey: vec![pubkey; 32],
+            kind,
+            ..Default::default()
+        }
+    }
+
+    fn rejection(event: &Event) -> Rejection {
+        let ban = Ban {
+            content: "spam".to_string(),
+            regex: false,
+            reason: Some("spam".to_string()),
+            date: None,
+            source: None,
+            ban_type: BanTypesEnum::CONTENT,
+        };
+        Rejection::new(BanTypesEnum::CONTENT, Some(&ban), event, "1.1.1.1")
+    }
+
+    #[test]
+    fn test_filter() {
+        let denied_event = event(1, 1);
+        let denied = trace(
+            &denied_event,
+            Some("1.1.1.1"),
+            &[rejection(&denied_event)],
+            true,
+            Duration::from_micros(5),
+        );
+        let permitted = trace(&event(2, 7), None, &[], false, Duration::ZERO);
+        assert_eq!(denied.matches[0].content.as_deref(), Some("spam"));
+
+        let filter = |request: StreamDecisionsRequest| DecisionFilter::try_from(&request).unwrap();
+
+        let any = filter(StreamDecisionsRequest::default());
+        assert!(any.matches(&denied) && any.matches(&permitted));
+
+        let deny = filter(StreamDecisionsRequest {
+            decision: Some("DENY".to_string()),
+            ..Default::default()
+        });
+        assert!(deny.matches(&denied) && !deny.matches(&permitted));
+
+        let content = filter(StreamDecisionsRequest {
+            ban_type: Some(BanTypesEnum::CONTENT as i32),
+            ..Default::default()
+        });
+        assert!(content.matches(&denied) && !content.matches(&permitted));
+
+        // Hex pubkeys are compared with npubs
+        let pubkey = filter(StreamDecisionsRequest {
+            pubkey: Some(hex::encode([2; 32])),
+            kind: Some(7),
+            ..Default::default()
+        });
+        assert!(!pubkey.matches(&denied) && pubkey.matches(&permitted));
+
+        let ip = filter(StreamDecisionsRequest {
+            ip: Some("1.1.1.1".to_string()),
+            ..Default::default()
+        });
+        assert!(ip.matches(&denied) && !ip.matches(&permitted));
+
+        for request in [
+            StreamDecisionsRequest {
+                decision: Some("maybe".to_string()),
+                ..Default::default()
+            },
+            StreamDecisionsRequest {
+                pubkey: Some("npub1nope".to_string()),
+                ..Default::default()
+            },
+            StreamDecisionsRequest {
+                ban_type: Some(42),
+                ..Default::default()
+            },
+        ] {
+            assert!(DecisionFilter::try_from(&request).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_dropped() {
+        let broadcast = DecisionBroadcast::default();
+        let trace = trace(&event(1, 1), None, &[], false, Duration::ZERO);
+
+        let mut slow = broadcast.subscribe(DecisionFilter::default());
+        let mut fast = broadcast.subscribe(DecisionFilter::default());
+        assert!(!broadcast.is_empty());
+
+        for _ in 0..BUFFER * 2 {
+            broadcast.publish(&trace);
+            // Only the fast subscriber reads as traces come
+            assert!(fast.next().await.unwrap().is_ok());
+        }
+
+        for _ in 0..BUFFER - 1 {
+            assert!(slow.next().await.unwrap().is_ok());
+        }
+        let error = slow.next().await.unwrap().unwrap_err();
+        assert_eq!(error.code(), tonic::Code::ResourceExhausted);
+        assert!(slow.next().await.is_none());
+
+        // Closed streams are forgotten
+        drop(fast);
+        broadcast.publish(&trace);
+        assert!(broadcast.is_empty());
+    }
+}
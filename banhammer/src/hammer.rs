@@ -1,84 +1,294 @@
-use std::{fmt, path::Path};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
+    str::FromStr,
+    sync::Arc,
+    time::Instant,
+};
 
 use banhammer_grpc::grpc::{
-    authorization_server::Authorization, event::TagEntry, AddBanRequest, Decision, Event,
-    EventReply, EventRequest,
+    authorization_server::Authorization, event::TagEntry, BanItem, Decision, Event, EventReply,
+    EventRequest, RejectionRecord,
 };
 use bech32::{ToBase32, Variant};
-use chrono::{NaiveDateTime, Utc};
+use chrono::{DateTime, Utc};
 use regex::Regex;
-
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
+use tokio::sync::Mutex;
 use tonic::{Request, Response, Status};
+use tracing::debug;
 
+use crate::audit::AuditLog;
+use crate::config::Config;
+use crate::created_at::CreatedAtWindow;
+use crate::decisions::{self, DecisionBroadcast};
+use crate::history::{History, HistoryError};
+use crate::hits::HitCounters;
+use crate::limits::SizeLimits;
+use crate::metrics::Metrics;
+use crate::nip42::Nip42;
+use crate::nostr::{verify_event, NostrEvent};
+use crate::policy::{Check, Lockdown, SystemClock, Violation};
+use crate::pow::Pow;
+use crate::ratelimit::RateLimiter;
+use crate::rejection::{Rejection, RejectionLog};
+use crate::rejection_store::RejectionWriter;
+use crate::sources::SourceLoader;
+use crate::strikes::{self, Strikes, AUTO_SOURCE};
+use crate::validators::Validator;
+pub use banhammer_grpc::banlist::Ban;
 use banhammer_grpc::BanTypesEnum;
-use num_traits::FromPrimitive;
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Ban {
-    pub content: String,
-    pub regex: bool,
-    pub reason: Option<String>,
-    pub ban_type: BanTypesEnum,
-}
 
-impl From<AddBanRequest> for Ban {
-    fn from(value: AddBanRequest) -> Self {
-        Self {
-            content: value.content,
-            regex: value.regex,
-            reason: value.reason,
-            ban_type: FromPrimitive::from_i32(value.ban_type).unwrap(),
-        }
+/// Host of an `Origin` header, such as `relay.example` for
+/// `https://relay.example:443`.
+fn origin_host(origin: &str) -> &str {
+    let host = origin.split_once("://").map_or(origin, |(_, rest)| rest);
+    let host = host.split('/').next().unwrap_or_default();
+    match host.rsplit_once(':') {
+        Some((host, port)) if port.bytes().all(|b| b.is_ascii_digit()) => host,
+        _ => host,
     }
 }
 
-#[derive(Debug, Clone)]
+/// Short identifier of a rule, derived from its type, content and regex
+/// flag. Duplicate bans share the same id.
+pub fn rule_id(ban: &Ban) -> String {
+    let digest = Sha256::new()
+        .chain_update(ban.ban_type.to_string())
+        .chain_update([ban.regex as u8])
+        .chain_update(&ban.content)
+        .finalize();
+    hex::encode(&digest[..6])
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct BanHammer {
     pub words: Vec<Ban>,
     pub tags: Vec<Ban>,
     pub users: Vec<Ban>,
     pub ips: Vec<Ban>,
+    /// Matched against the HTTP `Origin` header of the client
+    pub origins: Vec<Ban>,
+    /// Matched against the HTTP `User-Agent` header of the client
+    pub user_agents: Vec<Ban>,
+    /// Sources that can't be modified through the admin API,
+    /// such as remote ban lists.
+    pub read_only_sources: HashSet<String>,
+    /// Revisions of the ban set, see `commit`.
+    pub history: History,
+    pub hits: HitCounters,
+    /// Expiration dates of temporary bans, keyed by rule id
+    pub expirations: BTreeMap<String, DateTime<Utc>>,
 }
 
-impl Default for BanHammer {
-    fn default() -> Self {
-        Self {
-            words: Vec::new(),
-            tags: Vec::new(),
-            users: Vec::new(),
-            ips: Vec::new(),
-        }
+impl BanHammer {
+    /// Creates a ban hammer from a comma separated list of ban list
+    /// files or directories. See `SourceLoader` for precedence rules.
+    /// Unreadable sources are reported and skipped.
+    pub fn new(banlist: &str) -> Self {
+        let paths = SourceLoader::split(banlist);
+        let mut banhammer = Self::default();
+        banhammer.set_bans(SourceLoader::load(&paths));
+        banhammer.commit("startup");
+        banhammer
     }
-}
 
-impl BanHammer {
-    pub fn new(path: &str) -> Self {
-        let path = Path::new(path);
-        Self::default().load(path)
-    }
-
-    // Will silently fail if file is not readible.
-    // To do : Add panic! instead.
-    pub fn load(mut self, path: &Path) -> BanHammer {
-        // Load file
-        let file = match std::fs::File::open(path) {
-            Ok(file) => file,
-            Err(e) => {
-                println!("{}", e.to_string());
-                return self;
+    /// Records the current ban set as a new revision, if it changed.
+    /// Should be called after every change of the ban set.
+    pub fn commit(&mut self, author: &str) -> Option<u64> {
+        self.history.record(self.bans(), author)
+    }
+
+    /// Restores the bans of a previous revision, and records the result as
+    /// a new revision. Bans from read-only sources and temporary bans are
+    /// left as they are, since snapshots don't record expiration dates:
+    /// automatic bans of the revision aren't restored.
+    /// Returns the number of the new revision, if anything changed.
+    pub fn rollback(&mut self, revision: u64, author: &str) -> Result<Option<u64>, HistoryError> {
+        let snapshot = self.history.snapshot(revision)?;
+
+        let kept = |b: &Ban| self.is_read_only(b) || self.is_temporary(b);
+        let bans: Vec<Ban> = self
+            .bans()
+            .into_iter()
+            .filter(|b| kept(b))
+            .chain(
+                snapshot
+                    .iter()
+                    .filter(|b| !kept(b) && b.source.as_deref() != Some(AUTO_SOURCE))
+                    .cloned(),
+            )
+            .collect();
+
+        // Swap the whole ban set at once, the caller holds the lock
+        self.set_bans(bans);
+
+        Ok(self.commit(&format!("{} (rollback to {})", author, revision)))
+    }
+
+    /// Returns every ban, whatever its type.
+    pub fn bans(&self) -> Vec<Ban> {
+        [
+            self.words.clone(),
+            self.tags.clone(),
+            self.users.clone(),
+            self.ips.clone(),
+            self.origins.clone(),
+            self.user_agents.clone(),
+        ]
+        .concat()
+    }
+
+    /// Replaces the bans of a read-only source, such as a remote ban list.
+    /// Rules already declared by another source are skipped.
+    pub fn replace_source(&mut self, source: &str, bans: Vec<Ban>) {
+        self.read_only_sources.insert(source.to_string());
+
+        let mut all: Vec<Ban> = self
+            .bans()
+            .into_iter()
+            .filter(|b| b.source.as_deref() != Some(source))
+            .collect();
+
+        for ban in bans {
+            if !all.iter().any(|b| b.is_duplicate_of(&ban)) {
+                all.push(ban);
             }
+        }
+
+        self.set_bans(all);
+    }
+
+    /// Adds a ban, unless the same rule is already declared.
+    /// Returns whether the ban was added.
+    pub fn add(&mut self, ban: Ban) -> bool {
+        let list = match self.list_mut(ban.ban_type) {
+            Some(list) => list,
+            None => return false,
         };
 
-        // Parse file
-        let bans: Vec<Ban> = match serde_yaml::from_reader(file) {
-            Ok(r) => r,
-            Err(e) => {
-                println!("{}", e.to_string());
-                return self;
+        if list.iter().any(|b| b.is_duplicate_of(&ban)) {
+            return false;
+        }
+
+        list.push(ban);
+        true
+    }
+
+    /// Adds a ban removed by `remove_expired` once `expires` is past,
+    /// unless the same rule is already declared.
+    pub fn add_temporary(&mut self, ban: Ban, expires: DateTime<Utc>) -> bool {
+        let id = rule_id(&ban);
+        if !self.add(ban) {
+            return false;
+        }
+
+        self.expirations.insert(id, expires);
+        true
+    }
+
+    /// Whether a ban is removed once expired, see `add_temporary`.
+    pub fn is_temporary(&self, ban: &Ban) -> bool {
+        self.expirations.contains_key(&rule_id(ban))
+    }
+
+    /// Removes the temporary bans expired at `now`, and returns them.
+    pub fn remove_expired(&mut self, now: DateTime<Utc>) -> Vec<Ban> {
+        let expired: Vec<Ban> = self
+            .bans()
+            .into_iter()
+            .filter(|b| {
+                self.expirations
+                    .get(&rule_id(b))
+                    .is_some_and(|expires| *expires <= now)
+            })
+            .collect();
+
+        self.remove(&expired);
+        self.expirations.retain(|_, expires| *expires > now);
+        expired
+    }
+
+    /// Removes bans, whatever their reason, date or source. Temporary bans
+    /// are forgotten, so that the same rule added again doesn't expire.
+    pub fn remove(&mut self, bans: &[Ban]) {
+        for ban in bans {
+            if let Some(list) = self.list_mut(ban.ban_type) {
+                list.retain(|b| !b.is_duplicate_of(ban));
             }
-        };
+            self.expirations.remove(&rule_id(ban));
+        }
+    }
+
+    /// Bans without hits for `days` days, see `HitCounters::is_stale`.
+    /// Bans from read-only sources are left out since they can't be removed.
+    pub fn stale(&self, days: u32, now: DateTime<Utc>) -> Vec<Ban> {
+        self.bans()
+            .into_iter()
+            .filter(|b| !self.is_read_only(b) && self.hits.is_stale(b, days, now))
+            .collect()
+    }
+
+    /// Records the hits of the bans rejecting an event.
+    pub fn record_hits(&mut self, rejections: &[Rejection]) {
+        let now = Utc::now();
+        for ban in rejections.iter().filter_map(|r| r.ban.as_ref()) {
+            self.hits.record(ban, now);
+        }
+    }
+
+    /// Describes a ban along with its hits.
+    pub fn item(&self, ban: &Ban) -> BanItem {
+        let mut item = BanItem::from(ban);
+        if let Some(hits) = self.hits.get(ban) {
+            let format = |date: DateTime<Utc>| date.format("%Y-%m-%dT%H:%M:%S").to_string();
+            item.hits = hits.count;
+            item.first_hit = hits.first_hit.map(format);
+            item.last_hit = hits.last_hit.map(format);
+        }
+        item.expires = self
+            .expirations
+            .get(&rule_id(ban))
+            .map(|date| date.format("%Y-%m-%dT%H:%M:%S").to_string());
+        item
+    }
 
+    /// The `limit` bans with the most hits, bans without hits left out.
+    pub fn top_rules(&self, limit: usize) -> Vec<Ban> {
+        let mut bans: Vec<(u64, Ban)> = self
+            .bans()
+            .into_iter()
+            .filter_map(|ban| Some((self.hits.get(&ban)?.count, ban)))
+            .collect();
+
+        bans.sort_by_key(|(hits, _)| std::cmp::Reverse(*hits));
+        bans.into_iter().take(limit).map(|(_, ban)| ban).collect()
+    }
+
+    pub fn is_read_only(&self, ban: &Ban) -> bool {
+        match &ban.source {
+            Some(source) => self.read_only_sources.contains(source),
+            None => false,
+        }
+    }
+
+    /// Returns the ban list matching a ban type.
+    pub fn list_mut(&mut self, ban_type: BanTypesEnum) -> Option<&mut Vec<Ban>> {
+        match ban_type {
+            BanTypesEnum::CONTENT => Some(&mut self.words),
+            BanTypesEnum::TAG => Some(&mut self.tags),
+            BanTypesEnum::USER => Some(&mut self.users),
+            BanTypesEnum::IP => Some(&mut self.ips),
+            BanTypesEnum::ORIGIN => Some(&mut self.origins),
+            BanTypesEnum::USER_AGENT => Some(&mut self.user_agents),
+            _ => None,
+        }
+    }
+
+    // Dispatches bans in their respective lists
+    fn set_bans(&mut self, bans: Vec<Ban>) {
         // Extract content bans
         let ban_words: Vec<Ban> = bans
             .clone()
@@ -111,190 +321,408 @@ impl BanHammer {
             .cloned()
             .collect();
 
+        // Extract client bans
+        let ban_origins: Vec<Ban> = bans
+            .iter()
+            .filter(|b| b.ban_type == BanTypesEnum::ORIGIN)
+            .cloned()
+            .collect();
+        let ban_user_agents: Vec<Ban> = bans
+            .iter()
+            .filter(|b| b.ban_type == BanTypesEnum::USER_AGENT)
+            .cloned()
+            .collect();
+
         self.words = ban_words;
         self.tags = ban_tags;
         self.ips = ban_ips;
         self.users = ban_users;
+        self.origins = ban_origins;
+        self.user_agents = ban_user_agents;
+    }
 
-        self
+    pub fn invalidate_ip(&self, ip: Option<String>) -> bool {
+        match ip {
+            Some(ip) => self.find_ip(&ip).is_some(),
+            None => true,
+        }
     }
 
-    // Helper to format stdout display.
-    fn rejection_log_prompt(
-        &self,
-        ban_type: BanTypesEnum,
-        event: Event,
-        reply: EventReply,
-        ip: String,
-    ) -> () {
-        let id = hex::encode(event.id).as_str().to_string();
-
-        let created_at = event.created_at * 1000;
-        let datetime = match NaiveDateTime::from_timestamp_millis(created_at as i64) {
-            Some(v) => v,
-            None => Utc::now().naive_utc(),
-        };
+    pub fn invalidate_content(&self, content: String) -> bool {
+        self.find_content(&content).is_some()
+    }
 
-        let pubkey = match bech32::encode("npub", event.pubkey.to_base32(), Variant::Bech32) {
-            Ok(v) => v,
-            Err(_) => "".to_string(),
-        };
+    pub fn invalidate_user(&self, user: Vec<u8>) -> bool {
+        // A nostr public key is always 32 bytes long.
+        if user.len() != 32 {
+            return true;
+        }
 
-        println!("REJECTED | {} | {{\"event\": {} , \"ip\" : \"{}\" , \"type\": \"{}\", \"pubkey\": \"{}\" }}",
-                datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
-                id,
-                ip,
-                ban_type,
-                pubkey
-                );
+        self.find_user(&user).is_some()
     }
 
-    pub fn invalidate_ip(&self, ip: Option<String>) -> bool {
-        if ip.is_none() {
-            return true;
-        };
+    pub fn invalidate_tags(&self, tags: Vec<TagEntry>) -> bool {
+        self.find_tag(&tags).is_some()
+    }
 
-        let ip = ip.unwrap();
+    // Clients without the header can't match any ban
+    pub fn invalidate_origin(&self, origin: Option<&str>) -> bool {
+        origin.is_some_and(|origin| self.find_origin(origin).is_some())
+    }
 
-        self.find_in_list(self.ips.clone(), ip)
+    pub fn invalidate_user_agent(&self, user_agent: Option<&str>) -> bool {
+        user_agent.is_some_and(|user_agent| self.find_user_agent(user_agent).is_some())
     }
 
-    pub fn invalidate_content(&self, content: String) -> bool {
-        self.find_in_list(self.words.clone(), content)
+    // The find methods return the ban matching the provided value, if any.
+
+    pub fn find_ip(&self, ip: &str) -> Option<&Ban> {
+        self.find_in_list(&self.ips, ip)
     }
 
-    pub fn invalidate_user(&self, user: Vec<u8>) -> bool {
+    pub fn find_content(&self, content: &str) -> Option<&Ban> {
+        self.find_in_list(&self.words, content)
+    }
+
+    pub fn find_user(&self, user: &[u8]) -> Option<&Ban> {
         match bech32::encode("npub", user.to_base32(), Variant::Bech32) {
-            Ok(user) => self.find_in_list(self.users.clone(), user),
-            Err(_) => {
-                return true;
-            }
+            Ok(user) => self.find_in_list(&self.users, &user),
+            Err(_) => None,
         }
     }
 
-    pub fn invalidate_tags(&self, tags: Vec<TagEntry>) -> bool {
-        for tag in tags {
-            for value in tag.values {
-                let r = &self.tags.iter().find(|b| &b.content == &value);
+    pub fn find_tag(&self, tags: &[TagEntry]) -> Option<&Ban> {
+        tags.iter()
+            .flat_map(|tag| tag.values.iter())
+            .find_map(|value| self.tags.iter().find(|b| &b.content == value))
+    }
 
-                if r.is_some() {
-                    return true;
-                }
+    // Regex bans match origins like content. Plain bans match the same
+    // origin or host, case insensitive.
+    pub fn find_origin(&self, origin: &str) -> Option<&Ban> {
+        let host = origin_host(origin);
+        self.origins.iter().find(|b| match b.regex {
+            true => self.matches(b, origin),
+            false => {
+                !b.content.is_empty()
+                    && (b.content.eq_ignore_ascii_case(origin)
+                        || b.content.eq_ignore_ascii_case(host))
             }
-        }
+        })
+    }
 
-        return false;
+    // Regex bans match user agents like content. Plain bans match the
+    // user agents containing them, such as a product name.
+    pub fn find_user_agent(&self, user_agent: &str) -> Option<&Ban> {
+        self.user_agents.iter().find(|b| match b.regex {
+            true => self.matches(b, user_agent),
+            false => !b.content.is_empty() && user_agent.contains(&b.content),
+        })
     }
 
     // Takes a ban list and performs check for content validation
-    fn find_in_list(&self, list: Vec<Ban>, content: String) -> bool {
-        match list.iter().find(|b| {
-            if b.regex == false {
-                return b.content.contains(&content);
-                // return content == b.content;
-            }
+    fn find_in_list<'a>(&self, list: &'a [Ban], content: &str) -> Option<&'a Ban> {
+        list.iter().find(|b| self.matches(b, content))
+    }
 
-            let regex = Regex::new(&b.content);
+    // Whether a ban matches a value, in the match mode the ban declares.
+    fn matches(&self, b: &Ban, content: &str) -> bool {
+        if !b.regex {
+            return b.content.contains(content);
+            // return content == b.content;
+        }
 
-            if regex.is_err() {
-                return false;
-            }
+        let regex = Regex::new(&b.content);
 
-            self.regex_search(regex.unwrap(), content.clone())
-        }) {
-            Some(_) => true,
-            None => false,
+        if regex.is_err() {
+            return false;
         }
+
+        self.regex_search(regex.unwrap(), content.to_string())
     }
 
     // Helper method to perform check when ban is a regex.
     fn regex_search(&self, regex: Regex, content: String) -> bool {
-        match regex.captures(&content) {
-            Some(_) => {
-                return true;
-            }
-            None => {
-                return false;
-            }
+        regex.is_match(&content)
+    }
+}
+
+impl BanHammer {
+    /// Finds the bans rejecting an event, at most one per ban type.
+    /// Events without a client IP or a valid pubkey are always rejected.
+    pub fn rejections(&self, req: &EventRequest) -> Vec<Rejection> {
+        // Presence of the event is checked by the authorization service
+        let event = req.event.clone().unwrap_or_default();
+        let ip = req.ip_addr.clone().unwrap_or_default();
+        let mut rejections = Vec::new();
+
+        if self.invalidate_ip(req.ip_addr.clone()) {
+            let ban = req.ip_addr.as_deref().and_then(|ip| self.find_ip(ip));
+            rejections.push(Rejection::new(BanTypesEnum::IP, ban, &event, &ip));
+        }
+
+        if self.invalidate_content(event.content.clone()) {
+            let ban = self.find_content(&event.content);
+            rejections.push(Rejection::new(BanTypesEnum::CONTENT, ban, &event, &ip));
+        }
+
+        if self.invalidate_user(event.pubkey.clone()) {
+            let ban = self.find_user(&event.pubkey);
+            rejections.push(Rejection::new(BanTypesEnum::USER, ban, &event, &ip));
+        }
+
+        if self.invalidate_tags(event.tags.clone()) {
+            let ban = self.find_tag(&event.tags);
+            rejections.push(Rejection::new(BanTypesEnum::TAG, ban, &event, &ip));
+        }
+
+        let origin = req.origin.as_deref();
+        if self.invalidate_origin(origin) {
+            let ban = origin.and_then(|origin| self.find_origin(origin));
+            rejections.push(Rejection::new(BanTypesEnum::ORIGIN, ban, &event, &ip));
+        }
+
+        let user_agent = req.user_agent.as_deref();
+        if self.invalidate_user_agent(user_agent) {
+            let ban = user_agent.and_then(|user_agent| self.find_user_agent(user_agent));
+            rejections.push(Rejection::new(BanTypesEnum::USER_AGENT, ban, &event, &ip));
+        }
+
+        for rejection in &mut rejections {
+            rejection.origin = req.origin.clone();
+            rejection.user_agent = req.user_agent.clone();
+        }
+        rejections
+    }
+}
+
+/// What the authorization service does with rejected events.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyMode {
+    /// Rejected events are denied
+    #[default]
+    Enforce,
+    /// Rejected events are logged and counted, but permitted,
+    /// to try new rules without dropping events
+    Monitor,
+}
+
+impl FromStr for PolicyMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "enforce" => Ok(PolicyMode::Enforce),
+            "monitor" => Ok(PolicyMode::Monitor),
+            _ => Err(format!("unknown policy mode: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for PolicyMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PolicyMode::Enforce => write!(f, "enforce"),
+            PolicyMode::Monitor => write!(f, "monitor"),
         }
     }
 }
 
+/// Authorization service. The ban hammer is shared with the admin service
+/// and the remote sources so that their updates apply to the next events.
+pub struct Authz {
+    pub banhammer: Arc<Mutex<BanHammer>>,
+    pub rejection_log: Arc<RejectionLog>,
+    pub metrics: Arc<Metrics>,
+    pub mode: PolicyMode,
+    /// Decisions are traced to the `StreamDecisions` subscribers
+    pub decisions: Arc<DecisionBroadcast>,
+    /// Denied decisions are stored there, when enabled
+    pub rejection_store: Option<RejectionWriter>,
+    pub rate_limiter: Arc<RateLimiter>,
+    /// Authentication requirements, and the pubkey of allowlists
+    pub nip42: Nip42,
+    /// Whether event ids and signatures are checked
+    pub verify_events: bool,
+    /// Checked before anything else
+    pub size_limits: SizeLimits,
+    /// Malformed tags and content are denied
+    pub validator: Validator,
+    pub created_at: CreatedAtWindow,
+    pub pow: Pow,
+    /// Tightens the checks while active
+    pub lockdown: Arc<Lockdown>,
+    /// Repeat offenders are banned for a while
+    pub strikes: Arc<Strikes>,
+    /// Bans created by strikes are recorded there
+    pub audit: Arc<Mutex<AuditLog>>,
+}
+
+impl Authz {
+    /// Creates the service with the checks of a configuration. The state
+    /// shared with the other services, such as the metrics, the lockdown or
+    /// the audit log, starts fresh and can be replaced afterwards.
+    pub fn new(banhammer: Arc<Mutex<BanHammer>>, config: &Config) -> Self {
+        let clock = Arc::new(SystemClock);
+
+        Self {
+            banhammer,
+            rejection_log: Arc::new(RejectionLog::from_settings(&config.logging.rejections)),
+            metrics: Default::default(),
+            mode: config.policy.mode,
+            decisions: Default::default(),
+            rejection_store: None,
+            rate_limiter: Arc::new(RateLimiter::new(&config.rate_limits, clock.clone())),
+            nip42: Nip42::new(&config.nip42),
+            verify_events: config.policy.verify_events,
+            size_limits: SizeLimits::new(&config.size_limits),
+            validator: Validator::new(&config.conformance),
+            created_at: CreatedAtWindow::new(&config.created_at, clock),
+            pow: Pow::new(&config.pow),
+            lockdown: Default::default(),
+            strikes: Arc::new(Strikes::new(&config.strikes)),
+            audit: Default::default(),
+        }
+    }
+
+    /// Runs the checks of an event not rejected by a ban, and returns the
+    /// first one it fails. The rate limit comes last since it takes a token.
+    /// Sizes and signatures are checked before the bans, see `event_admit`.
+    fn check(
+        &self,
+        event: &Event,
+        ip: Option<&str>,
+        auth_pubkey: Option<&[u8]>,
+    ) -> Option<Violation> {
+        let violation =
+            |check, message: Option<String>| message.map(|message| Violation::new(check, message));
+        let pubkey = self.nip42.identity(event, auth_pubkey);
+
+        violation(Check::Auth, self.nip42.check(event, auth_pubkey))
+            .or_else(|| {
+                let nonconformity = self.validator.check(event);
+                let message = nonconformity.map(|n| format!("invalid: {} ({})", n.message, n.nip));
+                violation(Check::Conformance, message)
+            })
+            .or_else(|| violation(Check::CreatedAt, self.created_at.check(event)))
+            .or_else(|| {
+                let lockdown = self.lockdown.is_active();
+                violation(Check::Pow, self.pow.check(event, pubkey, lockdown))
+            })
+            .or_else(|| {
+                let limited = self.rate_limiter.check(pubkey, ip, event.kind);
+                violation(Check::RateLimit, limited)
+            })
+    }
+
+    /// Checks the id and the signature of an event, when enabled.
+    fn verify(&self, event: &Event) -> Option<String> {
+        if !self.verify_events {
+            return None;
+        }
+
+        verify_event(event).err().map(|e| format!("invalid: {}", e))
+    }
+}
+
 #[tonic::async_trait]
-impl Authorization for BanHammer {
+impl Authorization for Authz {
     async fn event_admit(
         &self,
         request: Request<EventRequest>,
     ) -> Result<Response<EventReply>, Status> {
-        let mut reply;
-        let req: EventRequest = request.into_inner();
-        let event = req.event.unwrap();
-
-        reply = EventReply {
-            decision: Decision::Permit as i32,
-            message: None,
+        let req = request.into_inner();
+        let Some(event) = req.event.as_ref() else {
+            return Err(Status::invalid_argument("missing event"));
         };
+        let ip = req.ip_addr.as_deref();
 
-        // let banhammer = BanHammer::new(&self.dict.as_str());
-
-        if self.invalidate_ip(req.ip_addr.clone()) == true {
-            reply = EventReply {
-                decision: Decision::Deny as i32,
-                message: None,
-            };
+        let start = Instant::now();
+        // Oversized events are denied before any expensive matching, and
+        // forged ones before their pubkey is charged with hits and strikes
+        let invalid = self
+            .size_limits
+            .check(event)
+            .map(|message| Violation::new(Check::Size, message))
+            .or_else(|| {
+                self.verify(event)
+                    .map(|message| Violation::new(Check::Signature, message))
+            });
+        let rejections = match invalid {
+            Some(_) => vec![],
+            None => {
+                let mut banhammer = self.banhammer.lock().await;
+                let rejections = banhammer.rejections(&req);
+                banhammer.record_hits(&rejections);
+                rejections
+            }
+        };
+        // Rejected events don't take rate limit tokens
+        let violation = invalid.or_else(|| match rejections.is_empty() {
+            true => self.check(event, ip, req.auth_pubkey.as_deref()),
+            false => None,
+        });
+        let duration = start.elapsed();
 
-            _ = &self.rejection_log_prompt(
-                BanTypesEnum::IP,
-                event.clone(),
-                reply.clone(),
-                req.ip_addr.clone().unwrap_or("".to_string()),
-            );
+        match &violation {
+            Some(violation) => {
+                debug!(
+                    check = %violation.check,
+                    ip = %self.rejection_log.hash(ip.unwrap_or_default()),
+                    "{}",
+                    violation.message
+                );
+                self.metrics
+                    .record_violation(violation, &event.pubkey, ip, duration);
+                self.rejection_log.record_violation(violation, &req);
+            }
+            None => self.metrics.record_decision(&rejections, duration),
         }
-
-        if self.invalidate_content(event.content.clone()) == true {
-            reply = EventReply {
-                decision: Decision::Deny as i32,
-                message: None,
-            };
-
-            _ = &self.rejection_log_prompt(
-                BanTypesEnum::CONTENT,
-                event.clone(),
-                reply.clone(),
-                req.ip_addr.clone().unwrap_or("".to_string()),
-            );
+        for rejection in &rejections {
+            self.rejection_log.record(rejection);
         }
 
-        let pubkey = event.pubkey.clone();
-        if self.invalidate_user(pubkey) == true {
-            reply = EventReply {
-                decision: Decision::Deny as i32,
-                message: None,
-            };
-
-            _ = &self.rejection_log_prompt(
-                BanTypesEnum::USER,
-                event.clone(),
-                reply.clone(),
-                req.ip_addr.clone().unwrap_or("".to_string()),
-            );
+        let rejected = !rejections.is_empty() || violation.is_some();
+        let decision = match (rejected, self.mode) {
+            (false, _) | (true, PolicyMode::Monitor) => Decision::Permit,
+            (true, PolicyMode::Enforce) => Decision::Deny,
+        };
+
+        if decision == Decision::Deny {
+            // Unverified pubkeys may be forged, only their IP gets a strike
+            let pubkey = self.verify_events.then_some(event.pubkey.as_slice());
+            let bans = self
+                .strikes
+                .strike(&rejections, violation.as_ref(), pubkey, ip, Utc::now());
+            if !bans.is_empty() {
+                strikes::apply(&self.banhammer, &self.audit, &self.rejection_log, bans).await;
+            }
         }
 
-        if self.invalidate_tags(event.tags.clone()) == true {
-            reply = EventReply {
-                decision: Decision::Deny as i32,
-                message: None,
-            };
-
-            _ = &self.rejection_log_prompt(
-                BanTypesEnum::TAG,
-                event.clone(),
-                reply.clone(),
-                req.ip_addr.clone().unwrap_or("".to_string()),
-            );
+        let store = self.rejection_store.as_ref().filter(|_| rejected);
+        if store.is_some() || !self.decisions.is_empty() {
+            let mut trace =
+                decisions::trace(event, ip, &rejections, decision == Decision::Deny, duration);
+            trace.message = violation.as_ref().map(|v| v.message.clone());
+            self.decisions.publish(&trace);
+
+            if let Some(store) = store {
+                store.record(RejectionRecord {
+                    decision: Some(trace),
+                    event: store
+                        .store_events()
+                        .then(|| NostrEvent::from(event).to_json()),
+                });
+            }
         }
 
-        Ok(Response::new(reply))
+        Ok(Response::new(EventReply {
+            decision: decision as i32,
+            message: violation
+                .filter(|_| decision == Decision::Deny)
+                .map(|v| v.message),
+        }))
     }
 }
 
@@ -308,18 +736,24 @@ mod tests {
                 content: "".to_string(),
                 regex: false,
                 reason: None,
+                date: None,
+                source: None,
                 ban_type: BanTypesEnum::CONTENT,
             },
             Ban {
                 content: "".to_string(),
                 regex: false,
                 reason: None,
+                date: None,
+                source: None,
                 ban_type: BanTypesEnum::IP,
             },
             Ban {
                 content: "1.2.3.4".to_string(),
                 regex: false,
                 reason: None,
+                date: None,
+                source: None,
                 ban_type: BanTypesEnum::IP,
             },
         ]
@@ -338,10 +772,12 @@ mod tests {
 
     #[test]
     fn test_content_invalidation() {
-        let mut ban = Ban {
+        let ban = Ban {
             content: "test".to_string(),
             regex: false,
             reason: Some("test reason".to_string()),
+            date: None,
+            source: None,
             ban_type: BanTypesEnum::CONTENT,
         };
         let banhammer = BanHammer {
@@ -349,21 +785,24 @@ mod tests {
             tags: [].to_vec(),
             users: [].to_vec(),
             ips: [].to_vec(),
+            ..Default::default()
         };
 
         let result = banhammer.invalidate_content("test".to_string());
-        assert_eq!(true, result);
+        assert!(result);
 
         let result = banhammer.invalidate_content("lipsum".to_string());
-        assert_eq!(false, result);
+        assert!(!result);
     }
 
     #[test]
     fn test_user_invalidation() {
-        let mut ban = Ban {
+        let ban = Ban {
             content: "npub1234".to_string(),
             regex: false,
             reason: Some("test reason".to_string()),
+            date: None,
+            source: None,
             ban_type: BanTypesEnum::USER,
         };
         let banhammer = BanHammer {
@@ -371,18 +810,21 @@ mod tests {
             tags: [].to_vec(),
             users: [ban].to_vec(),
             ips: [].to_vec(),
+            ..Default::default()
         };
 
         let result = banhammer.invalidate_user("test".into());
-        assert_eq!(true, result);
+        assert!(result);
     }
 
     #[test]
     fn test_ip_invalidation() {
-        let mut ban = Ban {
+        let ban = Ban {
             content: "127.0.0.1".to_string(),
             regex: false,
             reason: Some("test reason".to_string()),
+            date: None,
+            source: None,
             ban_type: BanTypesEnum::IP,
         };
         let banhammer = BanHammer {
@@ -390,15 +832,168 @@ mod tests {
             tags: [].to_vec(),
             users: [].to_vec(),
             ips: [ban].to_vec(),
+            ..Default::default()
+        };
+
+        assert!(banhammer.invalidate_ip(Some("127.0.0.1".to_string())));
+        assert!(!banhammer.invalidate_ip(Some("192.168.0.1".to_string())));
+    }
+
+    #[test]
+    fn test_replace_source() {
+        let ban = Ban {
+            content: "spam".to_string(),
+            regex: false,
+            reason: None,
+            date: None,
+            source: Some("bans.yaml".to_string()),
+            ban_type: BanTypesEnum::CONTENT,
+        };
+        let mut banhammer = BanHammer {
+            words: [ban.clone()].to_vec(),
+            ..Default::default()
+        };
+
+        let remote = "https://example.org/bans.yaml";
+        let mut duplicate = ban.clone();
+        duplicate.source = Some(remote.to_string());
+        let mut scam = duplicate.clone();
+        scam.content = "scam".to_string();
+
+        banhammer.replace_source(remote, [duplicate, scam.clone()].to_vec());
+        assert_eq!(banhammer.words.len(), 2);
+        assert!(!banhammer.is_read_only(&banhammer.words[0]));
+        assert!(banhammer.is_read_only(&banhammer.words[1]));
+
+        // A refresh replaces the previous copy
+        banhammer.replace_source(remote, [].to_vec());
+        assert_eq!(banhammer.words, [ban].to_vec());
+    }
+
+    #[test]
+    fn test_rollback_temporary() {
+        let manual = Ban {
+            content: "npub1manual".to_string(),
+            regex: false,
+            reason: None,
+            date: None,
+            source: Some("admin".to_string()),
+            ban_type: BanTypesEnum::USER,
+        };
+        let auto = Ban {
+            content: "npub1auto".to_string(),
+            source: Some(AUTO_SOURCE.to_string()),
+            ..manual.clone()
         };
+        let now = Utc::now();
+        let mut banhammer = BanHammer::default();
+        banhammer.add(manual.clone());
+        assert_eq!(banhammer.commit("startup"), Some(1));
+
+        banhammer.add_temporary(auto.clone(), now + chrono::Duration::hours(1));
+        assert_eq!(banhammer.commit("system"), Some(2));
+        banhammer.remove_expired(now + chrono::Duration::hours(1));
+        assert_eq!(banhammer.commit("system"), Some(3));
+
+        // Expired automatic bans aren't restored as permanent ones
+        assert_eq!(banhammer.rollback(2, "alice"), Ok(None));
+        assert_eq!(banhammer.users, [manual.clone()].to_vec());
+
+        // Active ones are kept, along with their expiration date
+        banhammer.add_temporary(auto.clone(), now + chrono::Duration::hours(1));
+        banhammer.commit("system");
+        banhammer.remove(std::slice::from_ref(&manual));
+        banhammer.commit("alice");
+        assert!(banhammer.rollback(1, "alice").unwrap().is_some());
+        assert_eq!(banhammer.users, [auto.clone(), manual].to_vec());
+        assert!(banhammer.is_temporary(&auto));
+    }
+
+    #[test]
+    fn test_remove_temporary() {
+        let auto = Ban {
+            content: "npub1auto".to_string(),
+            regex: false,
+            reason: Some("auto: 3 strikes".to_string()),
+            date: None,
+            source: Some(AUTO_SOURCE.to_string()),
+            ban_type: BanTypesEnum::USER,
+        };
+        let manual = Ban {
+            reason: None,
+            source: Some("admin".to_string()),
+            ..auto.clone()
+        };
+        let now = Utc::now();
+        let mut banhammer = BanHammer::default();
+        assert!(banhammer.add_temporary(auto.clone(), now + chrono::Duration::hours(1)));
+
+        // Lifted early, then banned for good
+        banhammer.remove(&[auto]);
+        assert!(banhammer.add(manual.clone()));
+        assert!(!banhammer.is_temporary(&manual));
+
+        assert!(banhammer
+            .remove_expired(now + chrono::Duration::hours(1))
+            .is_empty());
+        assert_eq!(banhammer.users, [manual].to_vec());
+    }
+
+    #[test]
+    fn test_rollback() {
+        let ban = Ban {
+            content: "spam".to_string(),
+            regex: false,
+            reason: None,
+            date: None,
+            source: Some("admin".to_string()),
+            ban_type: BanTypesEnum::CONTENT,
+        };
+        let mut banhammer = BanHammer {
+            words: [ban.clone()].to_vec(),
+            ..Default::default()
+        };
+        assert_eq!(banhammer.commit("startup"), Some(1));
+
+        // A bad bulk import, along with a remote list refresh
+        for i in 0..10 {
+            let mut imported = ban.clone();
+            imported.content = format!("word{}", i);
+            banhammer.add(imported);
+        }
+        assert_eq!(banhammer.commit("import"), Some(2));
+
+        let remote = "https://example.org/bans.yaml";
+        let mut scam = ban.clone();
+        scam.content = "scam".to_string();
+        scam.source = Some(remote.to_string());
+        banhammer.replace_source(remote, [scam.clone()].to_vec());
+        assert_eq!(banhammer.commit("remote"), Some(3));
+
+        // Read-only bans are kept
+        assert_eq!(banhammer.rollback(1, "alice"), Ok(Some(4)));
+        assert_eq!(banhammer.words, [scam, ban].to_vec());
+        assert_eq!(
+            banhammer.history.get(4).unwrap().author,
+            "alice (rollback to 1)"
+        );
+        assert_eq!(banhammer.history.get(4).unwrap().removed.len(), 10);
+
+        assert_eq!(banhammer.rollback(1, "alice"), Ok(None));
+        assert_eq!(
+            banhammer.rollback(9, "alice"),
+            Err(HistoryError::UnknownRevision(9))
+        );
     }
 
     #[test]
     fn test_tags_invalidation() {
-        let mut ban = Ban {
+        let ban = Ban {
             content: "banhammer".to_string(),
             regex: false,
             reason: Some("test reason".to_string()),
+            date: None,
+            source: None,
             ban_type: BanTypesEnum::IP,
         };
         let banhammer = BanHammer {
@@ -406,6 +1001,100 @@ mod tests {
             tags: [ban].to_vec(),
             users: [].to_vec(),
             ips: [].to_vec(),
+            ..Default::default()
+        };
+
+        let tags = vec![TagEntry {
+            values: vec!["t".to_string(), "banhammer".to_string()],
+        }];
+        assert!(banhammer.invalidate_tags(tags));
+
+        let tags = vec![TagEntry {
+            values: vec!["t".to_string(), "nostr".to_string()],
+        }];
+        assert!(!banhammer.invalidate_tags(tags));
+    }
+
+    #[test]
+    fn test_client_invalidation() {
+        let ban = |ban_type, content: &str, regex| Ban {
+            content: content.to_string(),
+            regex,
+            reason: None,
+            date: None,
+            source: None,
+            ban_type,
+        };
+        let mut banhammer = BanHammer::default();
+        banhammer.set_bans(vec![
+            ban(BanTypesEnum::ORIGIN, "https://spam.example", false),
+            ban(BanTypesEnum::ORIGIN, "scam.example", false),
+            ban(BanTypesEnum::USER_AGENT, "^python-requests/", true),
+            ban(BanTypesEnum::USER_AGENT, "HeadlessChrome", false),
+        ]);
+        assert_eq!(banhammer.bans().len(), 4);
+
+        assert!(banhammer.invalidate_origin(Some("https://spam.example")));
+        assert!(banhammer.invalidate_origin(Some("https://SPAM.example")));
+        assert!(banhammer.invalidate_origin(Some("http://scam.example:8080")));
+        assert!(banhammer.invalidate_origin(Some("https://Scam.Example")));
+        assert!(!banhammer.invalidate_origin(Some("https://spam.example.org")));
+        assert!(!banhammer.invalidate_origin(Some("https://nostr.example")));
+        // Used to match every ban containing the header
+        assert!(!banhammer.invalidate_origin(Some("https")));
+        assert!(!banhammer.invalidate_origin(Some("")));
+        assert!(!banhammer.invalidate_origin(None));
+
+        assert!(banhammer.invalidate_user_agent(Some("python-requests/2.31.0")));
+        assert!(banhammer.invalidate_user_agent(Some(
+            "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) \
+             HeadlessChrome/120.0.6099.109 Safari/537.36"
+        )));
+        assert!(!banhammer.invalidate_user_agent(Some(
+            "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) \
+             Chrome/120.0.0.0 Safari/537.36"
+        )));
+        assert!(!banhammer.invalidate_user_agent(Some("Chrome")));
+        assert!(!banhammer.invalidate_user_agent(Some("")));
+        assert!(!banhammer.invalidate_user_agent(None));
+
+        // Regex bans are matched like content bans
+        let mut regexes = BanHammer::default();
+        regexes.set_bans(vec![
+            ban(
+                BanTypesEnum::ORIGIN,
+                r"^https://([a-z0-9-]+\.)*spam\.example(:\d+)?$",
+                true,
+            ),
+            ban(BanTypesEnum::USER_AGENT, r"(?i)\b(curl|wget)/\d", true),
+        ]);
+        assert!(regexes.invalidate_origin(Some("https://relay.spam.example")));
+        assert!(regexes.invalidate_origin(Some("https://spam.example:8443")));
+        assert!(!regexes.invalidate_origin(Some("http://spam.example")));
+        assert!(!regexes.invalidate_origin(Some("https://spam.example.org")));
+        assert!(regexes.invalidate_user_agent(Some("curl/8.4.0")));
+        assert!(regexes.invalidate_user_agent(Some("Wget/1.21.4")));
+        assert!(!regexes.invalidate_user_agent(Some(
+            "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 \
+             (KHTML, like Gecko) Version/17.1 Safari/605.1.15"
+        )));
+
+        let request = EventRequest {
+            event: Some(Event {
+                pubkey: vec![1; 32],
+                ..Default::default()
+            }),
+            ip_addr: Some("1.2.3.4".to_string()),
+            origin: Some("https://nostr.example".to_string()),
+            user_agent: Some("python-requests/2.31.0".to_string()),
+            ..Default::default()
         };
+        let rejections = banhammer.rejections(&request);
+        assert_eq!(rejections.len(), 1);
+        assert_eq!(rejections[0].ban_type, BanTypesEnum::USER_AGENT);
+        assert_eq!(
+            rejections[0].origin.as_deref(),
+            Some("https://nostr.example")
+        );
     }
 }
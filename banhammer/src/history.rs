@@ -0,0 +1,220 @@
+use std::{collections::HashSet, fmt, sync::Arc};
+
+use chrono::Utc;
+
+use crate::hammer::{rule_id, Ban};
+
+/// Revisions kept by default, see `History::set_max_revisions`
+pub const DEFAULT_MAX_REVISIONS: usize = 1000;
+
+/// A numbered change of the ban set.
+#[derive(Debug, Clone)]
+pub struct Revision {
+    pub number: u64,
+    pub author: String,
+    pub timestamp: String,
+    pub added: Vec<Ban>,
+    pub removed: Vec<Ban>,
+    /// Every ban after this revision was applied
+    bans: Arc<Vec<Ban>>,
+}
+
+impl Revision {
+    pub fn bans(&self) -> Arc<Vec<Ban>> {
+        self.bans.clone()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum HistoryError {
+    UnknownRevision(u64),
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HistoryError::UnknownRevision(number) => write!(f, "unknown revision {}", number),
+        }
+    }
+}
+
+impl std::error::Error for HistoryError {}
+
+/// Computes the bans added and removed between two ban sets,
+/// comparing their rule ids.
+pub fn diff(old: &[Ban], new: &[Ban]) -> (Vec<Ban>, Vec<Ban>) {
+    let ids = |bans: &[Ban]| bans.iter().map(rule_id).collect::<HashSet<String>>();
+    let (old_ids, new_ids) = (ids(old), ids(new));
+
+    let added = new
+        .iter()
+        .filter(|b| !old_ids.contains(&rule_id(b)))
+        .cloned()
+        .collect();
+    let removed = old
+        .iter()
+        .filter(|b| !new_ids.contains(&rule_id(b)))
+        .cloned()
+        .collect();
+
+    (added, removed)
+}
+
+/// Every revision of the ban set, oldest first.
+///
+/// Each revision keeps a snapshot of the whole ban set, so any revision can
+/// be restored without replaying the ones before it. Revision 0 is the empty
+/// ban set, the first recorded revision is number 1. Only the latest
+/// revisions are kept, older ones are forgotten.
+#[derive(Debug, Clone)]
+pub struct History {
+    revisions: Vec<Revision>,
+    max_revisions: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            revisions: Vec::new(),
+            max_revisions: DEFAULT_MAX_REVISIONS,
+        }
+    }
+}
+
+impl History {
+    /// Sets the number of revisions kept, at least one.
+    pub fn set_max_revisions(&mut self, max_revisions: usize) {
+        self.max_revisions = max_revisions.max(1);
+        self.prune();
+    }
+
+    fn prune(&mut self) {
+        let excess = self.revisions.len().saturating_sub(self.max_revisions);
+        self.revisions.drain(..excess);
+    }
+
+    /// Records a new revision if the ban set changed since the last one.
+    /// Returns the number of the recorded revision.
+    pub fn record(&mut self, bans: Vec<Ban>, author: &str) -> Option<u64> {
+        let previous = self.snapshot(self.head()).unwrap_or_default();
+        let (added, removed) = diff(&previous, &bans);
+
+        if added.is_empty() && removed.is_empty() {
+            return None;
+        }
+
+        let number = self.head() + 1;
+        self.revisions.push(Revision {
+            number,
+            author: author.to_string(),
+            timestamp: Utc::now().format("%Y-%m-%dT%H:%M:%S").to_string(),
+            added,
+            removed,
+            bans: Arc::new(bans),
+        });
+        self.prune();
+
+        Some(number)
+    }
+
+    /// Number of the latest revision, 0 when nothing was recorded.
+    pub fn head(&self) -> u64 {
+        self.revisions.last().map(|r| r.number).unwrap_or(0)
+    }
+
+    pub fn revisions(&self) -> &[Revision] {
+        &self.revisions
+    }
+
+    pub fn get(&self, number: u64) -> Option<&Revision> {
+        self.revisions.iter().find(|r| r.number == number)
+    }
+
+    /// Returns the ban set as it was after a revision.
+    pub fn snapshot(&self, number: u64) -> Result<Arc<Vec<Ban>>, HistoryError> {
+        match number {
+            0 => Ok(Arc::default()),
+            number => self
+                .get(number)
+                .map(|r| r.bans())
+                .ok_or(HistoryError::UnknownRevision(number)),
+        }
+    }
+
+    /// Computes the bans added and removed between two revisions.
+    pub fn diff(&self, from: u64, to: u64) -> Result<(Vec<Ban>, Vec<Ban>), HistoryError> {
+        let from = self.snapshot(from)?;
+        let to = self.snapshot(to)?;
+
+        Ok(diff(&from, &to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use banhammer_grpc::BanTypesEnum;
+
+    fn ban(content: &str) -> Ban {
+        Ban {
+            content: content.to_string(),
+            regex: false,
+            reason: None,
+            date: None,
+            ban_type: BanTypesEnum::CONTENT,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let mut history = History::default();
+        assert_eq!(history.head(), 0);
+
+        assert_eq!(history.record(vec![ban("a")], "startup"), Some(1));
+        assert_eq!(history.record(vec![ban("a")], "admin"), None);
+        assert_eq!(history.record(vec![ban("a"), ban("b")], "admin"), Some(2));
+        assert_eq!(history.record(vec![ban("b")], "alice"), Some(3));
+
+        let revision = history.get(3).unwrap();
+        assert_eq!(revision.author, "alice");
+        assert!(revision.added.is_empty());
+        assert_eq!(revision.removed, vec![ban("a")]);
+        assert_eq!(history.revisions().len(), 3);
+    }
+
+    #[test]
+    fn test_snapshot_and_diff() {
+        let mut history = History::default();
+        history.record(vec![ban("a")], "startup");
+        history.record(vec![ban("a"), ban("b"), ban("c")], "import");
+        history.record(vec![ban("c"), ban("d")], "admin");
+
+        assert_eq!(*history.snapshot(1).unwrap(), vec![ban("a")]);
+        assert!(history.snapshot(0).unwrap().is_empty());
+        assert_eq!(history.snapshot(4), Err(HistoryError::UnknownRevision(4)));
+
+        let (added, removed) = history.diff(1, 3).unwrap();
+        assert_eq!(added, vec![ban("c"), ban("d")]);
+        assert_eq!(removed, vec![ban("a")]);
+
+        let (added, removed) = history.diff(3, 1).unwrap();
+        assert_eq!(added, vec![ban("a")]);
+        assert_eq!(removed, vec![ban("c"), ban("d")]);
+    }
+
+    #[test]
+    fn test_max_revisions() {
+        let mut history = History::default();
+        history.set_max_revisions(2);
+        for content in ["a", "b", "c"] {
+            history.record(vec![ban(content)], "admin");
+        }
+
+        assert_eq!(history.head(), 3);
+        let numbers: Vec<u64> = history.revisions().iter().map(|r| r.number).collect();
+        assert_eq!(numbers, vec![2, 3]);
+        assert_eq!(history.snapshot(1), Err(HistoryError::UnknownRevision(1)));
+        assert!(history.diff(2, 3).is_ok());
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::error;
+
+use crate::hammer::{rule_id, Ban, BanHammer};
+
+/// Hits of a rule.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RuleHits {
+    pub count: u64,
+    pub first_hit: Option<DateTime<Utc>>,
+    pub last_hit: Option<DateTime<Utc>>,
+}
+
+/// Hit counters of the rules, keyed by rule id. Counters survive reloads,
+/// rollbacks and restarts since rule ids only depend on the rule itself.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HitCounters {
+    /// When hits started being tracked
+    pub since: DateTime<Utc>,
+    pub rules: BTreeMap<String, RuleHits>,
+    /// Whether hits were recorded since the last save
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl Default for HitCounters {
+    fn default() -> Self {
+        Self {
+            since: Utc::now(),
+            rules: BTreeMap::new(),
+            dirty: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum HitsError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, serde_json::Error),
+}
+
+impl fmt::Display for HitsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HitsError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            HitsError::Parse(path, e) => write!(f, "{}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for HitsError {}
+
+/// Parses a ban date, written `YYYY-MM-DDTHH:MM:SS` (UTC) or RFC 3339.
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(date.with_timezone(&Utc));
+    }
+
+    NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S")
+        .ok()
+        .map(|date| date.and_utc())
+}
+
+impl HitCounters {
+    /// Loads hit counters saved with `save`. A missing file starts
+    /// tracking from now.
+    pub fn load(path: &Path) -> Result<Self, HitsError> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(HitsError::Io(path.into(), e)),
+        };
+
+        serde_json::from_str(&content).map_err(|e| HitsError::Parse(path.into(), e))
+    }
+
+    /// Saves the counters, if hits were recorded since the last save.
+    /// The file is replaced at once so a crash can't leave it truncated.
+    pub fn save(&mut self, path: &Path) -> Result<(), HitsError> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let mut temporary = path.to_path_buf().into_os_string();
+        temporary.push(".tmp");
+        let content = serde_json::to_string(self).map_err(|e| HitsError::Parse(path.into(), e))?;
+
+        fs::write(&temporary, content)
+            .and_then(|_| fs::rename(&temporary, path))
+            .map_err(|e| HitsError::Io(path.into(), e))?;
+
+        self.dirty = false;
+        Ok(())
+    }
+
+    pub fn record(&mut self, ban: &Ban, at: DateTime<Utc>) {
+        let hits = self.rules.entry(rule_id(ban)).or_default();
+        hits.count += 1;
+        hits.first_hit.get_or_insert(at);
+        hits.last_hit = Some(at);
+        self.dirty = true;
+    }
+
+    pub fn get(&self, ban: &Ban) -> Option<&RuleHits> {
+        self.rules.get(&rule_id(ban))
+    }
+
+    /// Whether a rule had no hit in the `days` before `now`. Rules that
+    /// never matched are compared using their date, or the start of the
+    /// tracking when it is more recent, so new rules are not stale.
+    pub fn is_stale(&self, ban: &Ban, days: u32, now: DateTime<Utc>) -> bool {
+        let reference = match self.get(ban).and_then(|hits| hits.last_hit) {
+            Some(last_hit) => last_hit,
+            None => ban
+                .date
+                .as_deref()
+                .and_then(parse_date)
+                .map_or(self.since, |date| date.max(self.since)),
+        };
+
+        now - reference > chrono::Duration::days(days as i64)
+    }
+}
+
+/// Saves the hit counters of the ban hammer periodically.
+pub async fn save_periodically(
+    banhammer: Arc<Mutex<BanHammer>>,
+    path: PathBuf,
+    interval: Duration,
+) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = banhammer.lock().await.hits.save(&path) {
+            error!("hit counters: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use banhammer_grpc::BanTypesEnum;
+
+    fn ban(content: &str, date: Option<&str>) -> Ban {
+        Ban {
+            content: content.to_string(),
+            regex: false,
+            reason: None,
+            date: date.map(|d| d.to_string()),
+            source: None,
+            ban_type: BanTypesEnum::CONTENT,
+        }
+    }
+
+    fn date(date: &str) -> DateTime<Utc> {
+        parse_date(date).unwrap()
+    }
+
+    #[test]
+    fn test_record_and_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hits.json");
+        let spam = ban("spam", None);
+
+        let mut hits = HitCounters::load(&path).unwrap();
+        hits.record(&spam, date("2024-01-01T00:00:00"));
+        hits.record(&spam, date("2024-01-02T00:00:00"));
+        // Reasons and dates don't change the rule
+        hits.record(
+            &ban("spam", Some("2023-01-01T00:00:00")),
+            date("2024-01-03T00:00:00"),
+        );
+
+        let recorded = hits.get(&spam).unwrap();
+        assert_eq!(recorded.count, 3);
+        assert_eq!(recorded.first_hit, Some(date("2024-01-01T00:00:00")));
+        assert_eq!(recorded.last_hit, Some(date("2024-01-03T00:00:00")));
+
+        hits.save(&path).unwrap();
+        assert_eq!(HitCounters::load(&path).unwrap(), hits);
+
+        // Nothing to save
+        fs::remove_file(&path).unwrap();
+        hits.save(&path).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_is_stale() {
+        let mut hits = HitCounters {
+            since: date("2024-01-01T00:00:00"),
+            ..Default::default()
+        };
+        let now = date("2024-06-01T00:00:00");
+
+        let hit = ban("hit", None);
+        hits.record(&hit, date("2024-05-01T00:00:00"));
+        assert!(!hits.is_stale(&hit, 60, now));
+        assert!(hits.is_stale(&hit, 15, now));
+
+        // Never hit, compared with the start of the tracking
+        assert!(hits.is_stale(&ban("old", Some("2020-01-01T00:00:00")), 60, now));
+        assert!(hits.is_stale(&ban("undated", None), 60, now));
+        assert!(!hits.is_stale(&ban("undated", None), 200, now));
+
+        // Never hit, but recently added
+        assert!(!hits.is_stale(&ban("new", Some("2024-05-20T00:00:00")), 60, now));
+        assert!(!hits.is_stale(&ban("new", Some("2024-05-20T00:00:00+00:00")), 60, now));
+    }
+}
@@ -0,0 +1,212 @@
+use banhammer_grpc::grpc::Event;
+use serde::{Deserialize, Serialize};
+
+use crate::policy::KindRange;
+
+/// Limits of some kinds, overriding the default ones.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SizeLimitKindSettings {
+    pub kinds: Vec<KindRange>,
+    pub max_content_bytes: Option<usize>,
+    pub max_content_chars: Option<usize>,
+    pub max_tags: Option<usize>,
+    pub max_tag_values: Option<usize>,
+    pub max_tag_value_bytes: Option<usize>,
+    pub max_p_tags: Option<usize>,
+    pub max_e_tags: Option<usize>,
+}
+
+/// Structural limits of events, each unlimited when unset.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SizeLimitSettings {
+    /// Length of the content, in bytes
+    pub max_content_bytes: Option<usize>,
+    /// Length of the content, in characters
+    pub max_content_chars: Option<usize>,
+    /// Number of tags
+    pub max_tags: Option<usize>,
+    /// Number of values of a tag, including its name
+    pub max_tag_values: Option<usize>,
+    /// Length of a tag value, in bytes
+    pub max_tag_value_bytes: Option<usize>,
+    /// Number of `p` tags, the mentioned pubkeys
+    pub max_p_tags: Option<usize>,
+    /// Number of `e` tags, the referenced events
+    pub max_e_tags: Option<usize>,
+    /// The first entry listing the kind of an event applies,
+    /// its unset limits falling back to the default ones
+    pub kinds: Vec<SizeLimitKindSettings>,
+}
+
+/// Number of tags of an event with a name.
+fn count_tags(event: &Event, name: &str) -> usize {
+    event
+        .tags
+        .iter()
+        .filter(|tag| tag.values.first().map(String::as_str) == Some(name))
+        .count()
+}
+
+/// Denies oversized events. Cheap enough to run before any other check.
+#[derive(Debug, Default)]
+pub struct SizeLimits {
+    default: SizeLimitKindSettings,
+    kinds: Vec<SizeLimitKindSettings>,
+}
+
+impl SizeLimits {
+    pub fn new(settings: &SizeLimitSettings) -> Self {
+        Self {
+            default: SizeLimitKindSettings {
+                kinds: vec![],
+                max_content_bytes: settings.max_content_bytes,
+                max_content_chars: settings.max_content_chars,
+                max_tags: settings.max_tags,
+                max_tag_values: settings.max_tag_values,
+                max_tag_value_bytes: settings.max_tag_value_bytes,
+                max_p_tags: settings.max_p_tags,
+                max_e_tags: settings.max_e_tags,
+            },
+            kinds: settings.kinds.clone(),
+        }
+    }
+
+    /// Returns why an event is too large, if it is.
+    pub fn check(&self, event: &Event) -> Option<String> {
+        let entry = self
+            .kinds
+            .iter()
+            .find(|k| k.kinds.iter().any(|range| range.contains(event.kind)));
+        let limit = |field: fn(&SizeLimitKindSettings) -> Option<usize>| {
+            entry.and_then(field).or_else(|| field(&self.default))
+        };
+        let exceeds = |field, count: &dyn Fn() -> usize| limit(field).filter(|max| count() > *max);
+
+        if let Some(max) = exceeds(|l| l.max_content_bytes, &|| event.content.len()) {
+            return Some(format!("invalid: content is longer than {} bytes", max));
+        }
+        if let Some(max) = exceeds(|l| l.max_content_chars, &|| event.content.chars().count()) {
+            return Some(format!(
+                "invalid: content is longer than {} characters",
+                max
+            ));
+        }
+        if let Some(max) = exceeds(|l| l.max_tags, &|| event.tags.len()) {
+            return Some(format!("invalid: more than {} tags", max));
+        }
+        if let Some(max) = exceeds(|l| l.max_tag_values, &|| {
+            event
+                .tags
+                .iter()
+                .map(|tag| tag.values.len())
+                .max()
+                .unwrap_or(0)
+        }) {
+            return Some(format!("invalid: a tag has more than {} values", max));
+        }
+        if let Some(max) = exceeds(|l| l.max_tag_value_bytes, &|| {
+            event
+                .tags
+                .iter()
+                .flat_map(|tag| tag.values.iter().map(String::len))
+                .max()
+                .unwrap_or(0)
+        }) {
+            return Some(format!("invalid: a tag value is longer than {} bytes", max));
+        }
+        if let Some(max) = exceeds(|l| l.max_p_tags, &|| count_tags(event, "p")) {
+            return Some(format!("invalid: more than {} p tags", max));
+        }
+        if let Some(max) = exceeds(|l| l.max_e_tags, &|| count_tags(event, "e")) {
+            return Some(format!("invalid: more than {} e tags", max));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use banhammer_grpc::grpc::event::TagEntry;
+
+    fn event(kind: u64, content: &str, tags: &[&[&str]]) -> Event {
+        Event {
+            kind,
+            content: content.to_string(),
+            tags: tags
+                .iter()
+                .map(|values| TagEntry {
+                    values: values.iter().map(|v| v.to_string()).collect(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let settings: SizeLimitSettings = toml::from_str(
+            r#"
+max_content_bytes = 8
+max_content_chars = 4
+max_tags = 3
+max_tag_values = 3
+max_tag_value_bytes = 8
+max_p_tags = 2
+
+[[kinds]]
+kinds = [3]
+max_tags = 5
+max_p_tags = 5
+"#,
+        )
+        .unwrap();
+        let limits = SizeLimits::new(&settings);
+
+        assert_eq!(limits.check(&event(1, "abcd", &[&["p", "a"]])), None);
+        assert_eq!(
+            limits.check(&event(1, "abcde", &[])),
+            Some("invalid: content is longer than 4 characters".to_string())
+        );
+        // 3 characters, 9 bytes
+        assert_eq!(
+            limits.check(&event(1, "€€€", &[])),
+            Some("invalid: content is longer than 8 bytes".to_string())
+        );
+        assert_eq!(
+            limits.check(&event(
+                1,
+                "",
+                &[&["t", "a"], &["t", "b"], &["t", "c"], &["t", "d"]]
+            )),
+            Some("invalid: more than 3 tags".to_string())
+        );
+        assert_eq!(
+            limits.check(&event(1, "", &[&["e", "a", "", "root"]])),
+            Some("invalid: a tag has more than 3 values".to_string())
+        );
+        assert_eq!(
+            limits.check(&event(1, "", &[&["t", "abcdefghi"]])),
+            Some("invalid: a tag value is longer than 8 bytes".to_string())
+        );
+        assert_eq!(
+            limits.check(&event(1, "", &[&["p", "a"], &["p", "b"], &["p", "c"]])),
+            Some("invalid: more than 2 p tags".to_string())
+        );
+
+        // Contact lists have more room, other limits still apply
+        let contacts = [&["p", "a"][..], &["p", "b"], &["p", "c"], &["p", "d"]];
+        assert_eq!(limits.check(&event(3, "", &contacts)), None);
+        assert!(limits.check(&event(3, "abcde", &contacts)).is_some());
+    }
+
+    #[test]
+    fn test_disabled() {
+        let limits = SizeLimits::new(&SizeLimitSettings::default());
+        let tags = vec![&["p", "a"][..]; 1000];
+        assert_eq!(limits.check(&event(1, &"a".repeat(100_000), &tags)), None);
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    error::Error,
+    fmt, fs,
+    net::SocketAddr,
+    os::unix::fs::{FileTypeExt, PermissionsExt},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use tokio::net::{TcpListener, UnixListener};
+use tokio_stream::wrappers::UnixListenerStream;
+use tonic::transport::server::Router;
+use tracing::{info, warn};
+
+use crate::tls::ReloadableTls;
+
+/// Where a gRPC service listens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddress {
+    Tcp(SocketAddr),
+    /// A Unix domain socket, written `unix:<path>`
+    Unix(PathBuf),
+    /// Written `off`
+    Disabled,
+}
+
+impl FromStr for ListenAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if let Some(path) = s.strip_prefix("unix:") {
+            return match path.is_empty() {
+                true => Err("missing Unix socket path".to_string()),
+                false => Ok(ListenAddress::Unix(PathBuf::from(path))),
+            };
+        }
+
+        match s.to_lowercase().as_str() {
+            "" | "off" | "disabled" => Ok(ListenAddress::Disabled),
+            _ => s
+                .parse()
+                .map(ListenAddress::Tcp)
+                .map_err(|_| format!("invalid listen address: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ListenAddress::Tcp(address) => write!(f, "{}", address),
+            ListenAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+            ListenAddress::Disabled => write!(f, "off"),
+        }
+    }
+}
+
+/// Parses octal Unix file permissions, such as `660` or `0o600`.
+pub fn parse_mode(s: &str) -> Result<u32, String> {
+    let s = s.trim();
+    let digits = s.strip_prefix("0o").unwrap_or(s);
+
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o777 => Ok(mode),
+        _ => Err(format!("invalid socket permissions: {}", s)),
+    }
+}
+
+/// Binds a Unix socket, replacing a stale socket left by a previous run,
+/// and restricts who can connect to it.
+fn bind_unix(path: &PathBuf, mode: u32) -> Result<UnixListener, Box<dyn Error>> {
+    if let Ok(metadata) = fs::symlink_metadata(path) {
+        match metadata.file_type().is_socket() {
+            true => fs::remove_file(path)?,
+            false => return Err(format!("{} exists and is not a socket", path.display()).into()),
+        }
+    }
+
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+
+    Ok(listener)
+}
+
+/// Serves a router on an address. Unix sockets get `mode` permissions,
+/// TCP listeners use TLS when configured.
+/// Returns right away for disabled addresses.
+pub async fn serve(
+    name: &str,
+    router: Router,
+    address: &ListenAddress,
+    mode: u32,
+    tls: Option<ReloadableTls>,
+) -> Result<(), Box<dyn Error>> {
+    match address {
+        ListenAddress::Tcp(address) => match tls {
+            Some(tls) => {
+                let listener = TcpListener::bind(address).await?;
+                info!("{} listening on {} (TLS)", name, address);
+                router.serve_with_incoming(tls.incoming(listener)).await?;
+            }
+            None => {
+                info!("{} listening on {}", name, address);
+                router.serve(*address).await?;
+            }
+        },
+        ListenAddress::Unix(path) => {
+            if tls.is_some() {
+                warn!("{}: TLS is not used on Unix sockets", name);
+            }
+            let listener = bind_unix(path, mode)?;
+            info!("{} listening on unix:{}", name, path.display());
+            router
+                .serve_with_incoming(UnixListenerStream::new(listener))
+                .await?;
+        }
+        ListenAddress::Disabled => info!("{} disabled", name),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(
+            "[::1]:50051".parse(),
+            Ok(ListenAddress::Tcp("[::1]:50051".parse().unwrap()))
+        );
+        assert_eq!(
+            "unix:/run/banhammer/admin.sock".parse(),
+            Ok(ListenAddress::Unix(PathBuf::from(
+                "/run/banhammer/admin.sock"
+            )))
+        );
+        assert_eq!("off".parse(), Ok(ListenAddress::Disabled));
+        assert!("unix:".parse::<ListenAddress>().is_err());
+        assert!("localhost".parse::<ListenAddress>().is_err());
+
+        let address: ListenAddress = "unix:admin.sock".parse().unwrap();
+        assert_eq!(address.to_string(), "unix:admin.sock");
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("660"), Ok(0o660));
+        assert_eq!(parse_mode("0o600"), Ok(0o600));
+        assert!(parse_mode("800").is_err());
+        assert!(parse_mode("7777").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bind_unix() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("admin.sock");
+
+        let listener = bind_unix(&path, 0o600).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        drop(listener);
+
+        // Stale sockets are replaced, other files are left alone
+        assert!(bind_unix(&path, 0o660).is_ok());
+        let file = dir.path().join("bans.yaml");
+        fs::write(&file, "bans: []").unwrap();
+        assert!(bind_unix(&file, 0o600).is_err());
+        assert!(file.exists());
+    }
+}
@@ -0,0 +1,128 @@
+use std::{fmt, str::FromStr};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::{field::Field, Event, Subscriber};
+use tracing_subscriber::{
+    field::Visit,
+    fmt::{format::Writer, FmtContext, FormatEvent, FormatFields},
+    registry::LookupSpan,
+    EnvFilter,
+};
+
+/// Output format of the logs.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Json,
+    #[default]
+    Logfmt,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(LogFormat::Json),
+            "logfmt" => Ok(LogFormat::Logfmt),
+            _ => Err(format!("unknown log format: {}", s)),
+        }
+    }
+}
+
+/// Quotes a logfmt value when needed.
+pub fn logfmt_value(value: &str) -> String {
+    let quote = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c.is_whitespace() || c == '=' || c == '"' || c.is_control());
+
+    match quote {
+        true => format!("{:?}", value),
+        false => value.to_string(),
+    }
+}
+
+/// Collects the fields of an event as logfmt pairs.
+#[derive(Default)]
+struct LogfmtVisitor {
+    message: String,
+    fields: Vec<String>,
+}
+
+impl Visit for LogfmtVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_string(),
+            name => self
+                .fields
+                .push(format!("{}={}", name, logfmt_value(value))),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value));
+    }
+}
+
+/// Formats events as logfmt lines.
+struct Logfmt;
+
+impl<S, N> FormatEvent<S, N> for Logfmt
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        _ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let mut visitor = LogfmtVisitor::default();
+        event.record(&mut visitor);
+
+        let metadata = event.metadata();
+        write!(
+            writer,
+            "ts={} level={} target={} msg={}",
+            Utc::now().to_rfc3339(),
+            metadata.level().as_str().to_lowercase(),
+            metadata.target(),
+            logfmt_value(&visitor.message)
+        )?;
+        for field in visitor.fields {
+            write!(writer, " {}", field)?;
+        }
+        writeln!(writer)
+    }
+}
+
+/// Installs the global logger. `level` is an `EnvFilter` directive,
+/// such as `info` or `banhammer=debug,warn`.
+pub fn init(level: &str, format: LogFormat) -> Result<(), Box<dyn std::error::Error>> {
+    let filter = EnvFilter::try_new(level)?;
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    let result = match format {
+        LogFormat::Json => builder.json().flatten_event(true).try_init(),
+        LogFormat::Logfmt => builder.event_format(Logfmt).try_init(),
+    };
+
+    result.map_err(|e| e.to_string().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_logfmt_value() {
+        assert_eq!(logfmt_value("info"), "info");
+        assert_eq!(logfmt_value(""), "\"\"");
+        assert_eq!(logfmt_value("two words"), "\"two words\"");
+        assert_eq!(logfmt_value("a=b"), "\"a=b\"");
+        assert_eq!(logfmt_value("say \"hi\""), "\"say \\\"hi\\\"\"");
+    }
+}
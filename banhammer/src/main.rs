@@ -1,180 +1,272 @@
-use std::collections::HashMap;
-use std::env;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
-use banhammer_grpc::grpc::authorization_server::{Authorization, AuthorizationServer};
+use banhammer_grpc::grpc::authorization_server::AuthorizationServer;
 use banhammer_grpc::grpc::validation_control_server::ValidationControlServer;
-use banhammer_grpc::grpc::{Decision, Event, EventReply, EventRequest};
-use banhammer_grpc::BanTypesEnum;
-use bech32::{ToBase32, Variant};
-use chrono::{DateTime, NaiveDateTime, Utc};
-use clap::Parser;
 
 use tokio::sync::Mutex;
-use tonic::{transport::Server, Request, Response, Status};
+use tonic::transport::Server;
+use tracing::{error, warn};
 
 use dotenv::dotenv;
 
 use crate::admin::Admin;
 use crate::app::App;
-use crate::hammer::BanHammer;
+use crate::audit::AuditLog;
+use crate::auth::{AdminAuth, Credentials};
+use crate::config::{Config, TlsSettings};
+use crate::decisions::DecisionBroadcast;
+use crate::hammer::{Authz, BanHammer};
+use crate::hits::HitCounters;
+use crate::listener::ListenAddress;
+use crate::metrics::{CountCalls, Metrics};
+use crate::nostr::Keys;
+use crate::policy::Lockdown;
+use crate::rejection_store::{RejectionStore, RejectionWriter};
+use crate::remote::{RemoteFetcher, RemoteSource};
+use crate::tls::{ReloadableTls, TlsFiles};
 
 mod admin;
 mod app;
+mod audit;
+mod auth;
+mod config;
+mod created_at;
+mod decisions;
 mod hammer;
+mod history;
+mod hits;
+mod limits;
+mod listener;
+mod logging;
+mod metrics;
+mod nip42;
+mod nip51;
+mod nostr;
+mod policy;
+mod pow;
+mod ratelimit;
+mod rejection;
+mod rejection_store;
+mod remote;
+mod sources;
+mod stats;
+mod strikes;
+mod tls;
+mod validators;
 
-#[derive(Default)]
-pub struct EventAuthz {
-    addr: String,
-    dict: String,
+/// Loads a listener TLS configuration, if a certificate is configured.
+fn tls_config(
+    settings: TlsSettings,
+    client_auth_required: bool,
+) -> Result<Option<ReloadableTls>, Box<dyn std::error::Error>> {
+    let (cert, key) = match (settings.cert, settings.key) {
+        (Some(cert), Some(key)) => (cert, key),
+        (None, None) => return Ok(None),
+        _ => return Err("a TLS certificate and its key must be configured together".into()),
+    };
+
+    let files = TlsFiles {
+        cert: PathBuf::from(cert),
+        key: PathBuf::from(key),
+        client_ca: settings.client_ca.map(PathBuf::from),
+        client_auth_required,
+    };
+
+    Ok(Some(ReloadableTls::new(files)?))
 }
 
-impl EventAuthz {
-    pub fn new() -> Self {
-        Self {
-            addr: env::var("GRPC_RELAY_ADDRESS").unwrap_or("[::1]:50051".to_string()),
-            dict: env::var("BANLIST").unwrap_or("bans.yaml".to_string()),
-        }
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    dotenv().ok();
+
+    let app = App::new();
+    let config = Config::resolve(&app)?;
+    if app.print_config {
+        print!("{}", config.to_toml()?);
+        return Ok(());
     }
+    logging::init(&config.logging.level, config.logging.format)?;
 
-    fn rejection_log_prompt(
-        &self,
-        ban_type: BanTypesEnum,
-        event: Event,
-        reply: EventReply,
-        ip: String,
-    ) -> () {
-        let id = hex::encode(event.id).as_str().to_string();
-
-        let datetime = match NaiveDateTime::from_timestamp_millis(event.created_at as i64) {
-            Some(v) => v,
-            None => Utc::now().naive_utc(),
-        };
-
-        let pubkey = match bech32::encode("npub", event.pubkey.to_base32(), Variant::Bech32) {
-            Ok(v) => v,
-            Err(_) => "".to_string(),
-        };
-
-        println!("REJECTED | {} | {{\"event\": {} , \"ip\" : \"{}\" , \"type\": \"{}\", \"pubkey\": \"{}\" }}",
-            datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
-            id,
-            ip,
-            ban_type,
-            pubkey
-            );
+    let mut checker = BanHammer::new(&config.bans.banlist.join(","));
+    checker.history.set_max_revisions(config.bans.max_revisions);
+    let hits_path = PathBuf::from(&config.bans.hits_file);
+    match HitCounters::load(&hits_path) {
+        Ok(hits) => checker.hits = hits,
+        Err(e) => error!("{}, hit counters start from zero", e),
     }
-}
+    let checker_arc = Arc::new(Mutex::new(checker));
+    tokio::spawn(hits::save_periodically(
+        checker_arc.clone(),
+        hits_path,
+        Duration::from_secs(config.bans.hits_save_interval.max(1)),
+    ));
+    let metrics = Arc::new(Metrics::default());
+    let decisions = Arc::new(DecisionBroadcast::default());
+    let lockdown = Arc::new(Lockdown::default());
 
-#[tonic::async_trait]
-impl Authorization for EventAuthz {
-    async fn event_admit(
-        &self,
-        request: Request<EventRequest>,
-    ) -> Result<Response<EventReply>, Status> {
-        let mut reply;
-        let req: EventRequest = request.into_inner();
-        let event = req.event.unwrap();
-
-        reply = EventReply {
-            decision: Decision::Permit as i32,
-            message: None,
-        };
-
-        let banhammer = BanHammer::new(&self.dict.as_str());
-
-        if banhammer.invalidate_ip(req.ip_addr.clone()) == true {
-            reply = EventReply {
-                decision: Decision::Deny as i32,
-                message: None,
-            };
-
-            _ = &self.rejection_log_prompt(
-                BanTypesEnum::IP,
-                event.clone(),
-                reply.clone(),
-                req.ip_addr.clone().unwrap_or("".to_string()),
-            );
+    let rejection_store = match config.rejection_store.path.clone() {
+        Some(path) => {
+            match RejectionStore::open(Path::new(&path), config.rejection_store.clone()) {
+                Ok(store) => Some(Arc::new(store)),
+                Err(e) => {
+                    error!("{}, rejections are not stored", e);
+                    None
+                }
+            }
         }
+        None => None,
+    };
+    let moderator_key = match config.bans.moderator_key.clone() {
+        Some(secret) => match Keys::parse(&secret) {
+            Ok(keys) => Some(keys),
+            Err(e) => {
+                warn!("moderator key: {}", e);
+                None
+            }
+        },
+        None => None,
+    };
 
-        if banhammer.invalidate_content(event.content.clone()) == true {
-            reply = EventReply {
-                decision: Decision::Deny as i32,
-                message: None,
-            };
-
-            _ = &self.rejection_log_prompt(
-                BanTypesEnum::CONTENT,
-                event.clone(),
-                reply.clone(),
-                req.ip_addr.clone().unwrap_or("".to_string()),
-            );
+    // Never run with an audit log that isn't persisted
+    let audit_path = PathBuf::from(&config.admin.audit_log);
+    let audit_log = match AuditLog::open(audit_path.clone()) {
+        Ok(log) => {
+            if let Err(e) = log.verify() {
+                error!(path = %audit_path.display(), "{}", e);
+            }
+            log
         }
-
-        let pubkey = event.pubkey.clone();
-        if banhammer.invalidate_user(pubkey) == true {
-            reply = EventReply {
-                decision: Decision::Deny as i32,
-                message: None,
-            };
-
-            _ = &self.rejection_log_prompt(
-                BanTypesEnum::USER,
-                event.clone(),
-                reply.clone(),
-                req.ip_addr.clone().unwrap_or("".to_string()),
-            );
+        Err(e) => {
+            error!(path = %audit_path.display(), "{}", e);
+            return Err(e.into());
         }
+    };
 
-        if banhammer.invalidate_tags(event.tags.clone()) == true {
-            reply = EventReply {
-                decision: Decision::Deny as i32,
-                message: None,
-            };
-
-            _ = &self.rejection_log_prompt(
-                BanTypesEnum::TAG,
-                event.clone(),
-                reply.clone(),
-                req.ip_addr.clone().unwrap_or("".to_string()),
-            );
-        }
+    let audit_log = Arc::new(Mutex::new(audit_log));
+    tokio::spawn(strikes::expire_periodically(
+        checker_arc.clone(),
+        audit_log.clone(),
+        Duration::from_secs(60),
+    ));
 
-        Ok(Response::new(reply))
+    let admin = Admin {
+        banhammer: checker_arc.clone(),
+        moderator_key,
+        audit: audit_log.clone(),
+        metrics: metrics.clone(),
+        mode: config.policy.mode,
+        decisions: decisions.clone(),
+        rejection_store: rejection_store.clone(),
+        lockdown: lockdown.clone(),
+    };
+    let authz = Authz {
+        metrics: metrics.clone(),
+        decisions,
+        rejection_store: rejection_store.map(RejectionWriter::spawn),
+        lockdown,
+        audit: audit_log,
+        ..Authz::new(checker_arc.clone(), &config)
+    };
+
+    // Subscribe to remote ban lists
+    if let Some(remotes) = config.bans.remotes.clone() {
+        let cache_dir = PathBuf::from(&config.bans.remote_cache);
+        for source in RemoteSource::load_all(Path::new(&remotes)) {
+            let fetcher = RemoteFetcher::new(source, &cache_dir);
+            tokio::spawn(fetcher.run(checker_arc.clone(), metrics.clone()));
+        }
     }
-}
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    dotenv().ok();
+    // Admin API authentication, never fall back to an open API
+    // when credentials are configured but can't be loaded
+    let admin_auth = match config.admin.credentials.clone() {
+        Some(path) => match Credentials::load(Path::new(&path)) {
+            Ok(credentials) => AdminAuth {
+                credentials: Some(Arc::new(credentials)),
+            },
+            Err(e) => {
+                error!(path = %path, "{}", e);
+                return Err(e.into());
+            }
+        },
+        None => {
+            warn!("no admin credentials configured, the admin API is not authenticated");
+            AdminAuth::default()
+        }
+    };
 
-    let app = App::new();
+    let authz_address: ListenAddress = config.listeners.address.parse()?;
+    let admin_address: ListenAddress = config.admin_address().parse()?;
+    let authz_mode = listener::parse_mode(&config.listeners.socket_mode)?;
+    let admin_mode = listener::parse_mode(&config.listeners.admin_socket_mode)?;
 
-    let ban_list = match app.banlist {
-        Some(value) => value,
-        None => env::var("GRPC_RELAY_ADDRESS").unwrap_or("[::1]:50051".to_string()),
-    };
+    // Moderators can authenticate with tokens, so admin client
+    // certificates are optional, while relays must present one
+    // when a CA is configured
+    let admin_tls = tls_config(config.tls.clone(), false)?;
+    let authz_tls = tls_config(config.authz_tls.clone(), true)?;
 
-    let checker = BanHammer::new(&ban_list);
-    let checker_arc = Arc::new(Mutex::new(checker.clone()));
-    let admin = Admin {
-        banhammer: checker_arc,
-    };
+    let reloadable: Vec<ReloadableTls> = [&admin_tls, &authz_tls]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect();
+    if !reloadable.is_empty() {
+        tls::reload_on_sighup(reloadable)?;
+    }
 
-    println!(
-        "Validation Server listening on {}",
-        app.address.clone().unwrap()
+    if let Some(address) = config.metrics.address.clone() {
+        let address = address.parse()?;
+        let metrics = metrics.clone();
+        let banhammer = checker_arc.clone();
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(address, metrics, banhammer).await {
+                error!("metrics endpoint: {}", e);
+            }
+        });
+    }
+
+    let admin_service = CountCalls::new(
+        ValidationControlServer::with_interceptor(admin, admin_auth),
+        metrics.clone(),
     );
+    let authz_service = AuthorizationServer::new(authz);
+
     // Start serving
-    Server::builder()
-        .add_service(ValidationControlServer::new(admin))
-        .add_service(AuthorizationServer::new(checker))
-        .serve(app.address.clone().unwrap().parse().unwrap())
+    if authz_address == admin_address {
+        if authz_address == ListenAddress::Disabled {
+            return Err("both the authorization and admin listeners are disabled".into());
+        }
+
+        // A shared listener uses the admin TLS settings
+        let router = Server::builder()
+            .add_service(admin_service)
+            .add_service(authz_service);
+        listener::serve(
+            "Validation Server",
+            router,
+            &authz_address,
+            authz_mode,
+            admin_tls,
+        )
         .await?;
-    Ok(())
-}
+        return Ok(());
+    }
 
-pub struct AuthorizationServerConfig {
-    addr: String,
-    dict: String,
+    let authz_router = Server::builder().add_service(authz_service);
+    let admin_router = Server::builder().add_service(admin_service);
+
+    tokio::try_join!(
+        listener::serve(
+            "Authorization",
+            authz_router,
+            &authz_address,
+            authz_mode,
+            authz_tls
+        ),
+        listener::serve("Admin", admin_router, &admin_address, admin_mode, admin_tls),
+    )?;
+    Ok(())
 }
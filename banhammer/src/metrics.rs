@@ -0,0 +1,527 @@
+use std::{
+    collections::BTreeMap,
+    convert::Infallible,
+    error::Error,
+    fmt::Write,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use banhammer_grpc::BanTypesEnum;
+use chrono::Utc;
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, StatusCode,
+};
+use tokio::sync::Mutex;
+use tonic::{
+    codegen::{http, Service},
+    server::NamedService,
+};
+use tracing::info;
+
+use crate::hammer::{rule_id, BanHammer};
+use crate::policy::Violation;
+use crate::rejection::Rejection;
+use crate::stats::DecisionWindows;
+
+/// Upper bounds of the `event_admit` latency buckets, in seconds
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25,
+];
+
+/// Counters sharing a name, one per set of label values.
+#[derive(Debug)]
+struct CounterVec {
+    labels: &'static [&'static str],
+    values: std::sync::Mutex<BTreeMap<Vec<String>, u64>>,
+}
+
+impl CounterVec {
+    fn new(labels: &'static [&'static str]) -> Self {
+        Self {
+            labels,
+            values: Default::default(),
+        }
+    }
+
+    fn inc(&self, values: &[&str]) {
+        let key = values.iter().map(|v| v.to_string()).collect();
+        *self.values.lock().unwrap().entry(key).or_default() += 1;
+    }
+
+    #[cfg(test)]
+    fn get(&self, values: &[&str]) -> u64 {
+        let key: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        self.values
+            .lock()
+            .unwrap()
+            .get(&key)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        header(out, name, help, "counter");
+        for (values, count) in self.values.lock().unwrap().iter() {
+            let _ = writeln!(out, "{}{} {}", name, labels(self.labels, values), count);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Observations per bucket, not cumulative. The last one is `+Inf`.
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    count: AtomicU64,
+    /// Sum of the observations, in nanoseconds
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        header(out, name, help, "histogram");
+
+        let mut cumulative = 0;
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            cumulative += bucket.load(Ordering::Relaxed);
+            let bound = match LATENCY_BUCKETS.get(index) {
+                Some(bound) => bound.to_string(),
+                None => "+Inf".to_string(),
+            };
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, cumulative);
+        }
+
+        let sum = self.sum.load(Ordering::Relaxed) as f64 / 1e9;
+        let _ = writeln!(out, "{}_sum {}", name, sum);
+        let _ = writeln!(out, "{}_count {}", name, self.count.load(Ordering::Relaxed));
+    }
+}
+
+fn header(out: &mut String, name: &str, help: &str, kind: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// Formats label pairs, escaping their values.
+fn labels(names: &[&str], values: &[String]) -> String {
+    if names.is_empty() {
+        return String::new();
+    }
+
+    let pairs: Vec<String> = names
+        .iter()
+        .zip(values)
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect();
+
+    format!("{{{}}}", pairs.join(","))
+}
+
+/// Validator metrics, exposed in the Prometheus text format.
+/// Every metric name is prefixed with `banhammer_`.
+#[derive(Debug)]
+pub struct Metrics {
+    evaluated: AtomicU64,
+    permitted: AtomicU64,
+    denied: AtomicU64,
+    denials: CounterVec,
+    violations: CounterVec,
+    rule_hits: CounterVec,
+    admit_duration: Histogram,
+    admin_calls: CounterVec,
+    reloads: CounterVec,
+    /// When the service started
+    pub started: Instant,
+    /// Recent decisions, reported by the `Stats` admin call
+    pub windows: DecisionWindows,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            evaluated: AtomicU64::default(),
+            permitted: AtomicU64::default(),
+            denied: AtomicU64::default(),
+            denials: CounterVec::new(&["ban_type"]),
+            violations: CounterVec::new(&["check"]),
+            rule_hits: CounterVec::new(&["ban_type", "rule"]),
+            admit_duration: Histogram::default(),
+            admin_calls: CounterVec::new(&["method"]),
+            reloads: CounterVec::new(&["source", "result"]),
+            started: Instant::now(),
+            windows: DecisionWindows::default(),
+        }
+    }
+}
+
+impl Metrics {
+    /// Records the decision taken for an event and how long it took.
+    pub fn record_decision(&self, rejections: &[Rejection], duration: Duration) {
+        self.evaluated.fetch_add(1, Ordering::Relaxed);
+        self.admit_duration.observe(duration);
+        // Every rejection of an event comes from the same pubkey and IP
+        let denied = rejections
+            .first()
+            .map(|r| (r.event.pubkey.as_slice(), r.ip.as_str()));
+        self.windows.record(denied, Utc::now());
+
+        if rejections.is_empty() {
+            self.permitted.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        self.denied.fetch_add(1, Ordering::Relaxed);
+        for rejection in rejections {
+            let ban_type = rejection.ban_type.to_string();
+            self.denials.inc(&[&ban_type]);
+            if let Some(ban) = &rejection.ban {
+                self.rule_hits.inc(&[&ban_type, &rule_id(ban)]);
+            }
+        }
+    }
+
+    /// Records an event denied by a check rather than a ban.
+    pub fn record_violation(
+        &self,
+        violation: &Violation,
+        pubkey: &[u8],
+        ip: Option<&str>,
+        duration: Duration,
+    ) {
+        self.evaluated.fetch_add(1, Ordering::Relaxed);
+        self.denied.fetch_add(1, Ordering::Relaxed);
+        self.violations.inc(&[&violation.check.to_string()]);
+        self.admit_duration.observe(duration);
+        self.windows
+            .record(Some((pubkey, ip.unwrap_or_default())), Utc::now());
+    }
+
+    pub fn record_admin_call(&self, method: &str) {
+        self.admin_calls.inc(&[method]);
+    }
+
+    /// Records the outcome of a ban list refresh.
+    pub fn record_reload(&self, source: &str, success: bool) {
+        let result = match success {
+            true => "success",
+            false => "failure",
+        };
+        self.reloads.inc(&[source, result]);
+    }
+
+    /// Renders the metrics, along with the rule counts of the ban hammer.
+    pub fn render(&self, banhammer: &BanHammer) -> String {
+        let mut out = String::new();
+
+        let counters = [
+            (
+                "banhammer_events_evaluated_total",
+                "Events evaluated by the authorization service.",
+                &self.evaluated,
+            ),
+            (
+                "banhammer_events_permitted_total",
+                "Events permitted.",
+                &self.permitted,
+            ),
+            (
+                "banhammer_events_denied_total",
+                "Events denied, whatever the number of matching bans.",
+                &self.denied,
+            ),
+        ];
+        for (name, help, counter) in counters {
+            header(&mut out, name, help, "counter");
+            let _ = writeln!(out, "{} {}", name, counter.load(Ordering::Relaxed));
+        }
+
+        self.denials.render(
+            &mut out,
+            "banhammer_denials_total",
+            "Denials by ban type, an event can be denied by several ban types.",
+        );
+        self.violations.render(
+            &mut out,
+            "banhammer_violations_total",
+            "Events denied by a check rather than a ban, by check.",
+        );
+        self.rule_hits.render(
+            &mut out,
+            "banhammer_rule_hits_total",
+            "Events matched by each rule, identified by its rule id.",
+        );
+        self.admit_duration.render(
+            &mut out,
+            "banhammer_event_admit_duration_seconds",
+            "Time taken to decide on an event.",
+        );
+
+        header(
+            &mut out,
+            "banhammer_rules",
+            "Rules currently loaded, by ban type.",
+            "gauge",
+        );
+        let rules = [
+            (BanTypesEnum::CONTENT, banhammer.words.len()),
+            (BanTypesEnum::TAG, banhammer.tags.len()),
+            (BanTypesEnum::USER, banhammer.users.len()),
+            (BanTypesEnum::IP, banhammer.ips.len()),
+            (BanTypesEnum::ORIGIN, banhammer.origins.len()),
+            (BanTypesEnum::USER_AGENT, banhammer.user_agents.len()),
+        ];
+        for (ban_type, count) in rules {
+            let _ = writeln!(
+                out,
+                "banhammer_rules{} {}",
+                labels(&["ban_type"], &[ban_type.to_string()]),
+                count
+            );
+        }
+
+        self.admin_calls.render(
+            &mut out,
+            "banhammer_admin_requests_total",
+            "Admin API calls, by RPC method.",
+        );
+        self.reloads.render(
+            &mut out,
+            "banhammer_banlist_reloads_total",
+            "Remote ban list refreshes, by source and result.",
+        );
+
+        out
+    }
+}
+
+/// Counts the calls of a gRPC service in the metrics, by method.
+#[derive(Debug, Clone)]
+pub struct CountCalls<S> {
+    inner: S,
+    metrics: Arc<Metrics>,
+}
+
+impl<S> CountCalls<S> {
+    pub fn new(inner: S, metrics: Arc<Metrics>) -> Self {
+        Self { inner, metrics }
+    }
+}
+
+impl<S, B> Service<http::Request<B>> for CountCalls<S>
+where
+    S: Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        // Paths are `/<service>/<method>`
+        if let Some(method) = request.uri().path().rsplit('/').next() {
+            self.metrics.record_admin_call(method);
+        }
+        self.inner.call(request)
+    }
+}
+
+impl<S: NamedService> NamedService for CountCalls<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+/// Serves the metrics over HTTP on `/metrics`.
+pub async fn serve(
+    address: SocketAddr,
+    metrics: Arc<Metrics>,
+    banhammer: Arc<Mutex<BanHammer>>,
+) -> Result<(), Box<dyn Error>> {
+    let make_service = make_service_fn(move |_| {
+        let metrics = metrics.clone();
+        let banhammer = banhammer.clone();
+
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request: http::Request<Body>| {
+                let metrics = metrics.clone();
+                let banhammer = banhammer.clone();
+
+                async move {
+                    let response = match (request.method(), request.uri().path()) {
+                        (&Method::GET, "/metrics") => {
+                            let body = metrics.render(&*banhammer.lock().await);
+                            http::Response::builder()
+                                .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+                                .body(Body::from(body))
+                        }
+                        _ => http::Response::builder()
+                            .status(StatusCode::NOT_FOUND)
+                            .body(Body::empty()),
+                    };
+                    response
+                }
+            }))
+        }
+    });
+
+    let server = hyper::Server::try_bind(&address)?.serve(make_service);
+    info!("Metrics listening on {}", address);
+    server.await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use banhammer_grpc::grpc::Event;
+
+    use crate::hammer::Ban;
+    use crate::policy::Check;
+
+    fn ban(content: &str) -> Ban {
+        Ban {
+            content: content.to_string(),
+            regex: false,
+            reason: None,
+            date: None,
+            source: None,
+            ban_type: BanTypesEnum::CONTENT,
+        }
+    }
+
+    #[test]
+    fn test_record_decision() {
+        let metrics = Metrics::default();
+        let spam = ban("spam");
+        let rejection = Rejection::new(
+            BanTypesEnum::CONTENT,
+            Some(&spam),
+            &Event::default(),
+            "1.2.3.4",
+        );
+
+        metrics.record_decision(&[], Duration::from_micros(50));
+        metrics.record_decision(std::slice::from_ref(&rejection), Duration::from_millis(2));
+        metrics.record_decision(&[rejection], Duration::from_secs(1));
+        metrics.record_violation(
+            &Violation::new(Check::Pow, "pow: difficulty 0 is less than 8".to_string()),
+            &[1; 32],
+            None,
+            Duration::from_micros(50),
+        );
+
+        let banhammer = BanHammer {
+            words: vec![spam.clone(), ban("scam")],
+            ..Default::default()
+        };
+        let out = metrics.render(&banhammer);
+
+        assert!(out.contains("banhammer_events_evaluated_total 4\n"));
+        assert!(out.contains("banhammer_events_permitted_total 1\n"));
+        assert!(out.contains("banhammer_events_denied_total 3\n"));
+        assert!(out.contains("banhammer_violations_total{check=\"pow\"} 1\n"));
+        assert!(out.contains("banhammer_denials_total{ban_type=\"content\"} 2\n"));
+        assert!(out.contains(&format!(
+            "banhammer_rule_hits_total{{ban_type=\"content\",rule=\"{}\"}} 2\n",
+            rule_id(&spam)
+        )));
+        assert!(out.contains("banhammer_rules{ban_type=\"content\"} 2\n"));
+        assert!(out.contains("banhammer_rules{ban_type=\"ip\"} 0\n"));
+
+        // Buckets are cumulative
+        assert!(out.contains("banhammer_event_admit_duration_seconds_bucket{le=\"0.0001\"} 2\n"));
+        assert!(out.contains("banhammer_event_admit_duration_seconds_bucket{le=\"0.0025\"} 3\n"));
+        assert!(out.contains("banhammer_event_admit_duration_seconds_bucket{le=\"0.25\"} 3\n"));
+        assert!(out.contains("banhammer_event_admit_duration_seconds_bucket{le=\"+Inf\"} 4\n"));
+        assert!(out.contains("banhammer_event_admit_duration_seconds_count 4\n"));
+    }
+
+    #[test]
+    fn test_labels() {
+        let metrics = Metrics::default();
+        metrics.record_reload("https://example.com/\"bans\".yaml", true);
+        metrics.record_reload("https://example.com/\"bans\".yaml", false);
+        metrics.record_reload("https://example.com/\"bans\".yaml", false);
+
+        assert_eq!(
+            metrics
+                .reloads
+                .get(&["https://example.com/\"bans\".yaml", "failure"]),
+            2
+        );
+        let out = metrics.render(&BanHammer::default());
+        assert!(out.contains(
+            "banhammer_banlist_reloads_total{source=\"https://example.com/\\\"bans\\\".yaml\",result=\"failure\"} 2\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_serve() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        drop(listener);
+
+        let metrics = Arc::new(Metrics::default());
+        metrics.record_admin_call("ListBans");
+        let banhammer = Arc::new(Mutex::new(BanHammer::default()));
+        tokio::spawn(async move {
+            let _ = serve(address, metrics, banhammer).await;
+        });
+
+        let client = reqwest::Client::new();
+        let mut response = None;
+        for _ in 0..50 {
+            match client
+                .get(format!("http://{}/metrics", address))
+                .send()
+                .await
+            {
+                Ok(r) => {
+                    response = Some(r);
+                    break;
+                }
+                Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+            }
+        }
+
+        let response = response.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let body = response.text().await.unwrap();
+        assert!(body.contains("banhammer_admin_requests_total{method=\"ListBans\"} 1\n"));
+
+        let response = client
+            .get(format!("http://{}/other", address))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+}
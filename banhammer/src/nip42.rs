@@ -0,0 +1,137 @@
+use banhammer_grpc::grpc::Event;
+use serde::{Deserialize, Serialize};
+
+use crate::policy::KindRange;
+
+/// Pubkey of an event looked up in allowlists and rate limits.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Identity {
+    /// The author of the event
+    #[default]
+    Event,
+    /// The pubkey of the NIP-42 session, the author when not authenticated
+    Auth,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Nip42Settings {
+    /// Every event requires an authenticated session
+    pub required: bool,
+    /// Events of these kinds require an authenticated session
+    pub kinds: Vec<KindRange>,
+    /// Events of these kinds can only be published by their author,
+    /// so that others can't rebroadcast them
+    pub author_kinds: Vec<KindRange>,
+    pub identity: Identity,
+}
+
+/// NIP-42 authentication requirements, checked against the pubkey the
+/// relay authenticated the session with.
+#[derive(Debug, Default)]
+pub struct Nip42 {
+    settings: Nip42Settings,
+}
+
+impl Nip42 {
+    pub fn new(settings: &Nip42Settings) -> Self {
+        Self {
+            settings: settings.clone(),
+        }
+    }
+
+    /// Returns why an event requires authentication, if it does.
+    pub fn check(&self, event: &Event, auth_pubkey: Option<&[u8]>) -> Option<String> {
+        let settings = &self.settings;
+        let listed = |kinds: &[KindRange]| kinds.iter().any(|range| range.contains(event.kind));
+
+        if auth_pubkey.is_none() && (settings.required || listed(&settings.kinds)) {
+            return Some(format!(
+                "auth-required: kind {} requires authentication",
+                event.kind
+            ));
+        }
+        if listed(&settings.author_kinds) && auth_pubkey != Some(event.pubkey.as_slice()) {
+            return Some(format!(
+                "auth-required: kind {} can only be published by its author",
+                event.kind
+            ));
+        }
+        None
+    }
+
+    /// Pubkey of an event looked up in allowlists and rate limits.
+    pub fn identity<'a>(&self, event: &'a Event, auth_pubkey: Option<&'a [u8]>) -> &'a [u8] {
+        match self.settings.identity {
+            Identity::Event => &event.pubkey,
+            Identity::Auth => auth_pubkey.unwrap_or(&event.pubkey),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: [u8; 32] = [1; 32];
+    const BOB: [u8; 32] = [2; 32];
+
+    fn event(kind: u64) -> Event {
+        Event {
+            kind,
+            pubkey: ALICE.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let settings: Nip42Settings = toml::from_str(
+            r#"
+kinds = [4, 1059]
+author_kinds = ["10000-19999"]
+"#,
+        )
+        .unwrap();
+        let nip42 = Nip42::new(&settings);
+
+        assert_eq!(nip42.check(&event(1), None), None);
+        assert_eq!(
+            nip42.check(&event(4), None),
+            Some("auth-required: kind 4 requires authentication".to_string())
+        );
+        // Anyone authenticated can send direct messages
+        assert_eq!(nip42.check(&event(1059), Some(&BOB)), None);
+
+        assert_eq!(nip42.check(&event(10002), Some(&ALICE)), None);
+        for auth_pubkey in [None, Some(&BOB[..])] {
+            assert_eq!(
+                nip42.check(&event(10002), auth_pubkey),
+                Some("auth-required: kind 10002 can only be published by its author".to_string())
+            );
+        }
+
+        let nip42 = Nip42::new(&Nip42Settings {
+            required: true,
+            ..Default::default()
+        });
+        assert!(nip42.check(&event(1), None).is_some());
+        assert_eq!(nip42.check(&event(1), Some(&BOB)), None);
+    }
+
+    #[test]
+    fn test_identity() {
+        let event = event(1);
+
+        let nip42 = Nip42::new(&Nip42Settings::default());
+        assert_eq!(nip42.identity(&event, Some(&BOB)), ALICE);
+
+        let nip42 = Nip42::new(&Nip42Settings {
+            identity: Identity::Auth,
+            ..Default::default()
+        });
+        assert_eq!(nip42.identity(&event, Some(&BOB)), BOB);
+        assert_eq!(nip42.identity(&event, None), ALICE);
+    }
+}
@@ -0,0 +1,238 @@
+use std::fmt;
+
+use banhammer_grpc::BanTypesEnum;
+use chrono::DateTime;
+
+use crate::hammer::Ban;
+use crate::nostr::{from_npub, to_npub, Keys, NostrError, NostrEvent};
+
+/// NIP-51 mute list, one per pubkey.
+pub const MUTE_LIST_KIND: u64 = 10000;
+/// NIP-51 parameterized list, identified by its `d` tag.
+pub const LIST_SET_KIND: u64 = 30000;
+
+#[derive(Debug, PartialEq)]
+pub enum Nip51Error {
+    UnsupportedKind(u64),
+    MissingIdentifier,
+    Event(NostrError),
+}
+
+impl fmt::Display for Nip51Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Nip51Error::UnsupportedKind(kind) => write!(f, "unsupported list kind: {}", kind),
+            Nip51Error::MissingIdentifier => write!(f, "kind 30000 lists require an identifier"),
+            Nip51Error::Event(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<NostrError> for Nip51Error {
+    fn from(value: NostrError) -> Self {
+        Nip51Error::Event(value)
+    }
+}
+
+/// Maps a ban to a NIP-51 list item.
+/// Regex bans and ban types that have no NIP-51 equivalent are not exported.
+fn ban_to_tag(ban: &Ban) -> Option<Vec<String>> {
+    if ban.regex {
+        return None;
+    }
+
+    match ban.ban_type {
+        BanTypesEnum::USER => {
+            let pubkey = from_npub(&ban.content)?;
+            Some(vec!["p".to_string(), hex::encode(pubkey)])
+        }
+        BanTypesEnum::TAG => Some(vec!["t".to_string(), ban.content.clone()]),
+        BanTypesEnum::CONTENT => Some(vec!["word".to_string(), ban.content.clone()]),
+        _ => None,
+    }
+}
+
+/// Maps a NIP-51 list item to a ban. Unknown items are ignored.
+fn tag_to_ban(tag: &[String]) -> Option<Ban> {
+    let (name, value) = match tag {
+        [name, value, ..] if !value.is_empty() => (name.as_str(), value),
+        _ => return None,
+    };
+
+    let (ban_type, content) = match name {
+        "p" => (
+            BanTypesEnum::USER,
+            to_npub(&hex::decode(value).ok().filter(|key| key.len() == 32)?)?,
+        ),
+        "t" => (BanTypesEnum::TAG, value.clone()),
+        "word" => (BanTypesEnum::CONTENT, value.clone()),
+        _ => return None,
+    };
+
+    Some(Ban {
+        content,
+        regex: false,
+        reason: None,
+        date: None,
+        ban_type,
+        source: None,
+    })
+}
+
+/// Exports bans as a signed NIP-51 list event.
+pub fn export(
+    bans: &[Ban],
+    keys: &Keys,
+    kind: u64,
+    identifier: Option<String>,
+) -> Result<NostrEvent, Nip51Error> {
+    let mut tags: Vec<Vec<String>> = Vec::new();
+
+    match kind {
+        MUTE_LIST_KIND => {}
+        LIST_SET_KIND => {
+            let identifier = identifier.ok_or(Nip51Error::MissingIdentifier)?;
+            tags.push(vec!["d".to_string(), identifier]);
+        }
+        kind => return Err(Nip51Error::UnsupportedKind(kind)),
+    }
+
+    for tag in bans.iter().filter_map(ban_to_tag) {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+
+    Ok(NostrEvent::sign(keys, kind, tags, "".to_string()))
+}
+
+/// Verifies a NIP-51 list event and converts its public items to bans.
+/// Encrypted private items are ignored.
+pub fn import(event: &NostrEvent) -> Result<Vec<Ban>, Nip51Error> {
+    if event.kind != MUTE_LIST_KIND && event.kind != LIST_SET_KIND {
+        return Err(Nip51Error::UnsupportedKind(event.kind));
+    }
+
+    event.verify()?;
+
+    let author = hex::decode(&event.pubkey)
+        .ok()
+        .and_then(|pubkey| to_npub(&pubkey))
+        .unwrap_or(event.pubkey.clone());
+    let source = format!("nip51:{}", author);
+    let date = DateTime::from_timestamp(event.created_at as i64, 0)
+        .map(|date| date.format("%Y-%m-%dT%H:%M:%S").to_string());
+
+    let bans = event
+        .tags
+        .iter()
+        .filter_map(|tag| tag_to_ban(tag))
+        .map(|mut ban| {
+            ban.date = date.clone();
+            ban.source = Some(source.clone());
+            ban
+        })
+        .collect();
+
+    Ok(bans)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0000000000000000000000000000000000000000000000000000000000000003";
+    const NPUB_HEX: &str = "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9";
+
+    fn ban(ban_type: BanTypesEnum, content: &str, regex: bool) -> Ban {
+        Ban {
+            content: content.to_string(),
+            regex,
+            reason: None,
+            date: None,
+            ban_type,
+            source: None,
+        }
+    }
+
+    fn sample() -> Vec<Ban> {
+        let npub = to_npub(&hex::decode(NPUB_HEX).unwrap()).unwrap();
+
+        vec![
+            ban(BanTypesEnum::USER, &npub, false),
+            ban(BanTypesEnum::TAG, "nsfw", false),
+            ban(BanTypesEnum::CONTENT, "buy now", false),
+            ban(BanTypesEnum::CONTENT, "^spam.*", true),
+            ban(BanTypesEnum::IP, "127.0.0.1", false),
+        ]
+    }
+
+    #[test]
+    fn test_export() {
+        let keys = Keys::parse(SECRET).unwrap();
+        let event = export(&sample(), &keys, MUTE_LIST_KIND, None).unwrap();
+
+        assert_eq!(event.kind, MUTE_LIST_KIND);
+        assert_eq!(
+            event.tags,
+            vec![
+                vec!["p".to_string(), NPUB_HEX.to_string()],
+                vec!["t".to_string(), "nsfw".to_string()],
+                vec!["word".to_string(), "buy now".to_string()],
+            ]
+        );
+        assert!(event.verify().is_ok());
+
+        let event = export(
+            &sample(),
+            &keys,
+            LIST_SET_KIND,
+            Some("banhammer".to_string()),
+        )
+        .unwrap();
+        assert_eq!(
+            event.tags[0],
+            vec!["d".to_string(), "banhammer".to_string()]
+        );
+
+        assert_eq!(
+            export(&sample(), &keys, LIST_SET_KIND, None),
+            Err(Nip51Error::MissingIdentifier)
+        );
+        assert_eq!(
+            export(&sample(), &keys, 1, None),
+            Err(Nip51Error::UnsupportedKind(1))
+        );
+    }
+
+    #[test]
+    fn test_import_round_trip() {
+        let keys = Keys::parse(SECRET).unwrap();
+        let event = export(&sample(), &keys, LIST_SET_KIND, Some("spam".to_string())).unwrap();
+
+        let bans = import(&event).unwrap();
+        assert_eq!(bans.len(), 3);
+        for (imported, original) in bans.iter().zip(sample().iter()) {
+            assert!(imported.is_duplicate_of(original));
+            assert!(imported.source.clone().unwrap().starts_with("nip51:npub1"));
+        }
+    }
+
+    #[test]
+    fn test_import_rejects_invalid_events() {
+        let keys = Keys::parse(SECRET).unwrap();
+        let event = export(&sample(), &keys, MUTE_LIST_KIND, None).unwrap();
+
+        let mut tampered = event.clone();
+        tampered
+            .tags
+            .push(vec!["word".to_string(), "injected".to_string()]);
+        assert_eq!(
+            import(&tampered),
+            Err(Nip51Error::Event(NostrError::InvalidId))
+        );
+
+        let note = NostrEvent::sign(&keys, 1, vec![], "hello".to_string());
+        assert_eq!(import(&note), Err(Nip51Error::UnsupportedKind(1)));
+    }
+}
@@ -0,0 +1,384 @@
+use std::fmt;
+
+use banhammer_grpc::grpc::Event;
+use bech32::{FromBase32, ToBase32, Variant};
+use chrono::Utc;
+use secp256k1::{schnorr::Signature, Keypair, Message, Secp256k1, SecretKey, XOnlyPublicKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// A nostr event, as described by NIP-01.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NostrEvent {
+    pub id: String,
+    pub pubkey: String,
+    pub created_at: u64,
+    pub kind: u64,
+    pub tags: Vec<Vec<String>>,
+    pub content: String,
+    pub sig: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum NostrError {
+    InvalidKey(String),
+    InvalidId,
+    InvalidSignature,
+    Json(String),
+}
+
+impl fmt::Display for NostrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NostrError::InvalidKey(e) => write!(f, "invalid key: {}", e),
+            NostrError::InvalidId => write!(f, "event id does not match its content"),
+            NostrError::InvalidSignature => write!(f, "invalid event signature"),
+            NostrError::Json(e) => write!(f, "invalid event: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for NostrError {}
+
+/// Escapes a string as required by the NIP-01 canonical serialization.
+/// Only a handful of characters are escaped, every other one is kept verbatim.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+    escaped.push('"');
+
+    for c in value.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{08}' => escaped.push_str("\\b"),
+            '\u{0c}' => escaped.push_str("\\f"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+/// Serializes the event fields following NIP-01:
+/// `[0,<pubkey>,<created_at>,<kind>,<tags>,<content>]`
+pub fn canonical_serialization(
+    pubkey: &str,
+    created_at: u64,
+    kind: u64,
+    tags: &[Vec<String>],
+    content: &str,
+) -> String {
+    let tags: Vec<String> = tags
+        .iter()
+        .map(|tag| {
+            let values: Vec<String> = tag.iter().map(|v| escape(v)).collect();
+            format!("[{}]", values.join(","))
+        })
+        .collect();
+
+    format!(
+        "[0,{},{},{},[{}],{}]",
+        escape(pubkey),
+        created_at,
+        kind,
+        tags.join(","),
+        escape(content)
+    )
+}
+
+/// Computes the event id, the SHA-256 of its canonical serialization.
+pub fn compute_id(
+    pubkey: &str,
+    created_at: u64,
+    kind: u64,
+    tags: &[Vec<String>],
+    content: &str,
+) -> [u8; 32] {
+    let serialized = canonical_serialization(pubkey, created_at, kind, tags, content);
+    Sha256::digest(serialized.as_bytes()).into()
+}
+
+/// Verifies a BIP-340 Schnorr signature of a 32 bytes message.
+pub fn verify_signature(pubkey: &[u8], message: &[u8], sig: &[u8]) -> Result<(), NostrError> {
+    let pubkey =
+        XOnlyPublicKey::from_slice(pubkey).map_err(|e| NostrError::InvalidKey(e.to_string()))?;
+    let message = Message::from_digest_slice(message).map_err(|_| NostrError::InvalidId)?;
+    let sig = Signature::from_slice(sig).map_err(|_| NostrError::InvalidSignature)?;
+
+    Secp256k1::verification_only()
+        .verify_schnorr(&sig, &message, &pubkey)
+        .map_err(|_| NostrError::InvalidSignature)
+}
+
+impl NostrEvent {
+    /// Builds and signs an event with the current timestamp.
+    pub fn sign(keys: &Keys, kind: u64, tags: Vec<Vec<String>>, content: String) -> Self {
+        let pubkey = keys.public_key_hex();
+        let created_at = Utc::now().timestamp() as u64;
+        let id = compute_id(&pubkey, created_at, kind, &tags, &content);
+        let sig = keys.sign(&id);
+
+        Self {
+            id: hex::encode(id),
+            pubkey,
+            created_at,
+            kind,
+            tags,
+            content,
+            sig: hex::encode(sig),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, NostrError> {
+        serde_json::from_str(json).map_err(|e| NostrError::Json(e.to_string()))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Checks that the id matches the event content and that
+    /// the signature is valid for the event pubkey.
+    pub fn verify(&self) -> Result<(), NostrError> {
+        let id = compute_id(
+            &self.pubkey,
+            self.created_at,
+            self.kind,
+            &self.tags,
+            &self.content,
+        );
+
+        if hex::encode(id) != self.id.to_lowercase() {
+            return Err(NostrError::InvalidId);
+        }
+
+        let pubkey =
+            hex::decode(&self.pubkey).map_err(|e| NostrError::InvalidKey(e.to_string()))?;
+        let sig = hex::decode(&self.sig).map_err(|_| NostrError::InvalidSignature)?;
+
+        verify_signature(&pubkey, &id, &sig)
+    }
+}
+
+/// Checks that the id of an event sent by the relay matches its
+/// content, and that it is signed by its pubkey.
+pub fn verify_event(event: &Event) -> Result<(), NostrError> {
+    NostrEvent::from(event).verify()
+}
+
+impl From<&Event> for NostrEvent {
+    fn from(event: &Event) -> Self {
+        Self {
+            id: hex::encode(&event.id),
+            pubkey: hex::encode(&event.pubkey),
+            created_at: event.created_at,
+            kind: event.kind,
+            tags: event.tags.iter().map(|tag| tag.values.clone()).collect(),
+            content: event.content.clone(),
+            sig: hex::encode(&event.sig),
+        }
+    }
+}
+
+/// A nostr key pair, used to sign moderation events.
+#[derive(Clone)]
+pub struct Keys {
+    keypair: Keypair,
+}
+
+impl fmt::Debug for Keys {
+    // Never print the secret key
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Keys({})", self.public_key_hex())
+    }
+}
+
+impl Keys {
+    /// Parses a secret key, either `nsec` bech32 encoded or hex encoded.
+    pub fn parse(secret: &str) -> Result<Self, NostrError> {
+        let secret = secret.trim();
+
+        let bytes = match secret.starts_with("nsec") {
+            true => {
+                let (hrp, data, _) =
+                    bech32::decode(secret).map_err(|e| NostrError::InvalidKey(e.to_string()))?;
+                if hrp != "nsec" {
+                    return Err(NostrError::InvalidKey(format!("unexpected prefix {}", hrp)));
+                }
+                Vec::<u8>::from_base32(&data).map_err(|e| NostrError::InvalidKey(e.to_string()))?
+            }
+            false => hex::decode(secret).map_err(|e| NostrError::InvalidKey(e.to_string()))?,
+        };
+
+        let secret_key =
+            SecretKey::from_slice(&bytes).map_err(|e| NostrError::InvalidKey(e.to_string()))?;
+
+        Ok(Self {
+            keypair: Keypair::from_secret_key(&Secp256k1::new(), &secret_key),
+        })
+    }
+
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.keypair.x_only_public_key().0.serialize())
+    }
+
+    /// Signs a 32 bytes message, such as an event id, with BIP-340 Schnorr.
+    pub fn sign(&self, id: &[u8; 32]) -> [u8; 64] {
+        let message = Message::from_digest(*id);
+        Secp256k1::new()
+            .sign_schnorr(&message, &self.keypair)
+            .serialize()
+    }
+}
+
+/// Encodes a public key as an `npub`.
+pub fn to_npub(pubkey: &[u8]) -> Option<String> {
+    bech32::encode("npub", pubkey.to_base32(), Variant::Bech32).ok()
+}
+
+/// Decodes an `npub` to the raw public key.
+pub fn from_npub(npub: &str) -> Option<Vec<u8>> {
+    let (hrp, data, _) = bech32::decode(npub).ok()?;
+    if hrp != "npub" {
+        return None;
+    }
+
+    Vec::<u8>::from_base32(&data).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use banhammer_grpc::grpc::event::TagEntry;
+
+    const SECRET: &str = "0000000000000000000000000000000000000000000000000000000000000003";
+
+    #[test]
+    fn test_canonical_serialization() {
+        let tags = vec![vec!["t".to_string(), "nostr".to_string()]];
+        let serialized =
+            canonical_serialization("abcd", 1700000000, 1, &tags, "line\n\"quoted\"\u{1}");
+
+        assert_eq!(
+            serialized,
+            "[0,\"abcd\",1700000000,1,[[\"t\",\"nostr\"]],\"line\\n\\\"quoted\\\"\u{1}\"]"
+        );
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let keys = Keys::parse(SECRET).unwrap();
+        assert_eq!(
+            keys.public_key_hex(),
+            "f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9"
+        );
+
+        let tags = vec![vec!["word".to_string(), "spam".to_string()]];
+        let event = NostrEvent::sign(&keys, 10000, tags, "".to_string());
+        assert!(event.verify().is_ok());
+
+        let json = event.to_json();
+        assert_eq!(NostrEvent::from_json(&json).unwrap(), event);
+
+        let mut tampered = event.clone();
+        tampered.content = "tampered".to_string();
+        assert_eq!(tampered.verify(), Err(NostrError::InvalidId));
+
+        let mut forged = event.clone();
+        forged.sig = "00".repeat(64);
+        assert_eq!(forged.verify(), Err(NostrError::InvalidSignature));
+    }
+
+    /// Event signed by the key `SECRET`
+    const EVENT: &str = r#"{"id":"a7000032fddc38b607a1f6d7efef25b10ba1f1d0599b76b605b00a05fec6c510","pubkey":"f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9","created_at":1700000000,"kind":1,"tags":[["t","nostr"]],"content":"hello \"nostr\"\n","sig":"13b70c4ed676a4e9e3e4a9d990a2a65c2d7548d215d1df5c5d8d51aaa23875144cf2b420dd2936831069898fe5b81dcb6fa3e7dc32da80a9646ec62c48b25bd9"}"#;
+
+    fn event(json: &str) -> Event {
+        let event = NostrEvent::from_json(json).unwrap();
+        Event {
+            id: hex::decode(&event.id).unwrap(),
+            pubkey: hex::decode(&event.pubkey).unwrap(),
+            created_at: event.created_at,
+            kind: event.kind,
+            content: event.content,
+            tags: event
+                .tags
+                .into_iter()
+                .map(|values| TagEntry { values })
+                .collect(),
+            sig: hex::decode(&event.sig).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_verify_event() {
+        let valid = event(EVENT);
+        assert_eq!(verify_event(&valid), Ok(()));
+
+        let mut tampered = valid.clone();
+        tampered.created_at += 1;
+        assert_eq!(verify_event(&tampered), Err(NostrError::InvalidId));
+
+        let mut tampered = valid.clone();
+        tampered.tags[0].values[1] = "spam".to_string();
+        assert_eq!(verify_event(&tampered), Err(NostrError::InvalidId));
+
+        let mut forged = valid.clone();
+        forged.sig[0] ^= 1;
+        assert_eq!(verify_event(&forged), Err(NostrError::InvalidSignature));
+
+        let mut truncated = valid.clone();
+        truncated.sig.truncate(32);
+        assert_eq!(verify_event(&truncated), Err(NostrError::InvalidSignature));
+
+        // A valid signature, by another key over the same content
+        let mut stolen = valid.clone();
+        stolen.pubkey =
+            hex::decode(Keys::parse(&"01".repeat(32)).unwrap().public_key_hex()).unwrap();
+        assert_eq!(verify_event(&stolen), Err(NostrError::InvalidId));
+        stolen.id = compute_id(
+            &hex::encode(&stolen.pubkey),
+            stolen.created_at,
+            stolen.kind,
+            &[vec!["t".to_string(), "nostr".to_string()]],
+            &stolen.content,
+        )
+        .to_vec();
+        assert_eq!(verify_event(&stolen), Err(NostrError::InvalidSignature));
+
+        assert_eq!(verify_event(&Event::default()), Err(NostrError::InvalidId));
+    }
+
+    #[test]
+    fn test_keys_parse() {
+        let keys = Keys::parse(SECRET).unwrap();
+        let nsec = bech32::encode(
+            "nsec",
+            hex::decode(SECRET).unwrap().to_base32(),
+            Variant::Bech32,
+        )
+        .unwrap();
+
+        let from_nsec = Keys::parse(&nsec).unwrap();
+        assert_eq!(from_nsec.public_key_hex(), keys.public_key_hex());
+
+        assert!(Keys::parse("npub1234").is_err());
+        assert!(Keys::parse("not a key").is_err());
+    }
+
+    #[test]
+    fn test_npub() {
+        let pubkey =
+            hex::decode("f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9")
+                .unwrap();
+        let npub = to_npub(&pubkey).unwrap();
+
+        assert!(npub.starts_with("npub1"));
+        assert_eq!(from_npub(&npub), Some(pubkey));
+        assert_eq!(from_npub("nsec1qqqsyqcyq5rqwzqf"), None);
+    }
+}
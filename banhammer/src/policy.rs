@@ -0,0 +1,205 @@
+use std::{
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Instant,
+};
+
+use chrono::Utc;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Checks denying events on their own, whatever the bans.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    Size,
+    Auth,
+    Signature,
+    Conformance,
+    CreatedAt,
+    RateLimit,
+    Pow,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let check = match self {
+            Check::Size => "size",
+            Check::Auth => "auth",
+            Check::Signature => "signature",
+            Check::Conformance => "conformance",
+            Check::CreatedAt => "created_at",
+            Check::RateLimit => "rate_limit",
+            Check::Pow => "pow",
+        };
+
+        write!(f, "{}", check)
+    }
+}
+
+/// An event failing a check. The message is sent back to the relay,
+/// prefixed as NIP-01 describes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub check: Check,
+    pub message: String,
+}
+
+impl Violation {
+    pub fn new(check: Check, message: String) -> Self {
+        Self { check, message }
+    }
+}
+
+/// Set by moderators during spam waves, tightens the checks
+/// until it is lifted.
+#[derive(Debug, Default)]
+pub struct Lockdown {
+    active: AtomicBool,
+}
+
+impl Lockdown {
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Returns whether it was active.
+    pub fn set(&self, active: bool) -> bool {
+        self.active.swap(active, Ordering::Relaxed)
+    }
+}
+
+/// Source of time of the checks, replaced in tests.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+    /// Seconds since the epoch
+    fn timestamp(&self) -> i64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn timestamp(&self) -> i64 {
+        Utc::now().timestamp()
+    }
+}
+
+/// Kinds from `start` to `end`, inclusive. Written as a single kind, such as
+/// `1`, or as a range, such as `"10000-19999"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KindRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl KindRange {
+    pub fn contains(&self, kind: u64) -> bool {
+        (self.start..=self.end).contains(&kind)
+    }
+}
+
+impl FromStr for KindRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid kind range: {}", s);
+
+        let (start, end) = match s.split_once('-') {
+            Some((start, end)) => (start.trim(), end.trim()),
+            None => (s.trim(), s.trim()),
+        };
+        let start: u64 = start.parse().map_err(|_| invalid())?;
+        let end: u64 = end.parse().map_err(|_| invalid())?;
+        if start > end {
+            return Err(invalid());
+        }
+
+        Ok(Self { start, end })
+    }
+}
+
+impl fmt::Display for KindRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.start == self.end {
+            true => write!(f, "{}", self.start),
+            false => write!(f, "{}-{}", self.start, self.end),
+        }
+    }
+}
+
+impl Serialize for KindRange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.start == self.end {
+            true => serializer.serialize_u64(self.start),
+            false => serializer.serialize_str(&self.to_string()),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for KindRange {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct KindRangeVisitor;
+
+        impl<'de> de::Visitor<'de> for KindRangeVisitor {
+            type Value = KindRange;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a kind or a range of kinds such as \"10000-19999\"")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                KindRange::from_str(v).map_err(E::custom)
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(KindRange { start: v, end: v })
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                u64::try_from(v)
+                    .map(|v| KindRange { start: v, end: v })
+                    .map_err(|_| E::custom(format!("invalid kind: {}", v)))
+            }
+        }
+
+        deserializer.deserialize_any(KindRangeVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Kinds {
+        kinds: Vec<KindRange>,
+    }
+
+    #[test]
+    fn test_kind_range() {
+        let kinds: Kinds = toml::from_str(r#"kinds = [1, "10000-19999", " 7 "]"#).unwrap();
+        assert_eq!(
+            kinds.kinds,
+            vec![
+                KindRange { start: 1, end: 1 },
+                KindRange {
+                    start: 10000,
+                    end: 19999
+                },
+                KindRange { start: 7, end: 7 },
+            ]
+        );
+        assert!(kinds.kinds[1].contains(10002) && !kinds.kinds[1].contains(20000));
+        assert_eq!(
+            toml::to_string(&kinds).unwrap(),
+            "kinds = [1, \"10000-19999\", 7]\n"
+        );
+
+        for invalid in [r#"kinds = ["2-1"]"#, r#"kinds = ["a"]"#, "kinds = [-1]"] {
+            assert!(toml::from_str::<Kinds>(invalid).is_err());
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashSet;
+
+use banhammer_grpc::grpc::Event;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::nostr::from_npub;
+use crate::policy::KindRange;
+
+/// Minimum difficulty of some kinds, overriding the default one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowKindSettings {
+    pub kinds: Vec<KindRange>,
+    pub difficulty: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowSettings {
+    /// Minimum difficulty, in leading zero bits of the event id.
+    /// Proof of work is not required when 0.
+    pub difficulty: u32,
+    pub kinds: Vec<PowKindSettings>,
+    /// Pubkeys, npub or hex encoded, that don't need any proof of work
+    pub allow: Vec<String>,
+    /// Minimum difficulty of every kind during a lockdown
+    pub lockdown_difficulty: u32,
+}
+
+impl Default for PowSettings {
+    fn default() -> Self {
+        Self {
+            difficulty: 0,
+            kinds: vec![],
+            allow: vec![],
+            lockdown_difficulty: 20,
+        }
+    }
+}
+
+/// Leading zero bits of an event id, its NIP-13 difficulty.
+pub fn leading_zero_bits(id: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in id {
+        bits += byte.leading_zeros();
+        if *byte != 0 {
+            break;
+        }
+    }
+    bits
+}
+
+/// Target difficulty committed in the `nonce` tag of an event.
+fn committed_target(event: &Event) -> Option<u32> {
+    event
+        .tags
+        .iter()
+        .find(|tag| tag.values.first().map(String::as_str) == Some("nonce"))
+        .and_then(|tag| tag.values.get(2))
+        .and_then(|target| target.parse().ok())
+}
+
+/// NIP-13 proof of work requirements.
+///
+/// The difficulty of an event is the lowest of the leading zero bits of its
+/// id and of the target committed in its `nonce` tag, so events mined for a
+/// low target that happen to get a lucky id don't pass.
+#[derive(Debug, Default)]
+pub struct Pow {
+    difficulty: u32,
+    kinds: Vec<PowKindSettings>,
+    allow: HashSet<Vec<u8>>,
+    lockdown_difficulty: u32,
+}
+
+impl Pow {
+    /// Invalid allowed pubkeys are reported and skipped.
+    pub fn new(settings: &PowSettings) -> Self {
+        let allow = settings
+            .allow
+            .iter()
+            .filter_map(|pubkey| {
+                let bytes = from_npub(pubkey)
+                    .or_else(|| hex::decode(pubkey).ok())
+                    .filter(|bytes| bytes.len() == 32);
+                if bytes.is_none() {
+                    warn!(pubkey = %pubkey, "invalid proof of work allowed pubkey");
+                }
+                bytes
+            })
+            .collect();
+
+        Self {
+            difficulty: settings.difficulty,
+            kinds: settings.kinds.clone(),
+            allow,
+            lockdown_difficulty: settings.lockdown_difficulty,
+        }
+    }
+
+    /// Minimum difficulty of a kind.
+    pub fn minimum(&self, kind: u64, lockdown: bool) -> u32 {
+        let minimum = self
+            .kinds
+            .iter()
+            .find(|settings| settings.kinds.iter().any(|range| range.contains(kind)))
+            .map_or(self.difficulty, |settings| settings.difficulty);
+
+        match lockdown {
+            true => minimum.max(self.lockdown_difficulty),
+            false => minimum,
+        }
+    }
+
+    /// Returns why an event lacks proof of work, if it does. `pubkey` is
+    /// looked up in the allowed pubkeys, the author or the authenticated one.
+    pub fn check(&self, event: &Event, pubkey: &[u8], lockdown: bool) -> Option<String> {
+        let minimum = self.minimum(event.kind, lockdown);
+        if minimum == 0 || self.allow.contains(pubkey) {
+            return None;
+        }
+
+        let Some(target) = committed_target(event) else {
+            return Some(format!(
+                "pow: a nonce tag committing to a difficulty of {} is required",
+                minimum
+            ));
+        };
+
+        let difficulty = leading_zero_bits(&event.id).min(target);
+        if difficulty < minimum {
+            return Some(format!(
+                "pow: difficulty {} is less than {}",
+                difficulty, minimum
+            ));
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use banhammer_grpc::grpc::event::TagEntry;
+
+    fn event(id_prefix: &[u8], kind: u64, target: Option<&str>) -> Event {
+        let mut id = vec![0xff; 32];
+        id[..id_prefix.len()].copy_from_slice(id_prefix);
+
+        Event {
+            id,
+            pubkey: vec![1; 32],
+            kind,
+            tags: target
+                .map(|target| TagEntry {
+                    values: vec!["nonce".to_string(), "42".to_string(), target.to_string()],
+                })
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_leading_zero_bits() {
+        assert_eq!(leading_zero_bits(&[0xff]), 0);
+        assert_eq!(leading_zero_bits(&[0x00, 0x0f]), 12);
+        assert_eq!(leading_zero_bits(&[0x00, 0x00, 0x01]), 23);
+        assert_eq!(leading_zero_bits(&[0x00; 32]), 256);
+    }
+
+    #[test]
+    fn test_check() {
+        let pow = Pow::new(&PowSettings {
+            difficulty: 8,
+            kinds: vec![PowKindSettings {
+                kinds: vec!["4".parse().unwrap()],
+                difficulty: 16,
+            }],
+            allow: vec![hex::encode([2; 32])],
+            lockdown_difficulty: 20,
+        });
+        let check = |event: &Event, lockdown| pow.check(event, &event.pubkey, lockdown);
+
+        // 12 leading zero bits
+        assert_eq!(check(&event(&[0x00, 0x0f], 1, Some("12")), false), None);
+        assert!(check(&event(&[0x00, 0x0f], 1, None), false)
+            .unwrap()
+            .starts_with("pow: a nonce tag"));
+        // Lucky ids don't count beyond the committed target
+        assert_eq!(
+            check(&event(&[0x00, 0x0f], 1, Some("4")), false),
+            Some("pow: difficulty 4 is less than 8".to_string())
+        );
+        assert_eq!(
+            check(&event(&[0x00, 0x0f], 4, Some("16")), false),
+            Some("pow: difficulty 12 is less than 16".to_string())
+        );
+        assert_eq!(
+            check(&event(&[0x00, 0x0f], 1, Some("12")), true),
+            Some("pow: difficulty 12 is less than 20".to_string())
+        );
+
+        let mut allowed = event(&[], 1, None);
+        allowed.pubkey = vec![2; 32];
+        assert_eq!(check(&allowed, true), None);
+        // Such as an authenticated pubkey
+        assert_eq!(pow.check(&event(&[], 1, None), &[2; 32], true), None);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let pow = Pow::new(&PowSettings::default());
+        let check = |event: &Event, lockdown| pow.check(event, &event.pubkey, lockdown);
+        assert_eq!(check(&event(&[], 1, None), false), None);
+        // Lockdowns still require some
+        assert!(check(&event(&[], 1, None), true).is_some());
+    }
+}
@@ -0,0 +1,454 @@
+use std::{
+    net::IpAddr,
+    num::NonZeroUsize,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use ipnet::IpNet;
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::nostr::{from_npub, to_npub};
+use crate::policy::{Clock, KindRange};
+
+/// What events are counted by.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitKey {
+    #[default]
+    Pubkey,
+    /// The IP, or its subnet, that sent the event
+    Ip,
+}
+
+/// Allows `events` every `seconds` for each key, with bursts of up to `events`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitRule {
+    pub by: RateLimitKey,
+    /// Kinds counted, every kind when empty
+    pub kinds: Vec<KindRange>,
+    pub events: u32,
+    pub seconds: u64,
+    /// IPv4 addresses are grouped in subnets of this prefix length
+    pub ipv4_prefix: u8,
+    /// IPv6 addresses are grouped in subnets of this prefix length
+    pub ipv6_prefix: u8,
+}
+
+impl Default for RateLimitRule {
+    fn default() -> Self {
+        Self {
+            by: RateLimitKey::default(),
+            kinds: vec![],
+            events: 10,
+            seconds: 60,
+            ipv4_prefix: 32,
+            ipv6_prefix: 64,
+        }
+    }
+}
+
+/// Overrides the limits of a pubkey (npub or hex) or an IP (or CIDR subnet).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitOverride {
+    pub key: String,
+    /// Events allowed every `seconds` by each rule, unlimited when unset
+    pub events: Option<u32>,
+    pub seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitSettings {
+    pub rules: Vec<RateLimitRule>,
+    pub overrides: Vec<RateLimitOverride>,
+    /// Keys tracked at most, the least recently seen are forgotten first
+    pub max_keys: usize,
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self {
+            rules: vec![],
+            overrides: vec![],
+            max_keys: 100_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Where an override applies.
+#[derive(Debug, Clone, PartialEq)]
+enum OverrideTarget {
+    Pubkey(String),
+    Subnet(IpNet),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Limit {
+    events: u32,
+    seconds: u64,
+}
+
+/// Token bucket rate limits on the events of a pubkey or an IP.
+pub struct RateLimiter {
+    rules: Vec<RateLimitRule>,
+    /// Overrides and their limit, `None` for unlimited
+    overrides: Vec<(OverrideTarget, Option<Limit>)>,
+    /// Buckets keyed by rule index and pubkey or subnet
+    buckets: Mutex<LruCache<(usize, String), TokenBucket>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RateLimiter {
+    /// Creates a rate limiter. Invalid overrides are reported and skipped.
+    pub fn new(settings: &RateLimitSettings, clock: Arc<dyn Clock>) -> Self {
+        let overrides = settings
+            .overrides
+            .iter()
+            .filter_map(|o| {
+                let target = match parse_target(&o.key) {
+                    Some(target) => target,
+                    None => {
+                        warn!(key = %o.key, "invalid rate limit override, expected a pubkey or an IP");
+                        return None;
+                    }
+                };
+                let limit = o.events.map(|events| Limit {
+                    events,
+                    seconds: o.seconds.unwrap_or(60),
+                });
+                Some((target, limit))
+            })
+            .collect();
+
+        let capacity = NonZeroUsize::new(settings.max_keys).unwrap_or(NonZeroUsize::MIN);
+
+        Self {
+            rules: settings.rules.clone(),
+            overrides,
+            buckets: Mutex::new(LruCache::new(capacity)),
+            clock,
+        }
+    }
+
+    /// Counts an event against the limits. Returns why it is rate
+    /// limited, if it is, in which case no token is taken.
+    pub fn check(&self, pubkey: &[u8], ip: Option<&str>, kind: u64) -> Option<String> {
+        if self.rules.is_empty() {
+            return None;
+        }
+
+        let npub = to_npub(pubkey);
+        let ip = ip.and_then(|ip| IpAddr::from_str(ip).ok());
+
+        // Buckets of the rules applying to the event, and their limit
+        let mut applicable = vec![];
+        for (index, rule) in self.rules.iter().enumerate() {
+            if !rule.kinds.is_empty() && !rule.kinds.iter().any(|range| range.contains(kind)) {
+                continue;
+            }
+
+            let (key, limit) = match rule.by {
+                RateLimitKey::Pubkey => {
+                    let Some(npub) = &npub else { continue };
+                    (npub.clone(), self.override_for_pubkey(npub))
+                }
+                RateLimitKey::Ip => {
+                    let Some(ip) = ip else { continue };
+                    let subnet = subnet(ip, rule);
+                    (subnet.to_string(), self.override_for_ip(ip))
+                }
+            };
+
+            let limit = match limit {
+                // Unlimited
+                Some(None) => continue,
+                Some(Some(limit)) => limit.clone(),
+                None => Limit {
+                    events: rule.events,
+                    seconds: rule.seconds,
+                },
+            };
+            applicable.push(((index, key), limit));
+        }
+
+        let now = self.clock.now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        // Every bucket is refilled and checked before any token is taken
+        for ((index, key), limit) in &applicable {
+            let bucket = buckets.get_or_insert_mut((*index, key.clone()), || TokenBucket {
+                tokens: limit.events as f64,
+                updated: now,
+            });
+
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            let rate = limit.events as f64 / limit.seconds.max(1) as f64;
+            bucket.tokens = (bucket.tokens + elapsed * rate).min(limit.events as f64);
+            bucket.updated = now;
+
+            if bucket.tokens < 1.0 {
+                let rule = &self.rules[*index];
+                return Some(message(rule, limit));
+            }
+        }
+
+        for (key, _) in applicable {
+            if let Some(bucket) = buckets.get_mut(&key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        None
+    }
+
+    fn override_for_pubkey(&self, npub: &str) -> Option<Option<&Limit>> {
+        self.overrides
+            .iter()
+            .find_map(|(target, limit)| match target {
+                OverrideTarget::Pubkey(pubkey) if pubkey == npub => Some(limit.as_ref()),
+                _ => None,
+            })
+    }
+
+    fn override_for_ip(&self, ip: IpAddr) -> Option<Option<&Limit>> {
+        self.overrides
+            .iter()
+            .find_map(|(target, limit)| match target {
+                OverrideTarget::Subnet(subnet) if subnet.contains(&ip) => Some(limit.as_ref()),
+                _ => None,
+            })
+    }
+}
+
+/// Parses an override key: an npub or hex pubkey, an IP or a CIDR subnet.
+fn parse_target(key: &str) -> Option<OverrideTarget> {
+    if let Ok(subnet) = IpNet::from_str(key) {
+        return Some(OverrideTarget::Subnet(subnet));
+    }
+    if let Ok(ip) = IpAddr::from_str(key) {
+        return Some(OverrideTarget::Subnet(IpNet::from(ip)));
+    }
+
+    from_npub(key)
+        .or_else(|| hex::decode(key).ok())
+        .filter(|bytes| bytes.len() == 32)
+        .and_then(|bytes| to_npub(&bytes))
+        .map(OverrideTarget::Pubkey)
+}
+
+/// The subnet an IP is counted in.
+fn subnet(ip: IpAddr, rule: &RateLimitRule) -> IpNet {
+    let prefix = match ip {
+        IpAddr::V4(_) => rule.ipv4_prefix.min(32),
+        IpAddr::V6(_) => rule.ipv6_prefix.min(128),
+    };
+    IpNet::new(ip, prefix)
+        .map(|subnet| subnet.trunc())
+        .unwrap_or(IpNet::from(ip))
+}
+
+/// Describes the limit reached, sent to the client.
+fn message(rule: &RateLimitRule, limit: &Limit) -> String {
+    let kinds = match rule.kinds.as_slice() {
+        [] => String::new(),
+        kinds => {
+            let kinds: Vec<String> = kinds.iter().map(|k| k.to_string()).collect();
+            format!("kind {} ", kinds.join(","))
+        }
+    };
+    let by = match rule.by {
+        RateLimitKey::Pubkey => "pubkey",
+        RateLimitKey::Ip => "IP",
+    };
+
+    format!(
+        "rate-limited: more than {} {}events in {}s from this {}",
+        limit.events, kinds, limit.seconds, by
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    /// A clock only moving forward when told to.
+    struct ManualClock(Mutex<Instant>);
+
+    impl ManualClock {
+        fn advance(&self, duration: Duration) {
+            *self.0.lock().unwrap() += duration;
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+
+        fn timestamp(&self) -> i64 {
+            0
+        }
+    }
+
+    fn limiter(settings: RateLimitSettings) -> (Arc<ManualClock>, RateLimiter) {
+        let clock = Arc::new(ManualClock(Mutex::new(Instant::now())));
+        let limiter = RateLimiter::new(&settings, clock.clone());
+        (clock, limiter)
+    }
+
+    #[test]
+    fn test_pubkey_limit() {
+        let (clock, limiter) = limiter(RateLimitSettings {
+            rules: vec![RateLimitRule {
+                kinds: vec!["1".parse().unwrap()],
+                events: 10,
+                seconds: 60,
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        let alice = [1; 32];
+
+        for _ in 0..10 {
+            assert_eq!(limiter.check(&alice, None, 1), None);
+        }
+        let message = limiter.check(&alice, None, 1).unwrap();
+        assert_eq!(
+            message,
+            "rate-limited: more than 10 kind 1 events in 60s from this pubkey"
+        );
+
+        // Other kinds and pubkeys are not limited
+        assert_eq!(limiter.check(&alice, None, 7), None);
+        assert_eq!(limiter.check(&[2; 32], None, 1), None);
+
+        // A token every 6 seconds
+        clock.advance(Duration::from_secs(6));
+        assert_eq!(limiter.check(&alice, None, 1), None);
+        assert!(limiter.check(&alice, None, 1).is_some());
+    }
+
+    #[test]
+    fn test_kind_ranges() {
+        let (_, limiter) = limiter(RateLimitSettings {
+            rules: vec![RateLimitRule {
+                kinds: vec!["30000-39999".parse().unwrap()],
+                events: 1,
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        let alice = [1; 32];
+
+        assert_eq!(limiter.check(&alice, None, 30023), None);
+        assert_eq!(
+            limiter.check(&alice, None, 30023).unwrap(),
+            "rate-limited: more than 1 kind 30000-39999 events in 60s from this pubkey"
+        );
+        assert_eq!(limiter.check(&alice, None, 1), None);
+    }
+
+    #[test]
+    fn test_ip_subnet() {
+        let (_, limiter) = limiter(RateLimitSettings {
+            rules: vec![RateLimitRule {
+                by: RateLimitKey::Ip,
+                events: 2,
+                ipv4_prefix: 24,
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+
+        assert_eq!(limiter.check(&[1; 32], Some("203.0.113.1"), 1), None);
+        assert_eq!(limiter.check(&[2; 32], Some("203.0.113.2"), 1), None);
+        assert!(limiter
+            .check(&[3; 32], Some("203.0.113.3"), 1)
+            .unwrap()
+            .ends_with("from this IP"));
+        assert_eq!(limiter.check(&[3; 32], Some("198.51.100.1"), 1), None);
+        // Events without an IP are only limited by pubkey
+        assert_eq!(limiter.check(&[3; 32], None, 1), None);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let relay = to_npub(&[9; 32]).unwrap();
+        let (_, limiter) = limiter(RateLimitSettings {
+            rules: vec![
+                RateLimitRule {
+                    events: 1,
+                    ..Default::default()
+                },
+                RateLimitRule {
+                    by: RateLimitKey::Ip,
+                    events: 1,
+                    ..Default::default()
+                },
+            ],
+            overrides: vec![
+                RateLimitOverride {
+                    key: relay,
+                    events: None,
+                    seconds: None,
+                },
+                RateLimitOverride {
+                    key: "10.0.0.0/8".to_string(),
+                    events: Some(3),
+                    seconds: Some(60),
+                },
+                RateLimitOverride {
+                    key: "not a key".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        });
+        assert_eq!(limiter.overrides.len(), 2);
+
+        // Unlimited pubkey, but its IP is still limited
+        for _ in 0..3 {
+            assert_eq!(limiter.check(&[9; 32], Some("10.1.2.3"), 1), None);
+        }
+        assert!(limiter.check(&[9; 32], Some("10.1.2.3"), 1).is_some());
+
+        // A failing rule doesn't take the tokens of the others
+        assert_eq!(limiter.check(&[1; 32], None, 1), None);
+        assert!(limiter.check(&[1; 32], Some("10.1.2.3"), 1).is_some());
+        assert!(limiter.check(&[1; 32], None, 1).is_some());
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let (_, limiter) = limiter(RateLimitSettings {
+            rules: vec![RateLimitRule {
+                events: 1,
+                ..Default::default()
+            }],
+            max_keys: 2,
+            ..Default::default()
+        });
+
+        assert_eq!(limiter.check(&[1; 32], None, 1), None);
+        assert_eq!(limiter.check(&[2; 32], None, 1), None);
+        assert_eq!(limiter.check(&[3; 32], None, 1), None);
+        assert_eq!(limiter.buckets.lock().unwrap().len(), 2);
+
+        // The first pubkey was forgotten, and starts with a full bucket
+        assert_eq!(limiter.check(&[1; 32], None, 1), None);
+        assert!(limiter.check(&[3; 32], None, 1).is_some());
+    }
+}
@@ -0,0 +1,604 @@
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    os::unix::net::UnixDatagram,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use banhammer_grpc::{
+    grpc::{Event, EventRequest},
+    BanTypesEnum,
+};
+use bech32::{ToBase32, Variant};
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::hammer::Ban;
+use crate::logging::{logfmt_value, LogFormat};
+use crate::policy::Violation;
+
+fn default_max_size() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_max_files() -> usize {
+    5
+}
+
+fn default_syslog_socket() -> PathBuf {
+    PathBuf::from("/dev/log")
+}
+
+/// Where rejection records are written.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum SinkSettings {
+    Stdout,
+    /// A file rotated once it reaches `max_size` bytes. Rotated files
+    /// are suffixed `.1` (the most recent) to `.<max_files>`.
+    File {
+        path: PathBuf,
+        #[serde(default = "default_max_size")]
+        max_size: u64,
+        #[serde(default = "default_max_files")]
+        max_files: usize,
+    },
+    /// A syslog daemon listening on a local datagram socket
+    Syslog {
+        #[serde(default = "default_syslog_socket")]
+        socket: PathBuf,
+    },
+}
+
+/// Settings of the rejection log.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RejectionSettings {
+    pub format: LogFormat,
+    /// Replaces IPs and pubkeys with a salted hash, requires `hash_salt`
+    pub hash_identifiers: bool,
+    pub hash_salt: Option<String>,
+    pub sinks: Vec<SinkSettings>,
+}
+
+impl Default for RejectionSettings {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Json,
+            hash_identifiers: false,
+            hash_salt: None,
+            sinks: vec![SinkSettings::Stdout],
+        }
+    }
+}
+
+/// An event rejected by a ban.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+    pub ban_type: BanTypesEnum,
+    /// The matching ban, if it could be found
+    pub ban: Option<Ban>,
+    pub event: Event,
+    pub ip: String,
+    /// HTTP headers of the client, when the relay sent them
+    pub origin: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl Rejection {
+    pub fn new(ban_type: BanTypesEnum, ban: Option<&Ban>, event: &Event, ip: &str) -> Self {
+        Self {
+            ban_type,
+            ban: ban.cloned(),
+            event: event.clone(),
+            ip: ip.to_string(),
+            origin: None,
+            user_agent: None,
+        }
+    }
+}
+
+/// Receives formatted rejection records, one line each.
+pub trait RejectionSink: Send + Sync {
+    fn write(&self, line: &str) -> io::Result<()>;
+}
+
+pub struct StdoutSink;
+
+impl RejectionSink for StdoutSink {
+    fn write(&self, line: &str) -> io::Result<()> {
+        writeln!(io::stdout().lock(), "{}", line)
+    }
+}
+
+/// Appends records to a file, rotated by size.
+pub struct FileSink {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: Mutex<Option<fs::File>>,
+}
+
+impl FileSink {
+    pub fn new(path: &Path, max_size: u64, max_files: usize) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            max_size,
+            max_files,
+            file: Mutex::new(None),
+        }
+    }
+
+    fn rotated(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        if self.max_files == 0 {
+            return fs::remove_file(&self.path);
+        }
+
+        for index in (1..self.max_files).rev() {
+            let from = self.rotated(index);
+            if from.exists() {
+                fs::rename(from, self.rotated(index + 1))?;
+            }
+        }
+        fs::rename(&self.path, self.rotated(1))
+    }
+}
+
+impl RejectionSink for FileSink {
+    fn write(&self, line: &str) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+
+        let size = match file.as_ref() {
+            Some(file) => file.metadata()?.len(),
+            None => fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0),
+        };
+        if size > 0 && size + line.len() as u64 + 1 > self.max_size {
+            *file = None;
+            self.rotate()?;
+        }
+
+        if file.is_none() {
+            *file = Some(
+                fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?,
+            );
+        }
+
+        writeln!(file.as_mut().unwrap(), "{}", line)
+    }
+}
+
+/// Sends records to a local syslog daemon, with the `local0` facility.
+pub struct SyslogSink {
+    socket: UnixDatagram,
+    path: PathBuf,
+}
+
+impl SyslogSink {
+    /// `local0.info`
+    const PRIORITY: u8 = 16 * 8 + 6;
+
+    pub fn new(path: &Path) -> io::Result<Self> {
+        Ok(Self {
+            socket: UnixDatagram::unbound()?,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl RejectionSink for SyslogSink {
+    fn write(&self, line: &str) -> io::Result<()> {
+        let message = format!(
+            "<{}>banhammer[{}]: {}",
+            Self::PRIORITY,
+            std::process::id(),
+            line
+        );
+        self.socket.send_to(message.as_bytes(), &self.path)?;
+        Ok(())
+    }
+}
+
+/// Formats rejections and writes them to every sink.
+pub struct RejectionLog {
+    format: LogFormat,
+    /// Salt of the identifier hashes, when identifiers are hashed
+    salt: Option<String>,
+    sinks: Vec<Box<dyn RejectionSink>>,
+}
+
+impl fmt::Debug for RejectionLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "RejectionLog({:?}, {} sinks)",
+            self.format,
+            self.sinks.len()
+        )
+    }
+}
+
+impl Default for RejectionLog {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Json,
+            salt: None,
+            sinks: vec![Box::new(StdoutSink)],
+        }
+    }
+}
+
+impl RejectionLog {
+    pub fn new(
+        format: LogFormat,
+        salt: Option<String>,
+        sinks: Vec<Box<dyn RejectionSink>>,
+    ) -> Self {
+        Self {
+            format,
+            salt,
+            sinks,
+        }
+    }
+
+    /// Opens the sinks of the settings. Sinks that can't be opened
+    /// are reported and skipped.
+    pub fn from_settings(settings: &RejectionSettings) -> Self {
+        let mut sinks: Vec<Box<dyn RejectionSink>> = Vec::new();
+
+        for sink in &settings.sinks {
+            match sink {
+                SinkSettings::Stdout => sinks.push(Box::new(StdoutSink)),
+                SinkSettings::File {
+                    path,
+                    max_size,
+                    max_files,
+                } => sinks.push(Box::new(FileSink::new(path, *max_size, *max_files))),
+                SinkSettings::Syslog { socket } => match SyslogSink::new(socket) {
+                    Ok(sink) => sinks.push(Box::new(sink)),
+                    Err(e) => warn!(socket = %socket.display(), "syslog sink disabled: {}", e),
+                },
+            }
+        }
+
+        let salt = match settings.hash_identifiers {
+            true => Some(settings.hash_salt.clone().unwrap_or_default()),
+            false => None,
+        };
+
+        Self::new(settings.format, salt, sinks)
+    }
+
+    /// Hides an identifier, such as an IP or an npub, when hashing is enabled.
+    pub fn hash(&self, value: &str) -> String {
+        match &self.salt {
+            Some(salt) => {
+                let digest = Sha256::new()
+                    .chain_update(salt)
+                    .chain_update(value)
+                    .finalize();
+                hex::encode(&digest[..8])
+            }
+            None => value.to_string(),
+        }
+    }
+
+    /// Fields of a rejection record, in output order.
+    pub fn fields(&self, rejection: &Rejection) -> Vec<(&'static str, String)> {
+        let ban = rejection.ban.as_ref();
+        // The rules of user and IP bans are the identifiers themselves
+        let rule = ban.map_or(String::new(), |b| match b.ban_type {
+            BanTypesEnum::USER | BanTypesEnum::IP => self.hash(&b.content),
+            _ => b.content.clone(),
+        });
+        let cause = vec![
+            ("type", rejection.ban_type.to_string()),
+            ("rule", rule),
+            (
+                "reason",
+                ban.and_then(|b| b.reason.clone()).unwrap_or_default(),
+            ),
+            (
+                "source",
+                ban.and_then(|b| b.source.clone()).unwrap_or_default(),
+            ),
+        ];
+
+        self.event_fields(
+            cause,
+            &rejection.event,
+            &rejection.ip,
+            rejection.origin.as_deref(),
+            rejection.user_agent.as_deref(),
+        )
+    }
+
+    /// Fields of the record of an event failing a check, in output order.
+    pub fn violation_fields(
+        &self,
+        violation: &Violation,
+        req: &EventRequest,
+    ) -> Vec<(&'static str, String)> {
+        let cause = vec![
+            ("check", violation.check.to_string()),
+            ("message", violation.message.clone()),
+        ];
+
+        self.event_fields(
+            cause,
+            &req.event.clone().unwrap_or_default(),
+            req.ip_addr.as_deref().unwrap_or_default(),
+            req.origin.as_deref(),
+            req.user_agent.as_deref(),
+        )
+    }
+
+    /// Fields describing why an event was rejected, followed by the event
+    /// and its client.
+    fn event_fields(
+        &self,
+        cause: Vec<(&'static str, String)>,
+        event: &Event,
+        ip: &str,
+        origin: Option<&str>,
+        user_agent: Option<&str>,
+    ) -> Vec<(&'static str, String)> {
+        // Seconds since the epoch, kept as is when it isn't a valid date
+        let created_at = i64::try_from(event.created_at)
+            .ok()
+            .and_then(|seconds| Utc.timestamp_opt(seconds, 0).single())
+            .map_or(event.created_at.to_string(), |date| date.to_rfc3339());
+        let pubkey =
+            bech32::encode("npub", event.pubkey.to_base32(), Variant::Bech32).unwrap_or_default();
+
+        let mut fields = vec![
+            ("ts", Utc::now().to_rfc3339()),
+            ("msg", "rejected".to_string()),
+        ];
+        fields.extend(cause);
+        fields.extend([
+            ("event", hex::encode(&event.id)),
+            ("kind", event.kind.to_string()),
+            ("created_at", created_at),
+            ("pubkey", self.hash(&pubkey)),
+            ("ip", self.hash(ip)),
+            ("origin", origin.unwrap_or_default().to_string()),
+            ("user_agent", user_agent.unwrap_or_default().to_string()),
+        ]);
+        fields
+    }
+
+    pub fn format(&self, rejection: &Rejection) -> String {
+        self.format_fields(self.fields(rejection))
+    }
+
+    fn format_fields(&self, fields: Vec<(&'static str, String)>) -> String {
+        match self.format {
+            LogFormat::Json => {
+                let object: serde_json::Map<String, serde_json::Value> = fields
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let value = match key {
+                            "kind" => value.parse::<u64>().map(Into::into).unwrap_or(value.into()),
+                            _ => value.into(),
+                        };
+                        (key.to_string(), value)
+                    })
+                    .collect();
+                serde_json::Value::Object(object).to_string()
+            }
+            LogFormat::Logfmt => fields
+                .into_iter()
+                .map(|(key, value)| format!("{}={}", key, logfmt_value(&value)))
+                .collect::<Vec<String>>()
+                .join(" "),
+        }
+    }
+
+    /// Writes a rejection to every sink. Failures are reported
+    /// and don't prevent writing to the other sinks.
+    pub fn record(&self, rejection: &Rejection) {
+        self.write(&self.format(rejection));
+    }
+
+    /// Writes an event failing a check, with the check and its message.
+    pub fn record_violation(&self, violation: &Violation, req: &EventRequest) {
+        self.write(&self.format_fields(self.violation_fields(violation, req)));
+    }
+
+    fn write(&self, line: &str) {
+        for sink in &self.sinks {
+            if let Err(e) = sink.write(line) {
+                warn!("rejection log: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    use crate::policy::Check;
+
+    /// Keeps the records in memory.
+    #[derive(Clone, Default)]
+    struct MemorySink(Arc<Mutex<Vec<String>>>);
+
+    impl RejectionSink for MemorySink {
+        fn write(&self, line: &str) -> io::Result<()> {
+            self.0.lock().unwrap().push(line.to_string());
+            Ok(())
+        }
+    }
+
+    fn rejection() -> Rejection {
+        let ban = Ban {
+            content: "spam words".to_string(),
+            regex: false,
+            reason: None,
+            date: None,
+            source: Some("bans.yaml".to_string()),
+            ban_type: BanTypesEnum::CONTENT,
+        };
+        let event = Event {
+            id: vec![0xab; 32],
+            pubkey: vec![0; 32],
+            kind: 1,
+            created_at: 1700000000,
+            content: "spam words".to_string(),
+            ..Default::default()
+        };
+
+        let mut rejection = Rejection::new(BanTypesEnum::CONTENT, Some(&ban), &event, "1.2.3.4");
+        rejection.user_agent = Some("spambot/1.0".to_string());
+        rejection
+    }
+
+    #[test]
+    fn test_json() {
+        let sink = MemorySink::default();
+        let log = RejectionLog::new(LogFormat::Json, None, vec![Box::new(sink.clone())]);
+        log.record(&rejection());
+
+        let lines = sink.0.lock().unwrap();
+        let record: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(record["event"], hex::encode([0xab; 32]));
+        assert_eq!(record["type"], "content");
+        assert_eq!(record["rule"], "spam words");
+        assert_eq!(record["source"], "bans.yaml");
+        assert_eq!(record["kind"], 1);
+        assert_eq!(record["ip"], "1.2.3.4");
+        assert_eq!(record["created_at"], "2023-11-14T22:13:20+00:00");
+        assert!(record["pubkey"].as_str().unwrap().starts_with("npub1"));
+        assert_eq!(record["origin"], "");
+        assert_eq!(record["user_agent"], "spambot/1.0");
+    }
+
+    #[test]
+    fn test_violation() {
+        let sink = MemorySink::default();
+        let log = RejectionLog::new(LogFormat::Json, None, vec![Box::new(sink.clone())]);
+        let violation = Violation::new(
+            Check::RateLimit,
+            "rate-limited: slow down there chief".to_string(),
+        );
+        let req = EventRequest {
+            event: Some(rejection().event),
+            ip_addr: Some("1.2.3.4".to_string()),
+            origin: Some("https://spam.example".to_string()),
+            ..Default::default()
+        };
+        log.record_violation(&violation, &req);
+
+        let lines = sink.0.lock().unwrap();
+        let record: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+        assert_eq!(record["check"], "rate_limit");
+        assert_eq!(record["message"], "rate-limited: slow down there chief");
+        assert_eq!(record["event"], hex::encode([0xab; 32]));
+        assert_eq!(record["kind"], 1);
+        assert_eq!(record["ip"], "1.2.3.4");
+        assert_eq!(record["origin"], "https://spam.example");
+        assert!(record.get("type").is_none());
+    }
+
+    #[test]
+    fn test_created_at_out_of_range() {
+        let log = RejectionLog::new(LogFormat::Json, None, vec![]);
+        let mut rejection = rejection();
+
+        // Used to wrap around to 1969
+        rejection.event.created_at = u64::MAX;
+        let fields = log.fields(&rejection);
+        let created_at = fields.iter().find(|(key, _)| *key == "created_at").unwrap();
+        assert_eq!(created_at.1, u64::MAX.to_string());
+
+        rejection.event.created_at = 0;
+        let fields = log.fields(&rejection);
+        let created_at = fields.iter().find(|(key, _)| *key == "created_at").unwrap();
+        assert_eq!(created_at.1, "1970-01-01T00:00:00+00:00");
+    }
+
+    #[test]
+    fn test_logfmt_and_hashing() {
+        let log = RejectionLog::new(LogFormat::Logfmt, Some("salt".to_string()), vec![]);
+        let line = log.format(&rejection());
+
+        assert!(line.contains(" type=content "));
+        assert!(line.contains(" rule=\"spam words\" "));
+        assert!(!line.contains("1.2.3.4"));
+        assert!(!line.contains("npub1"));
+        assert!(line.contains(&format!(" ip={}", log.hash("1.2.3.4"))));
+
+        // User and IP bans are hashed like the identifiers they match
+        let mut rejection = rejection();
+        let npub = bech32::encode("npub", [0u8; 32].to_base32(), Variant::Bech32).unwrap();
+        for (ban_type, content) in [
+            (BanTypesEnum::USER, npub),
+            (BanTypesEnum::IP, "1.2.3.4".to_string()),
+        ] {
+            let ban = rejection.ban.as_mut().unwrap();
+            ban.ban_type = ban_type;
+            ban.content = content.clone();
+            rejection.ban_type = ban_type;
+
+            let line = log.format(&rejection);
+            assert!(!line.contains(&content));
+            assert!(line.contains(&format!(" rule={} ", log.hash(&content))));
+        }
+
+        // Hashes depend on the salt
+        let other = RejectionLog::new(LogFormat::Logfmt, Some("other".to_string()), vec![]);
+        assert_ne!(log.hash("1.2.3.4"), other.hash("1.2.3.4"));
+    }
+
+    #[test]
+    fn test_file_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rejections.log");
+        let sink = FileSink::new(&path, 20, 2);
+
+        for line in ["first line", "second line", "third line", "fourth line"] {
+            sink.write(line).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "fourth line\n");
+        assert_eq!(fs::read_to_string(sink.rotated(1)).unwrap(), "third line\n");
+        assert_eq!(
+            fs::read_to_string(sink.rotated(2)).unwrap(),
+            "second line\n"
+        );
+        assert!(!sink.rotated(3).exists());
+    }
+
+    #[test]
+    fn test_syslog() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.sock");
+        let daemon = UnixDatagram::bind(&path).unwrap();
+
+        SyslogSink::new(&path)
+            .unwrap()
+            .write("msg=rejected")
+            .unwrap();
+
+        let mut buffer = [0; 256];
+        let size = daemon.recv(&mut buffer).unwrap();
+        let message = String::from_utf8_lossy(&buffer[..size]);
+        assert!(message.starts_with("<134>banhammer["));
+        assert!(message.ends_with("]: msg=rejected"));
+    }
+}
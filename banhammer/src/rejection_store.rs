@@ -0,0 +1,482 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use banhammer_grpc::grpc::{DecisionMatch, DecisionTrace, RejectionRecord};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::{params, params_from_iter, Connection};
+use serde::{Deserialize, Serialize};
+use tracing::{error, warn};
+
+/// Rejections waiting to be written before new ones are dropped
+const QUEUE: usize = 4096;
+
+/// Rejections written in a single transaction, at most
+const BATCH: usize = 512;
+
+/// Time between two retention passes
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Format of the stored timestamps, which sort as strings
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RejectionStoreSettings {
+    /// SQLite database, rejections are not stored when unset
+    pub path: Option<String>,
+    /// Rejections kept, the oldest are removed first
+    pub max_rows: u64,
+    /// Rejections older than this many days are removed
+    pub max_age_days: u32,
+    /// Also stores the rejected events
+    pub store_events: bool,
+}
+
+impl Default for RejectionStoreSettings {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_rows: 100_000,
+            max_age_days: 30,
+            store_events: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RejectionStoreError {
+    Sqlite(PathBuf, rusqlite::Error),
+}
+
+impl fmt::Display for RejectionStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RejectionStoreError::Sqlite(path, e) => write!(f, "{}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for RejectionStoreError {}
+
+/// Filters applied when querying the rejections.
+#[derive(Debug, Clone, Default)]
+pub struct RejectionQuery {
+    /// npub encoded
+    pub pubkey: Option<String>,
+    pub event_id: Option<String>,
+    pub ip: Option<String>,
+    pub since: Option<NaiveDateTime>,
+    pub until: Option<NaiveDateTime>,
+    /// Only returns the latest matching rejections
+    pub limit: usize,
+}
+
+/// Denied decisions, stored in a bounded SQLite database so moderators
+/// can find out why an event was rejected.
+pub struct RejectionStore {
+    path: PathBuf,
+    connection: Mutex<Connection>,
+    settings: RejectionStoreSettings,
+}
+
+impl RejectionStore {
+    pub fn open(
+        path: &Path,
+        settings: RejectionStoreSettings,
+    ) -> Result<Self, RejectionStoreError> {
+        let sqlite = |e| RejectionStoreError::Sqlite(path.into(), e);
+
+        let connection = Connection::open(path).map_err(sqlite)?;
+        connection
+            .execute_batch(
+                "PRAGMA journal_mode = WAL;
+                CREATE TABLE IF NOT EXISTS rejections (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    timestamp TEXT NOT NULL,
+                    denied INTEGER NOT NULL,
+                    event_id TEXT NOT NULL,
+                    pubkey TEXT NOT NULL,
+                    ip TEXT,
+                    kind INTEGER NOT NULL,
+                    matches TEXT NOT NULL,
+                    duration_us INTEGER NOT NULL,
+                    event TEXT,
+                    message TEXT
+                );
+                CREATE INDEX IF NOT EXISTS rejections_timestamp ON rejections (timestamp);
+                CREATE INDEX IF NOT EXISTS rejections_pubkey ON rejections (pubkey);
+                CREATE INDEX IF NOT EXISTS rejections_event_id ON rejections (event_id);
+                CREATE INDEX IF NOT EXISTS rejections_ip ON rejections (ip);",
+            )
+            .map_err(sqlite)?;
+
+        // Databases created before rate limiting have no message column
+        let has_message: bool = connection
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('rejections') WHERE name = 'message'",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(sqlite)?;
+        if !has_message {
+            connection
+                .execute("ALTER TABLE rejections ADD COLUMN message TEXT", [])
+                .map_err(sqlite)?;
+        }
+
+        Ok(Self {
+            path: path.into(),
+            connection: Mutex::new(connection),
+            settings,
+        })
+    }
+
+    fn error(&self, e: rusqlite::Error) -> RejectionStoreError {
+        RejectionStoreError::Sqlite(self.path.clone(), e)
+    }
+
+    /// Writes rejections in a single transaction.
+    pub fn write(&self, rejections: &[RejectionRecord]) -> Result<(), RejectionStoreError> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction().map_err(|e| self.error(e))?;
+
+        for rejection in rejections {
+            let trace = rejection.decision.clone().unwrap_or_default();
+            let matches: Vec<StoredMatch> = trace.matches.iter().map(StoredMatch::from).collect();
+
+            transaction
+                .execute(
+                    "INSERT INTO rejections
+                    (timestamp, denied, event_id, pubkey, ip, kind, matches, duration_us, event, message)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                    params![
+                        trace.timestamp,
+                        trace.denied,
+                        trace.event_id,
+                        trace.pubkey,
+                        trace.ip,
+                        trace.kind as i64,
+                        serde_json::to_string(&matches).unwrap_or_default(),
+                        trace.duration_us as i64,
+                        rejection.event,
+                        trace.message,
+                    ],
+                )
+                .map_err(|e| self.error(e))?;
+        }
+
+        transaction.commit().map_err(|e| self.error(e))
+    }
+
+    /// Removes the rejections beyond the retention limits,
+    /// returns how many were removed.
+    pub fn prune(&self, now: DateTime<Utc>) -> Result<usize, RejectionStoreError> {
+        let connection = self.connection.lock().unwrap();
+        let cutoff = now - chrono::Duration::days(self.settings.max_age_days as i64);
+
+        let expired = connection
+            .execute(
+                "DELETE FROM rejections WHERE timestamp < ?1",
+                params![cutoff.format(TIMESTAMP_FORMAT).to_string()],
+            )
+            .map_err(|e| self.error(e))?;
+        let overflow = connection
+            .execute(
+                "DELETE FROM rejections WHERE id <= (SELECT MAX(id) FROM rejections) - ?1",
+                params![self.settings.max_rows as i64],
+            )
+            .map_err(|e| self.error(e))?;
+
+        Ok(expired + overflow)
+    }
+
+    /// Returns the latest rejections matching a query, the latest first.
+    pub fn query(
+        &self,
+        query: &RejectionQuery,
+    ) -> Result<Vec<RejectionRecord>, RejectionStoreError> {
+        let mut conditions = vec![];
+        let mut values = vec![];
+
+        let filters = [
+            ("pubkey = ?", query.pubkey.clone()),
+            ("event_id = ?", query.event_id.clone()),
+            ("ip = ?", query.ip.clone()),
+            (
+                "timestamp >= ?",
+                query.since.map(|d| d.format(TIMESTAMP_FORMAT).to_string()),
+            ),
+            (
+                "timestamp <= ?",
+                query.until.map(|d| d.format(TIMESTAMP_FORMAT).to_string()),
+            ),
+        ];
+        for (condition, value) in filters {
+            if let Some(value) = value {
+                conditions.push(condition);
+                values.push(value);
+            }
+        }
+
+        let mut sql = "SELECT timestamp, denied, event_id, pubkey, ip, kind, matches, \
+            duration_us, event, message FROM rejections"
+            .to_string();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(&format!(" ORDER BY id DESC LIMIT {}", query.limit));
+
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(&sql).map_err(|e| self.error(e))?;
+        let rows = statement
+            .query_map(params_from_iter(values), |row| {
+                let matches: String = row.get(6)?;
+                let matches: Vec<StoredMatch> = serde_json::from_str(&matches).unwrap_or_default();
+
+                Ok(RejectionRecord {
+                    decision: Some(DecisionTrace {
+                        timestamp: row.get(0)?,
+                        denied: row.get(1)?,
+                        event_id: row.get(2)?,
+                        pubkey: row.get(3)?,
+                        ip: row.get(4)?,
+                        kind: row.get::<_, i64>(5)? as u64,
+                        matches: matches.into_iter().map(DecisionMatch::from).collect(),
+                        duration_us: row.get::<_, i64>(7)? as u64,
+                        message: row.get(9)?,
+                    }),
+                    event: row.get(8)?,
+                })
+            })
+            .map_err(|e| self.error(e))?;
+
+        rows.collect::<Result<_, _>>().map_err(|e| self.error(e))
+    }
+}
+
+/// A matching ban, stored as JSON.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredMatch {
+    ban_type: i32,
+    rule_id: Option<String>,
+    content: Option<String>,
+    reason: Option<String>,
+}
+
+impl From<&DecisionMatch> for StoredMatch {
+    fn from(value: &DecisionMatch) -> Self {
+        Self {
+            ban_type: value.ban_type,
+            rule_id: value.rule_id.clone(),
+            content: value.content.clone(),
+            reason: value.reason.clone(),
+        }
+    }
+}
+
+impl From<StoredMatch> for DecisionMatch {
+    fn from(value: StoredMatch) -> Self {
+        Self {
+            ban_type: value.ban_type,
+            rule_id: value.rule_id,
+            content: value.content,
+            reason: value.reason,
+        }
+    }
+}
+
+/// Queues rejections for the store, written by a background thread
+/// so that `event_admit` never waits for the disk.
+#[derive(Clone)]
+pub struct RejectionWriter {
+    sender: SyncSender<RejectionRecord>,
+    store_events: bool,
+}
+
+impl RejectionWriter {
+    pub fn spawn(store: Arc<RejectionStore>) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(QUEUE);
+        let store_events = store.settings.store_events;
+        thread::spawn(move || write_loop(store, receiver));
+
+        Self {
+            sender,
+            store_events,
+        }
+    }
+
+    /// Whether the rejected events should be provided.
+    pub fn store_events(&self) -> bool {
+        self.store_events
+    }
+
+    /// Queues a rejection, dropped when the queue is full.
+    pub fn record(&self, rejection: RejectionRecord) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(rejection) {
+            warn!("rejection store queue is full, dropping a rejection");
+        }
+    }
+}
+
+fn write_loop(store: Arc<RejectionStore>, receiver: Receiver<RejectionRecord>) {
+    let mut last_prune: Option<Instant> = None;
+
+    loop {
+        let first = match receiver.recv_timeout(PRUNE_INTERVAL) {
+            Ok(rejection) => Some(rejection),
+            Err(mpsc::RecvTimeoutError::Timeout) => None,
+            Err(mpsc::RecvTimeoutError::Disconnected) => return,
+        };
+
+        let batch: Vec<RejectionRecord> = first
+            .into_iter()
+            .chain(receiver.try_iter().take(BATCH - 1))
+            .collect();
+        if !batch.is_empty() {
+            if let Err(e) = store.write(&batch) {
+                error!("rejection store: {}", e);
+            }
+        }
+
+        if last_prune.is_none_or(|last| last.elapsed() >= PRUNE_INTERVAL) {
+            if let Err(e) = store.prune(Utc::now()) {
+                error!("rejection store: {}", e);
+            }
+            last_prune = Some(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use banhammer_grpc::BanTypesEnum;
+
+    use crate::audit::parse_date;
+
+    fn record(timestamp: &str, pubkey: &str, event_id: &str) -> RejectionRecord {
+        RejectionRecord {
+            decision: Some(DecisionTrace {
+                timestamp: timestamp.to_string(),
+                denied: true,
+                event_id: event_id.to_string(),
+                pubkey: pubkey.to_string(),
+                ip: Some("1.1.1.1".to_string()),
+                kind: 1,
+                matches: vec![DecisionMatch {
+                    ban_type: BanTypesEnum::CONTENT as i32,
+                    rule_id: Some("abcdef".to_string()),
+                    content: Some("spam".to_string()),
+                    reason: Some("no spam".to_string()),
+                }],
+                duration_us: 12,
+                message: None,
+            }),
+            event: None,
+        }
+    }
+
+    fn store(settings: RejectionStoreSettings) -> (tempfile::TempDir, RejectionStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = RejectionStore::open(&dir.path().join("rejections.db"), settings).unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn test_write_and_query() {
+        let (_dir, store) = store(RejectionStoreSettings::default());
+        store
+            .write(&[
+                record("2024-01-01T10:00:00.000", "npub1alice", "e1"),
+                record("2024-01-02T10:00:00.000", "npub1bob", "e2"),
+                record("2024-01-03T10:00:00.000", "npub1alice", "e3"),
+            ])
+            .unwrap();
+
+        let query =
+            |query: RejectionQuery| store.query(&RejectionQuery { limit: 10, ..query }).unwrap();
+        let event_ids = |records: Vec<RejectionRecord>| -> Vec<String> {
+            records
+                .into_iter()
+                .map(|r| r.decision.unwrap().event_id)
+                .collect()
+        };
+
+        let all = query(RejectionQuery::default());
+        assert_eq!(event_ids(all.clone()), vec!["e3", "e2", "e1"]);
+        assert_eq!(
+            all[0],
+            record("2024-01-03T10:00:00.000", "npub1alice", "e3")
+        );
+
+        let alice = query(RejectionQuery {
+            pubkey: Some("npub1alice".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(event_ids(alice), vec!["e3", "e1"]);
+
+        let event = query(RejectionQuery {
+            event_id: Some("e2".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(event_ids(event), vec!["e2"]);
+
+        let dates = query(RejectionQuery {
+            since: parse_date("2024-01-02"),
+            until: parse_date("2024-01-03"),
+            ..Default::default()
+        });
+        assert_eq!(event_ids(dates), vec!["e2"]);
+
+        let latest = store
+            .query(&RejectionQuery {
+                limit: 1,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(event_ids(latest), vec!["e3"]);
+    }
+
+    #[test]
+    fn test_prune() {
+        let (_dir, store) = store(RejectionStoreSettings {
+            max_rows: 2,
+            max_age_days: 30,
+            ..Default::default()
+        });
+        let now = DateTime::parse_from_rfc3339("2024-03-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        store
+            .write(&[
+                record("2024-01-01T00:00:00.000", "npub1alice", "expired"),
+                record("2024-02-20T00:00:00.000", "npub1alice", "overflow"),
+                record("2024-02-21T00:00:00.000", "npub1alice", "kept1"),
+                record("2024-02-22T00:00:00.000", "npub1alice", "kept2"),
+            ])
+            .unwrap();
+
+        assert_eq!(store.prune(now).unwrap(), 2);
+        let kept: Vec<String> = store
+            .query(&RejectionQuery {
+                limit: 10,
+                ..Default::default()
+            })
+            .unwrap()
+            .into_iter()
+            .map(|r| r.decision.unwrap().event_id)
+            .collect();
+        assert_eq!(kept, vec!["kept2", "kept1"]);
+    }
+}
@@ -0,0 +1,680 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use banhammer_grpc::banlist::{self, BanListFormat};
+use chrono::Utc;
+use reqwest::{
+    header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::hammer::{Ban, BanHammer};
+use crate::metrics::Metrics;
+use crate::nostr::{from_npub, verify_signature};
+
+fn default_refresh_interval() -> u64 {
+    3600
+}
+
+fn default_max_size() -> u64 {
+    1024 * 1024
+}
+
+fn default_timeout() -> u64 {
+    30
+}
+
+/// Seconds to wait for a connection, within the request timeout
+const CONNECT_TIMEOUT: u64 = 10;
+
+/// Maximum size of a detached signature, hex encoded with some whitespace
+const MAX_SIGNATURE_SIZE: u64 = 1024;
+
+/// A shared ban list fetched over HTTP.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RemoteSource {
+    pub url: String,
+    /// Seconds between two fetches
+    #[serde(default = "default_refresh_interval")]
+    pub refresh_interval: u64,
+    /// Maximum size of the ban list, in bytes
+    #[serde(default = "default_max_size")]
+    pub max_size: u64,
+    /// Seconds a fetch can take before it is given up
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Expected SHA-256 checksum of the ban list, hex encoded.
+    /// Pins the list to a known revision.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Public key, npub or hex encoded, that must sign the ban list.
+    /// Pins the list to its publisher rather than to a revision.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pubkey: Option<String>,
+    /// Url of the detached signature, `<url>.sig` by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature_url: Option<String>,
+}
+
+impl RemoteSource {
+    /// Loads remote sources declarations from a YAML file.
+    pub fn load_all(path: &Path) -> Vec<RemoteSource> {
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!(path = %path.display(), "{}", e);
+                return Vec::new();
+            }
+        };
+
+        match serde_yaml::from_reader(file) {
+            Ok(sources) => sources,
+            Err(e) => {
+                warn!(path = %path.display(), "{}", e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Validators of the last good copy, used for conditional requests.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CacheMetadata {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub fetched_at: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum FetchOutcome {
+    /// A new copy of the list was fetched
+    Updated(Vec<Ban>),
+    /// The server reported the list did not change
+    NotModified,
+}
+
+#[derive(Debug)]
+pub enum RemoteError {
+    Http(String),
+    Status(StatusCode),
+    TooLarge(u64),
+    Checksum(String),
+    Signature(String),
+    Parse(String),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RemoteError::Http(e) => write!(f, "request failed: {}", e),
+            RemoteError::Status(status) => write!(f, "unexpected status: {}", status),
+            RemoteError::TooLarge(max) => write!(f, "ban list exceeds {} bytes", max),
+            RemoteError::Checksum(checksum) => write!(f, "checksum mismatch: got {}", checksum),
+            RemoteError::Signature(e) => write!(f, "invalid signature: {}", e),
+            RemoteError::Parse(e) => write!(f, "{}", e),
+            RemoteError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<reqwest::Error> for RemoteError {
+    fn from(value: reqwest::Error) -> Self {
+        RemoteError::Http(value.to_string())
+    }
+}
+
+impl From<std::io::Error> for RemoteError {
+    fn from(value: std::io::Error) -> Self {
+        RemoteError::Io(value)
+    }
+}
+
+/// Reads a response body of at most `max_size` bytes. The announced
+/// length can't be trusted, so the body is read by chunks.
+async fn read_body(mut response: reqwest::Response, max_size: u64) -> Result<Vec<u8>, RemoteError> {
+    if response.content_length().unwrap_or(0) > max_size {
+        return Err(RemoteError::TooLarge(max_size));
+    }
+
+    let mut body: Vec<u8> = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if (body.len() + chunk.len()) as u64 > max_size {
+            return Err(RemoteError::TooLarge(max_size));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Fetches a remote ban list and keeps the last good copy on disk.
+/// A failed fetch never replaces the cached copy.
+pub struct RemoteFetcher {
+    pub source: RemoteSource,
+    cache_dir: PathBuf,
+    client: reqwest::Client,
+}
+
+impl RemoteFetcher {
+    pub fn new(source: RemoteSource, cache_dir: &Path) -> Self {
+        // A hanging server would otherwise stall the refresh loop forever
+        let timeout = Duration::from_secs(source.timeout.max(1));
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .connect_timeout(timeout.min(Duration::from_secs(CONNECT_TIMEOUT)))
+            .build()
+            .expect("could not build the HTTP client");
+
+        Self {
+            source,
+            cache_dir: cache_dir.to_path_buf(),
+            client,
+        }
+    }
+
+    // Cache files are named after the url hash.
+    fn cache_path(&self, extension: &str) -> PathBuf {
+        let hash = hex::encode(Sha256::digest(self.source.url.as_bytes()));
+        self.cache_dir.join(format!("{}.{}", hash, extension))
+    }
+
+    fn format(&self, content: &str) -> BanListFormat {
+        let path = self.source.url.split(['?', '#']).next().unwrap_or_default();
+        BanListFormat::from_path(Path::new(path)).unwrap_or_else(|| BanListFormat::detect(content))
+    }
+
+    fn parse(&self, content: &str) -> Result<Vec<Ban>, RemoteError> {
+        let document = banlist::parse_document(content, self.format(content))
+            .map_err(|e| RemoteError::Parse(e.to_string()))?;
+
+        // Remote lists can't reach files of the relay, nor other urls
+        if !document.include.is_empty() {
+            warn!(
+                url = %self.source.url,
+                include = %document.include.join(","),
+                "include directives of remote lists are ignored"
+            );
+        }
+
+        let mut bans = document.bans;
+        for ban in bans.iter_mut() {
+            ban.source = Some(self.source.url.clone());
+        }
+
+        Ok(bans)
+    }
+
+    pub fn metadata(&self) -> CacheMetadata {
+        fs::read_to_string(self.cache_path("meta.json"))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Checks a copy of the list against the pinned checksum, if any.
+    fn verify_checksum(&self, body: &[u8]) -> Result<(), RemoteError> {
+        if let Some(expected) = &self.source.sha256 {
+            let checksum = hex::encode(Sha256::digest(body));
+            if !checksum.eq_ignore_ascii_case(expected.trim()) {
+                return Err(RemoteError::Checksum(checksum));
+            }
+        }
+        Ok(())
+    }
+
+    fn signature_url(&self) -> String {
+        self.source
+            .signature_url
+            .clone()
+            .unwrap_or_else(|| format!("{}.sig", self.source.url))
+    }
+
+    /// Checks a copy of the list against its detached signature, when a
+    /// public key is pinned. The signature is a hex encoded BIP-340 Schnorr
+    /// signature of the SHA-256 of the list.
+    fn verify_signature(&self, body: &[u8], signature: Option<&str>) -> Result<(), RemoteError> {
+        let Some(pubkey) = &self.source.pubkey else {
+            return Ok(());
+        };
+
+        let pubkey = from_npub(pubkey.trim())
+            .or_else(|| hex::decode(pubkey.trim()).ok())
+            .ok_or_else(|| RemoteError::Signature(format!("invalid public key {}", pubkey)))?;
+        let signature = signature
+            .ok_or_else(|| RemoteError::Signature("missing signature".to_string()))
+            .and_then(|s| {
+                hex::decode(s.trim()).map_err(|e| RemoteError::Signature(e.to_string()))
+            })?;
+
+        verify_signature(&pubkey, &Sha256::digest(body), &signature)
+            .map_err(|e| RemoteError::Signature(e.to_string()))
+    }
+
+    /// Loads the last good copy of the list, if any. Copies fetched before
+    /// the checksum or the public key was pinned, or changed since, are
+    /// left out.
+    pub fn load_cache(&self) -> Option<Vec<Ban>> {
+        let content = fs::read_to_string(self.cache_path("list")).ok()?;
+        let signature = fs::read_to_string(self.cache_path("sig")).ok();
+
+        match self
+            .verify_checksum(content.as_bytes())
+            .and_then(|_| self.verify_signature(content.as_bytes(), signature.as_deref()))
+            .and_then(|_| self.parse(&content))
+        {
+            Ok(bans) => Some(bans),
+            Err(e) => {
+                warn!(url = %self.source.url, "invalid cached copy: {}", e);
+                None
+            }
+        }
+    }
+
+    fn store_cache(
+        &self,
+        content: &str,
+        signature: Option<&str>,
+        metadata: &CacheMetadata,
+    ) -> Result<(), RemoteError> {
+        fs::create_dir_all(&self.cache_dir)?;
+
+        // The signature is written first, a crash then leaves the previous
+        // copy without a valid signature rather than an unchecked one
+        match signature {
+            Some(signature) => fs::write(self.cache_path("sig"), signature)?,
+            None => _ = fs::remove_file(self.cache_path("sig")),
+        }
+
+        // Write then rename so that a crash never leaves a truncated copy
+        let list_path = self.cache_path("list");
+        let tmp_path = self.cache_path("list.tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, &list_path)?;
+
+        let metadata = serde_json::to_string(metadata).unwrap_or_default();
+        fs::write(self.cache_path("meta.json"), metadata)?;
+
+        Ok(())
+    }
+
+    /// Fetches the list, using the cached validators for a conditional request.
+    pub async fn fetch(&self) -> Result<FetchOutcome, RemoteError> {
+        let cached = self.metadata();
+        let has_cache = self.cache_path("list").exists();
+
+        let mut request = self.client.get(&self.source.url);
+        if has_cache {
+            if let Some(etag) = &cached.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = request.send().await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED && has_cache {
+            return Ok(FetchOutcome::NotModified);
+        }
+
+        if !response.status().is_success() {
+            return Err(RemoteError::Status(response.status()));
+        }
+
+        let max_size = self.source.max_size;
+
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok())
+                .map(String::from)
+        };
+        let metadata = CacheMetadata {
+            etag: header(ETAG),
+            last_modified: header(LAST_MODIFIED),
+            fetched_at: Some(Utc::now().to_rfc3339()),
+        };
+
+        let body = read_body(response, max_size).await?;
+        self.verify_checksum(&body)?;
+
+        let signature = match self.source.pubkey {
+            Some(_) => Some(self.fetch_signature().await?),
+            None => None,
+        };
+        self.verify_signature(&body, signature.as_deref())?;
+
+        let content = String::from_utf8(body).map_err(|e| RemoteError::Parse(e.to_string()))?;
+        let bans = self.parse(&content)?;
+
+        self.store_cache(&content, signature.as_deref(), &metadata)?;
+
+        Ok(FetchOutcome::Updated(bans))
+    }
+
+    /// Fetches the detached signature of the list.
+    async fn fetch_signature(&self) -> Result<String, RemoteError> {
+        let response = self.client.get(self.signature_url()).send().await?;
+        if !response.status().is_success() {
+            return Err(RemoteError::Signature(format!(
+                "unexpected status: {}",
+                response.status()
+            )));
+        }
+
+        let body = read_body(response, MAX_SIGNATURE_SIZE).await?;
+        String::from_utf8(body).map_err(|e| RemoteError::Signature(e.to_string()))
+    }
+
+    fn merge(&self, banhammer: &mut BanHammer, bans: Vec<Ban>) {
+        banhammer.replace_source(&self.source.url, bans);
+        banhammer.commit(&format!("remote:{}", self.source.url));
+    }
+
+    /// Loads the cached copy, then refreshes the list periodically.
+    pub async fn run(self, banhammer: Arc<Mutex<BanHammer>>, metrics: Arc<Metrics>) {
+        if let Some(bans) = self.load_cache() {
+            self.merge(&mut *banhammer.lock().await, bans);
+        }
+
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.source.refresh_interval.max(1)));
+
+        loop {
+            interval.tick().await;
+
+            match self.fetch().await {
+                Ok(FetchOutcome::Updated(bans)) => {
+                    metrics.record_reload(&self.source.url, true);
+                    info!(url = %self.source.url, "{} bans fetched", bans.len());
+                    self.merge(&mut *banhammer.lock().await, bans);
+                }
+                Ok(FetchOutcome::NotModified) => metrics.record_reload(&self.source.url, true),
+                Err(e) => {
+                    metrics.record_reload(&self.source.url, false);
+                    warn!(
+                        url = %self.source.url,
+                        "fetch failed, keeping last good copy: {}",
+                        e
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::nostr::Keys;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    const LIST: &str = "- content: spam\n  regex: false\n  ban_type: CONTENT\n";
+
+    /// A minimal HTTP server answering every request with the provided
+    /// handler. Returns the server url.
+    async fn serve<F>(handler: F) -> String
+    where
+        F: Fn(&str) -> (u16, Vec<(&'static str, String)>, String) + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let handler = Arc::new(handler);
+
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = match listener.accept().await {
+                    Ok(connection) => connection,
+                    Err(_) => return,
+                };
+                let handler = handler.clone();
+
+                tokio::spawn(async move {
+                    let mut buffer = vec![0; 4096];
+                    let read = socket.read(&mut buffer).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&buffer[..read]).to_string();
+                    let (status, headers, body) = handler(&request);
+
+                    let mut response = format!(
+                        "HTTP/1.1 {} X\r\ncontent-length: {}\r\nconnection: close\r\n",
+                        status,
+                        body.len()
+                    );
+                    for (name, value) in headers {
+                        response += &format!("{}: {}\r\n", name, value);
+                    }
+                    response += "\r\n";
+                    response += &body;
+
+                    _ = socket.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+
+        format!("http://{}/bans.yaml", address)
+    }
+
+    fn source(url: &str) -> RemoteSource {
+        RemoteSource {
+            url: url.to_string(),
+            refresh_interval: default_refresh_interval(),
+            max_size: default_max_size(),
+            timeout: default_timeout(),
+            sha256: None,
+            pubkey: None,
+            signature_url: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fetch_and_conditional_request() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let url = serve(move |request| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            match request.to_lowercase().contains("if-none-match: \"v1\"") {
+                true => (304, vec![], String::new()),
+                false => (200, vec![("etag", "\"v1\"".to_string())], LIST.to_string()),
+            }
+        })
+        .await;
+
+        let cache = tempfile::tempdir().unwrap();
+        let fetcher = RemoteFetcher::new(source(&url), cache.path());
+
+        let bans = match fetcher.fetch().await.unwrap() {
+            FetchOutcome::Updated(bans) => bans,
+            FetchOutcome::NotModified => panic!("expected a new copy"),
+        };
+        assert_eq!(bans.len(), 1);
+        assert_eq!(bans[0].source, Some(url.clone()));
+        assert_eq!(fetcher.metadata().etag, Some("\"v1\"".to_string()));
+
+        assert_eq!(fetcher.fetch().await.unwrap(), FetchOutcome::NotModified);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        // The cached copy survives a restart
+        let fetcher = RemoteFetcher::new(source(&url), cache.path());
+        assert_eq!(fetcher.load_cache(), Some(bans));
+    }
+
+    #[tokio::test]
+    async fn test_failures_keep_last_good_copy() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let url = serve(move |_| match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => (200, vec![], LIST.to_string()),
+            1 => (500, vec![], String::new()),
+            _ => (200, vec![], "- not: [a ban list".to_string()),
+        })
+        .await;
+
+        let cache = tempfile::tempdir().unwrap();
+        let fetcher = RemoteFetcher::new(source(&url), cache.path());
+
+        assert!(fetcher.fetch().await.is_ok());
+        assert!(matches!(
+            fetcher.fetch().await,
+            Err(RemoteError::Status(StatusCode::INTERNAL_SERVER_ERROR))
+        ));
+        assert!(matches!(fetcher.fetch().await, Err(RemoteError::Parse(_))));
+
+        let cached = fetcher.load_cache().unwrap();
+        assert_eq!(cached[0].content, "spam");
+    }
+
+    #[tokio::test]
+    async fn test_size_limit_and_checksum() {
+        let url = serve(|_| (200, vec![], LIST.to_string())).await;
+        let cache = tempfile::tempdir().unwrap();
+
+        let mut limited = source(&url);
+        limited.max_size = 10;
+        let fetcher = RemoteFetcher::new(limited, cache.path());
+        assert!(matches!(
+            fetcher.fetch().await,
+            Err(RemoteError::TooLarge(10))
+        ));
+
+        let mut pinned = source(&url);
+        pinned.sha256 = Some("00".repeat(32));
+        let fetcher = RemoteFetcher::new(pinned, cache.path());
+        assert!(matches!(
+            fetcher.fetch().await,
+            Err(RemoteError::Checksum(_))
+        ));
+        assert_eq!(fetcher.load_cache(), None);
+
+        let mut pinned = source(&url);
+        pinned.sha256 = Some(hex::encode(Sha256::digest(LIST.as_bytes())).to_uppercase());
+        let fetcher = RemoteFetcher::new(pinned, cache.path());
+        assert!(matches!(
+            fetcher.fetch().await,
+            Ok(FetchOutcome::Updated(_))
+        ));
+        assert!(fetcher.load_cache().is_some());
+
+        // The cached copy no longer matches once the pin changes
+        let mut repinned = source(&url);
+        repinned.sha256 = Some("00".repeat(32));
+        let fetcher = RemoteFetcher::new(repinned, cache.path());
+        assert_eq!(fetcher.load_cache(), None);
+    }
+
+    /// Serves the list along with a detached signature.
+    async fn serve_signed(signature: String) -> String {
+        serve(move |request| {
+            let path = request.split(' ').nth(1).unwrap_or_default();
+            match path {
+                "/bans.yaml" => (200, vec![], LIST.to_string()),
+                "/bans.yaml.sig" => (200, vec![], signature.clone()),
+                _ => (404, vec![], String::new()),
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_signature() {
+        let keys = Keys::parse(&"03".repeat(32)).unwrap();
+        let cache = tempfile::tempdir().unwrap();
+
+        let signature = keys.sign(&Sha256::digest(LIST.as_bytes()).into());
+        let url = serve_signed(hex::encode(signature)).await;
+        let mut signed = source(&url);
+        signed.pubkey = Some(keys.public_key_hex());
+        let fetcher = RemoteFetcher::new(signed.clone(), cache.path());
+        assert!(matches!(
+            fetcher.fetch().await,
+            Ok(FetchOutcome::Updated(_))
+        ));
+        assert!(fetcher.load_cache().is_some());
+
+        // Signed by another key
+        let mut other = signed.clone();
+        other.pubkey = Some(hex::encode([2; 32]));
+        let fetcher = RemoteFetcher::new(other, cache.path());
+        assert!(matches!(
+            fetcher.fetch().await,
+            Err(RemoteError::Signature(_))
+        ));
+        assert_eq!(fetcher.load_cache(), None);
+
+        // Missing signature
+        let mut missing = signed.clone();
+        missing.signature_url = Some(format!("{}.missing", url));
+        let fetcher = RemoteFetcher::new(missing, cache.path());
+        assert!(matches!(
+            fetcher.fetch().await,
+            Err(RemoteError::Signature(_))
+        ));
+
+        // Not a signature of the list
+        let forged_url = serve_signed(hex::encode(keys.sign(&[0; 32]))).await;
+        let mut forged = signed;
+        forged.url = forged_url;
+        let fetcher = RemoteFetcher::new(forged, cache.path());
+        assert!(matches!(
+            fetcher.fetch().await,
+            Err(RemoteError::Signature(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_timeout() {
+        // Accepts connections but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/bans.yaml", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut sockets = vec![];
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let cache = tempfile::tempdir().unwrap();
+        let mut hanging = source(&url);
+        hanging.timeout = 1;
+        let fetcher = RemoteFetcher::new(hanging, cache.path());
+
+        let fetch = tokio::time::timeout(Duration::from_secs(5), fetcher.fetch()).await;
+        assert!(matches!(fetch, Ok(Err(RemoteError::Http(_)))));
+    }
+
+    #[tokio::test]
+    async fn test_merge_into_banhammer() {
+        let url = serve(|_| (200, vec![], LIST.to_string())).await;
+        let cache = tempfile::tempdir().unwrap();
+        let fetcher = RemoteFetcher::new(source(&url), cache.path());
+
+        let banhammer = Arc::new(Mutex::new(BanHammer::default()));
+        let task = tokio::spawn(fetcher.run(banhammer.clone(), Arc::new(Metrics::default())));
+
+        for _ in 0..50 {
+            if !banhammer.lock().await.words.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        task.abort();
+
+        let banhammer = banhammer.lock().await;
+        assert!(banhammer.invalidate_content("spam".to_string()));
+        assert!(banhammer.is_read_only(&banhammer.words[0]));
+    }
+}
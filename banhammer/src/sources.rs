@@ -0,0 +1,170 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use banhammer_grpc::banlist::{self, BanListFormat};
+use tracing::warn;
+
+use crate::hammer::Ban;
+
+/// Loads bans from several ban list sources.
+///
+/// Sources are loaded in the order they are provided. A directory source
+/// loads every ban list file it contains, sorted by file name. A file's own
+/// bans come first, followed by the bans of the files it includes, in order.
+///
+/// When the same rule (same type, content and regex flag) is declared more
+/// than once, the first declaration wins and later ones are dropped.
+/// Each file is loaded at most once, which also prevents include cycles.
+#[derive(Debug, Default)]
+pub struct SourceLoader {
+    visited: HashSet<PathBuf>,
+    bans: Vec<Ban>,
+}
+
+impl SourceLoader {
+    /// Splits a comma separated list of ban list paths.
+    pub fn split(banlist: &str) -> Vec<PathBuf> {
+        banlist
+            .split(',')
+            .map(|path| path.trim())
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .collect()
+    }
+
+    pub fn load(paths: &[PathBuf]) -> Vec<Ban> {
+        let mut loader = Self::default();
+
+        for path in paths {
+            loader.load_path(path);
+        }
+
+        loader.bans
+    }
+
+    fn load_path(&mut self, path: &Path) {
+        match path.is_dir() {
+            true => self.load_directory(path),
+            false => self.load_file(path),
+        }
+    }
+
+    fn load_directory(&mut self, path: &Path) {
+        let entries = match fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!(path = %path.display(), "{}", e);
+                return;
+            }
+        };
+
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && BanListFormat::from_path(path).is_some())
+            .collect();
+        files.sort();
+
+        for file in files {
+            self.load_file(&file);
+        }
+    }
+
+    fn load_file(&mut self, path: &Path) {
+        let canonical = fs::canonicalize(path).unwrap_or(path.to_path_buf());
+        if !self.visited.insert(canonical) {
+            warn!(path = %path.display(), "already loaded, skipping");
+            return;
+        }
+
+        let document = match banlist::read_document(path) {
+            Ok(document) => document,
+            Err(e) => {
+                warn!(path = %path.display(), "{}", e);
+                return;
+            }
+        };
+
+        let source = path.display().to_string();
+        for mut ban in document.bans {
+            ban.source = Some(source.clone());
+            self.push(ban);
+        }
+
+        // Includes are relative to the including file
+        let parent = path.parent().unwrap_or(Path::new(""));
+        for include in document.include {
+            self.load_path(&parent.join(include));
+        }
+    }
+
+    fn push(&mut self, ban: Ban) {
+        match self.bans.iter().find(|b| b.is_duplicate_of(&ban)) {
+            Some(existing) => warn!(
+                source = ban.source.clone().unwrap_or_default(),
+                "duplicate {} ban \"{}\" already declared in {}, skipping",
+                ban.ban_type,
+                ban.content,
+                existing.source.clone().unwrap_or_default()
+            ),
+            None => self.bans.push(ban),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use banhammer_grpc::BanTypesEnum;
+
+    fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/sources")
+    }
+
+    #[test]
+    fn test_split() {
+        let paths = SourceLoader::split("bans.yaml, lists/ ,,");
+        assert_eq!(
+            paths,
+            vec![PathBuf::from("bans.yaml"), PathBuf::from("lists/")]
+        );
+    }
+
+    #[test]
+    fn test_load_with_includes() {
+        let root = fixtures().join("main.yaml");
+        let bans = SourceLoader::load(std::slice::from_ref(&root));
+
+        let contents: Vec<&str> = bans.iter().map(|b| b.content.as_str()).collect();
+        assert_eq!(contents, vec!["hello", "spam.example", "198.51.100.7"]);
+
+        assert_eq!(bans[0].source, Some(root.display().to_string()));
+        assert_eq!(
+            bans[1].source,
+            Some(fixtures().join("lists/domains.json").display().to_string())
+        );
+        assert_eq!(bans[2].ban_type, BanTypesEnum::IP);
+    }
+
+    #[test]
+    fn test_load_directory_and_duplicates() {
+        let bans = SourceLoader::load(&[fixtures().join("lists"), fixtures().join("main.yaml")]);
+
+        // Directory files are sorted, include cycle and duplicates are skipped
+        let contents: Vec<&str> = bans.iter().map(|b| b.content.as_str()).collect();
+        assert_eq!(contents, vec!["spam.example", "198.51.100.7", "hello"]);
+        assert_eq!(
+            bans[0].source,
+            Some(fixtures().join("lists/domains.json").display().to_string())
+        );
+    }
+
+    #[test]
+    fn test_missing_source() {
+        let bans = SourceLoader::load(&[fixtures().join("missing.yaml")]);
+        assert!(bans.is_empty());
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    num::NonZeroUsize,
+};
+
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+
+use crate::nostr::to_npub;
+
+/// Duration of a decision bucket, in seconds
+const BUCKET_SECONDS: i64 = 10;
+
+/// Sliding windows reported by `DecisionWindows::window`, in seconds
+pub const WINDOWS: [(&str, i64); 3] = [("1m", 60), ("1h", 3600), ("24h", 86400)];
+
+/// The longest window, older buckets are dropped
+const RETENTION: i64 = 86400;
+
+/// Pubkeys, and IPs, counted at most by an offender bucket
+const MAX_OFFENDERS: usize = 10_000;
+
+/// Pubkeys or IPs, with their denials, the most denied first
+pub type Offenders = Vec<(String, u64)>;
+
+/// Decisions taken during `BUCKET_SECONDS`.
+#[derive(Debug)]
+struct DecisionBucket {
+    /// Start of the bucket, in `BUCKET_SECONDS` since the epoch
+    start: i64,
+    evaluated: u64,
+    denied: u64,
+}
+
+/// Denials of each pubkey and IP during an hour. The least recently
+/// denied are forgotten first once `MAX_OFFENDERS` are counted.
+#[derive(Debug)]
+struct OffenderBucket {
+    /// Hours since the epoch
+    hour: i64,
+    pubkeys: LruCache<String, u64>,
+    ips: LruCache<String, u64>,
+}
+
+impl OffenderBucket {
+    fn new(hour: i64) -> Self {
+        let capacity = NonZeroUsize::new(MAX_OFFENDERS).unwrap();
+        Self {
+            hour,
+            pubkeys: LruCache::new(capacity),
+            ips: LruCache::new(capacity),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    decisions: VecDeque<DecisionBucket>,
+    offenders: VecDeque<OffenderBucket>,
+}
+
+/// Decisions over the last 24 hours, and the pubkeys and IPs denied
+/// the most. Memory is bounded by the number of buckets and by the
+/// `MAX_OFFENDERS` of each hour, so floods of fresh keys or IPs only
+/// evict the offenders seen the least recently.
+#[derive(Debug, Default)]
+pub struct DecisionWindows {
+    buckets: std::sync::Mutex<Buckets>,
+}
+
+impl DecisionWindows {
+    /// Records the decision taken for an event, with the pubkey and IP
+    /// of the event when it was denied.
+    pub fn record(&self, denied: Option<(&[u8], &str)>, now: DateTime<Utc>) {
+        let start = now.timestamp().div_euclid(BUCKET_SECONDS);
+        let hour = now.timestamp().div_euclid(3600);
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let Buckets {
+            decisions,
+            offenders,
+        } = &mut *buckets;
+
+        if decisions.back().map(|b| b.start) != Some(start) {
+            decisions.push_back(DecisionBucket {
+                start,
+                evaluated: 0,
+                denied: 0,
+            });
+        }
+        let bucket = decisions.back_mut().unwrap();
+        bucket.evaluated += 1;
+
+        if let Some((pubkey, ip)) = denied {
+            bucket.denied += 1;
+
+            if offenders.back().map(|b| b.hour) != Some(hour) {
+                offenders.push_back(OffenderBucket::new(hour));
+            }
+            let bucket = offenders.back_mut().unwrap();
+
+            if let Some(pubkey) = to_npub(pubkey) {
+                *bucket.pubkeys.get_or_insert_mut(pubkey, || 0) += 1;
+            }
+            if !ip.is_empty() {
+                *bucket.ips.get_or_insert_mut(ip.to_string(), || 0) += 1;
+            }
+        }
+
+        while decisions
+            .front()
+            .is_some_and(|b| b.start <= start - RETENTION / BUCKET_SECONDS)
+        {
+            decisions.pop_front();
+        }
+        while offenders
+            .front()
+            .is_some_and(|b| b.hour <= hour - RETENTION / 3600)
+        {
+            offenders.pop_front();
+        }
+    }
+
+    /// Events evaluated and denied during the last `seconds`.
+    pub fn window(&self, seconds: i64, now: DateTime<Utc>) -> (u64, u64) {
+        let start = now.timestamp().div_euclid(BUCKET_SECONDS);
+        let buckets = self.buckets.lock().unwrap();
+
+        buckets
+            .decisions
+            .iter()
+            .filter(|b| b.start > start - seconds / BUCKET_SECONDS && b.start <= start)
+            .fold((0, 0), |(evaluated, denied), b| {
+                (evaluated + b.evaluated, denied + b.denied)
+            })
+    }
+
+    /// Pubkeys and IPs denied the most during the last 24 hours,
+    /// with their denials.
+    pub fn top_offenders(&self, limit: usize, now: DateTime<Utc>) -> (Offenders, Offenders) {
+        let hour = now.timestamp().div_euclid(3600);
+        let buckets = self.buckets.lock().unwrap();
+        let recent: Vec<&OffenderBucket> = buckets
+            .offenders
+            .iter()
+            .filter(|b| b.hour > hour - RETENTION / 3600)
+            .collect();
+
+        let pubkeys = top(recent.iter().map(|b| &b.pubkeys), limit);
+        let ips = top(recent.iter().map(|b| &b.ips), limit);
+        (pubkeys, ips)
+    }
+}
+
+/// Sums the counts of each key, and keeps the `limit` highest.
+fn top<'a>(counts: impl Iterator<Item = &'a LruCache<String, u64>>, limit: usize) -> Offenders {
+    let mut total: HashMap<&str, u64> = HashMap::new();
+    for counts in counts {
+        for (key, count) in counts {
+            *total.entry(key).or_default() += count;
+        }
+    }
+
+    let mut total: Offenders = total
+        .into_iter()
+        .map(|(key, count)| (key.to_string(), count))
+        .collect();
+    total.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    total.truncate(limit);
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::Duration;
+
+    const ALICE: [u8; 32] = [1; 32];
+    const BOB: [u8; 32] = [2; 32];
+
+    #[test]
+    fn test_windows() {
+        let windows = DecisionWindows::default();
+        let now = DateTime::parse_from_rfc3339("2024-01-02T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        windows.record(Some((&ALICE[..], "1.1.1.1")), now - Duration::hours(30));
+        windows.record(Some((&ALICE[..], "1.1.1.1")), now - Duration::hours(2));
+        windows.record(None, now - Duration::minutes(30));
+        windows.record(Some((&BOB[..], "2.2.2.2")), now - Duration::seconds(20));
+        windows.record(Some((&BOB[..], "2.2.2.2")), now);
+        windows.record(None, now);
+
+        assert_eq!(windows.window(60, now), (3, 2));
+        assert_eq!(windows.window(3600, now), (4, 2));
+        // The oldest decision has expired
+        assert_eq!(windows.window(86400, now), (5, 3));
+
+        let (pubkeys, ips) = windows.top_offenders(1, now);
+        assert_eq!(pubkeys.len(), 1);
+        assert_eq!(pubkeys[0].1, 2);
+        assert!(pubkeys[0].0.starts_with("npub1"));
+        assert_eq!(ips, vec![("2.2.2.2".to_string(), 2)]);
+
+        let (_, ips) = windows.top_offenders(10, now);
+        assert_eq!(
+            ips,
+            vec![("2.2.2.2".to_string(), 2), ("1.1.1.1".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_max_offenders() {
+        let windows = DecisionWindows::default();
+        let now = Utc::now();
+
+        // A steady offender among a flood of fresh IPs
+        for i in 0..MAX_OFFENDERS * 2 {
+            windows.record(Some((&ALICE[..], "1.1.1.1")), now);
+            let ip = format!("10.{}.{}.{}", i >> 16, (i >> 8) & 0xff, i & 0xff);
+            windows.record(Some((&BOB[..], &ip)), now);
+        }
+
+        let buckets = windows.buckets.lock().unwrap();
+        assert_eq!(buckets.offenders.len(), 1);
+        assert_eq!(buckets.offenders[0].ips.len(), MAX_OFFENDERS);
+        drop(buckets);
+
+        let (_, ips) = windows.top_offenders(1, now);
+        assert_eq!(ips, vec![("1.1.1.1".to_string(), MAX_OFFENDERS as u64 * 2)]);
+    }
+}
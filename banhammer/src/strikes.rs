@@ -0,0 +1,463 @@
+use std::{num::NonZeroUsize, sync::Arc, time::Duration};
+
+use banhammer_grpc::BanTypesEnum;
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use crate::audit::{Action, AuditLog};
+use crate::hammer::{Ban, BanHammer};
+use crate::nostr::to_npub;
+use crate::policy::{Check, Violation};
+use crate::rejection::{Rejection, RejectionLog};
+
+/// Source of the bans created by strikes
+pub const AUTO_SOURCE: &str = "auto";
+
+/// Actor of the automatic actions in the audit log
+pub const SYSTEM_ACTOR: &str = "system";
+
+/// Offenders get a temporary ban of `seconds` once they reach `strikes`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct StrikeThreshold {
+    pub strikes: u32,
+    pub seconds: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct StrikeSettings {
+    /// Denials by bans of these types are strikes
+    pub ban_types: Vec<BanTypesEnum>,
+    /// Whether rate limited events are strikes
+    pub rate_limited: bool,
+    /// Strikes are disabled when empty
+    pub thresholds: Vec<StrikeThreshold>,
+    /// Strikes are forgotten after this many seconds without a new one
+    pub reset_after: u64,
+    /// Offenders tracked at most, the least recently seen are forgotten first
+    pub max_keys: usize,
+}
+
+impl Default for StrikeSettings {
+    fn default() -> Self {
+        Self {
+            ban_types: vec![BanTypesEnum::CONTENT, BanTypesEnum::TAG],
+            rate_limited: false,
+            thresholds: vec![],
+            reset_after: 7 * 86400,
+            max_keys: 100_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Offender {
+    strikes: u32,
+    last_strike: DateTime<Utc>,
+    /// Index of the last threshold the offender was banned for
+    threshold: Option<usize>,
+    /// Expiration date of the last ban of the offender
+    banned_until: Option<DateTime<Utc>>,
+}
+
+/// A ban created by strikes, removed once `expires` is past.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutoBan {
+    pub ban: Ban,
+    pub expires: DateTime<Utc>,
+}
+
+/// Counts the strikes of pubkeys and IPs, and bans them for a while when
+/// they reach a threshold. Every threshold bans for longer than the
+/// previous one, so repeat offenders are banned for longer. Offenders
+/// striking again once their ban expired are banned again, for the
+/// highest threshold they reached.
+pub struct Strikes {
+    ban_types: Vec<BanTypesEnum>,
+    rate_limited: bool,
+    /// Thresholds, by increasing strikes
+    thresholds: Vec<StrikeThreshold>,
+    reset_after: chrono::Duration,
+    /// Offenders keyed by the type of ban they get, USER or IP, and its content
+    offenders: std::sync::Mutex<LruCache<(BanTypesEnum, String), Offender>>,
+}
+
+impl Strikes {
+    pub fn new(settings: &StrikeSettings) -> Self {
+        let mut thresholds = settings.thresholds.clone();
+        thresholds.sort_by_key(|threshold| threshold.strikes);
+
+        let capacity = NonZeroUsize::new(settings.max_keys).unwrap_or(NonZeroUsize::MIN);
+
+        Self {
+            ban_types: settings.ban_types.clone(),
+            rate_limited: settings.rate_limited,
+            thresholds,
+            reset_after: chrono::Duration::seconds(settings.reset_after as i64),
+            offenders: std::sync::Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Records a strike for the pubkey and the IP of a denied event, when it
+    /// was denied for a reason that counts. The pubkey is left out when the
+    /// event wasn't verified. Returns the bans of the offenders that reached
+    /// a threshold.
+    pub fn strike(
+        &self,
+        rejections: &[Rejection],
+        violation: Option<&Violation>,
+        pubkey: Option<&[u8]>,
+        ip: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> Vec<AutoBan> {
+        if self.thresholds.is_empty() {
+            return vec![];
+        }
+
+        let counts = rejections
+            .iter()
+            .any(|r| r.ban.is_some() && self.ban_types.contains(&r.ban_type))
+            || (self.rate_limited && violation.is_some_and(|v| v.check == Check::RateLimit));
+        if !counts {
+            return vec![];
+        }
+
+        let offenders = [
+            pubkey
+                .and_then(to_npub)
+                .map(|npub| (BanTypesEnum::USER, npub)),
+            ip.filter(|ip| !ip.is_empty())
+                .map(|ip| (BanTypesEnum::IP, ip.to_string())),
+        ];
+
+        offenders
+            .into_iter()
+            .flatten()
+            .filter_map(|key| self.strike_offender(key, now))
+            .collect()
+    }
+
+    fn strike_offender(&self, key: (BanTypesEnum, String), now: DateTime<Utc>) -> Option<AutoBan> {
+        let mut offenders = self.offenders.lock().unwrap();
+        let offender = offenders.get_or_insert_mut(key.clone(), || Offender {
+            strikes: 0,
+            last_strike: now,
+            threshold: None,
+            banned_until: None,
+        });
+
+        if now - offender.last_strike > self.reset_after {
+            offender.strikes = 0;
+            offender.threshold = None;
+            offender.banned_until = None;
+        }
+        offender.strikes += 1;
+        offender.last_strike = now;
+
+        let strikes = offender.strikes;
+        let index = self.thresholds.iter().rposition(|t| t.strikes <= strikes)?;
+        // Reaching a higher threshold extends the ban, otherwise
+        // the offender is banned again once the ban expired
+        let escalates = offender.threshold.is_none_or(|last| index > last);
+        let banned = offender.banned_until.is_some_and(|until| until > now);
+        if !escalates && banned {
+            return None;
+        }
+
+        let threshold = &self.thresholds[index];
+        let expires = now + chrono::Duration::seconds(threshold.seconds as i64);
+        offender.threshold = Some(index);
+        offender.banned_until = Some(expires);
+
+        let (ban_type, content) = key;
+        Some(AutoBan {
+            ban: Ban {
+                content,
+                regex: false,
+                reason: Some(format!("auto: {} strikes", strikes)),
+                date: Some(now.format("%Y-%m-%dT%H:%M:%S").to_string()),
+                source: Some(AUTO_SOURCE.to_string()),
+                ban_type,
+            },
+            expires,
+        })
+    }
+}
+
+/// Describes an automatic action for the audit log.
+fn system_action(name: &str, ban: &Ban, result: String) -> Action {
+    Action {
+        actor: SYSTEM_ACTOR.to_string(),
+        address: None,
+        action: name.to_string(),
+        payload: format!("{:?}", ban),
+        result,
+    }
+}
+
+/// Adds the bans created by strikes, and records them in the audit log.
+/// Their pubkeys and IPs are logged hashed like in the rejection log.
+pub async fn apply(
+    banhammer: &Mutex<BanHammer>,
+    audit: &Mutex<AuditLog>,
+    log: &RejectionLog,
+    bans: Vec<AutoBan>,
+) {
+    let mut banhammer = banhammer.lock().await;
+    let mut added = vec![];
+    for AutoBan { ban, expires } in bans {
+        if banhammer.add_temporary(ban.clone(), expires) {
+            added.push((ban, expires));
+        }
+    }
+    if added.is_empty() {
+        return;
+    }
+    banhammer.commit(SYSTEM_ACTOR);
+    drop(banhammer);
+
+    let mut audit = audit.lock().await;
+    for (ban, expires) in added {
+        info!(
+            ban_type = %ban.ban_type,
+            content = %log.hash(&ban.content),
+            expires = %expires.format("%Y-%m-%dT%H:%M:%S"),
+            "{}",
+            ban.reason.clone().unwrap_or_default()
+        );
+
+        let result = format!("ok: expires {}", expires.format("%Y-%m-%dT%H:%M:%S"));
+        if let Err(e) = audit.record(system_action("AutoBan", &ban, result)) {
+            error!("audit log: {}", e);
+        }
+    }
+}
+
+/// Removes the expired temporary bans periodically.
+pub async fn expire_periodically(
+    banhammer: Arc<Mutex<BanHammer>>,
+    audit: Arc<Mutex<AuditLog>>,
+    interval: Duration,
+) {
+    let mut interval = tokio::time::interval(interval);
+
+    loop {
+        interval.tick().await;
+
+        let expired = {
+            let mut banhammer = banhammer.lock().await;
+            let expired = banhammer.remove_expired(Utc::now());
+            if !expired.is_empty() {
+                banhammer.commit(SYSTEM_ACTOR);
+            }
+            expired
+        };
+
+        let mut audit = audit.lock().await;
+        for ban in expired {
+            if let Err(e) = audit.record(system_action("ExpireBan", &ban, "ok".to_string())) {
+                error!("audit log: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use banhammer_grpc::grpc::Event;
+
+    use crate::logging::LogFormat;
+
+    const ALICE: [u8; 32] = [1; 32];
+
+    fn date(date: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(date)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn rejection(ban_type: BanTypesEnum) -> Rejection {
+        let ban = Ban {
+            content: "spam".to_string(),
+            regex: false,
+            reason: None,
+            date: None,
+            source: None,
+            ban_type,
+        };
+        Rejection::new(ban_type, Some(&ban), &Event::default(), "1.1.1.1")
+    }
+
+    fn settings() -> StrikeSettings {
+        StrikeSettings {
+            thresholds: vec![
+                StrikeThreshold {
+                    strikes: 5,
+                    seconds: 86400,
+                },
+                StrikeThreshold {
+                    strikes: 3,
+                    seconds: 3600,
+                },
+            ],
+            reset_after: 86400,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_escalation() {
+        let strikes = Strikes::new(&settings());
+        let spam = [rejection(BanTypesEnum::CONTENT)];
+        let now = date("2024-01-01T00:00:00Z");
+
+        for _ in 0..2 {
+            assert!(strikes
+                .strike(&spam, None, Some(&ALICE), Some("1.1.1.1"), now)
+                .is_empty());
+        }
+
+        // The pubkey and the IP are banned for an hour
+        let bans = strikes.strike(&spam, None, Some(&ALICE), Some("1.1.1.1"), now);
+        assert_eq!(bans.len(), 2);
+        assert_eq!(bans[0].ban.ban_type, BanTypesEnum::USER);
+        assert_eq!(bans[0].ban.content, to_npub(&ALICE).unwrap());
+        assert_eq!(bans[0].ban.reason.as_deref(), Some("auto: 3 strikes"));
+        assert_eq!(bans[0].ban.source.as_deref(), Some(AUTO_SOURCE));
+        assert_eq!(bans[0].expires, date("2024-01-01T01:00:00Z"));
+        assert_eq!(bans[1].ban.ban_type, BanTypesEnum::IP);
+        assert_eq!(bans[1].ban.content, "1.1.1.1");
+
+        // Then for a day
+        strikes.strike(&spam, None, Some(&ALICE), None, now);
+        let bans = strikes.strike(&spam, None, Some(&ALICE), None, now);
+        assert_eq!(bans.len(), 1);
+        assert_eq!(bans[0].expires, date("2024-01-02T00:00:00Z"));
+
+        // Strikes are forgotten after a day without any
+        let later = date("2024-01-03T00:00:00Z");
+        strikes.strike(&spam, None, Some(&ALICE), None, later);
+        strikes.strike(&spam, None, Some(&ALICE), None, later);
+        let bans = strikes.strike(&spam, None, Some(&ALICE), None, later);
+        assert_eq!(bans[0].ban.reason.as_deref(), Some("auto: 3 strikes"));
+    }
+
+    #[test]
+    fn test_past_last_threshold() {
+        let strikes = Strikes::new(&StrikeSettings {
+            reset_after: 7 * 86400,
+            ..settings()
+        });
+        let spam = [rejection(BanTypesEnum::CONTENT)];
+        let now = date("2024-01-01T00:00:00Z");
+
+        for _ in 0..5 {
+            strikes.strike(&spam, None, Some(&ALICE), None, now);
+        }
+
+        // Still banned for a day
+        assert!(strikes
+            .strike(&spam, None, Some(&ALICE), None, now)
+            .is_empty());
+
+        // Banned again for a day once the ban expired
+        let later = date("2024-01-02T01:00:00Z");
+        let bans = strikes.strike(&spam, None, Some(&ALICE), None, later);
+        assert_eq!(bans.len(), 1);
+        assert_eq!(bans[0].ban.reason.as_deref(), Some("auto: 7 strikes"));
+        assert_eq!(bans[0].expires, date("2024-01-03T01:00:00Z"));
+        assert!(strikes
+            .strike(&spam, None, Some(&ALICE), None, later)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_unverified() {
+        let strikes = Strikes::new(&settings());
+        let spam = [rejection(BanTypesEnum::CONTENT)];
+        let now = date("2024-01-01T00:00:00Z");
+
+        for _ in 0..2 {
+            strikes.strike(&spam, None, None, Some("1.1.1.1"), now);
+        }
+        let bans = strikes.strike(&spam, None, None, Some("1.1.1.1"), now);
+        assert_eq!(bans.len(), 1);
+        assert_eq!(bans[0].ban.ban_type, BanTypesEnum::IP);
+    }
+
+    #[test]
+    fn test_categories() {
+        let strikes = Strikes::new(&settings());
+        let now = date("2024-01-01T00:00:00Z");
+
+        let limited = Violation::new(Check::RateLimit, "rate-limited".to_string());
+
+        // User bans and rate limits don't count by default
+        for _ in 0..5 {
+            assert!(strikes
+                .strike(
+                    &[rejection(BanTypesEnum::USER)],
+                    Some(&limited),
+                    Some(&ALICE),
+                    None,
+                    now
+                )
+                .is_empty());
+        }
+
+        let strikes = Strikes::new(&StrikeSettings {
+            rate_limited: true,
+            ..settings()
+        });
+        for _ in 0..2 {
+            strikes.strike(&[], Some(&limited), Some(&ALICE), None, now);
+        }
+        assert_eq!(
+            strikes
+                .strike(&[], Some(&limited), Some(&ALICE), None, now)
+                .len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_apply_and_expire() {
+        let banhammer = Mutex::new(BanHammer::default());
+        let audit = Mutex::new(AuditLog::default());
+        let log = RejectionLog::new(LogFormat::Json, None, vec![]);
+        let strikes = Strikes::new(&settings());
+        let spam = [rejection(BanTypesEnum::CONTENT)];
+        let now = Utc::now();
+
+        let mut bans = vec![];
+        for _ in 0..3 {
+            bans = strikes.strike(&spam, None, Some(&ALICE), None, now);
+        }
+        apply(&banhammer, &audit, &log, bans.clone()).await;
+        // Already banned
+        apply(&banhammer, &audit, &log, bans).await;
+
+        let mut banhammer = banhammer.into_inner();
+        assert_eq!(banhammer.users.len(), 1);
+        let item = banhammer.item(&banhammer.users[0].clone());
+        assert_eq!(item.source.as_deref(), Some(AUTO_SOURCE));
+        assert!(item.expires.is_some());
+
+        let entries = audit.into_inner().query(&Default::default());
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].actor, SYSTEM_ACTOR);
+        assert_eq!(entries[0].action, "AutoBan");
+
+        assert!(banhammer.remove_expired(now).is_empty());
+        let expired = banhammer.remove_expired(now + chrono::Duration::hours(1));
+        assert_eq!(expired.len(), 1);
+        assert!(banhammer.users.is_empty());
+        assert!(banhammer.expirations.is_empty());
+    }
+}
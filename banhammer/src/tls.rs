@@ -0,0 +1,382 @@
+use std::{
+    fmt, fs,
+    io::{self, BufReader},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use tokio::{
+    net::{TcpListener, TcpStream},
+    signal::unix::{signal, SignalKind},
+    sync::mpsc,
+};
+use tokio_rustls::{
+    rustls::{
+        server::{AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient},
+        Certificate, PrivateKey, RootCertStore, ServerConfig,
+    },
+    server::TlsStream,
+    TlsAcceptor,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{error, info, warn};
+
+/// Handshakes taking longer are dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Files of a listener TLS configuration.
+#[derive(Debug, Clone)]
+pub struct TlsFiles {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// CA used to verify client certificates
+    pub client_ca: Option<PathBuf>,
+    /// Whether clients without a certificate are rejected,
+    /// only relevant with a client CA
+    pub client_auth_required: bool,
+}
+
+#[derive(Debug)]
+pub enum TlsError {
+    Io(PathBuf, io::Error),
+    NoCertificate(PathBuf),
+    NoKey(PathBuf),
+    Config(String),
+}
+
+impl fmt::Display for TlsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TlsError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            TlsError::NoCertificate(path) => write!(f, "{}: no certificate found", path.display()),
+            TlsError::NoKey(path) => write!(f, "{}: no private key found", path.display()),
+            TlsError::Config(e) => write!(f, "invalid TLS configuration: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TlsError {}
+
+fn read_pem(path: &Path) -> Result<Vec<rustls_pemfile::Item>, TlsError> {
+    let file = fs::File::open(path).map_err(|e| TlsError::Io(path.to_path_buf(), e))?;
+    rustls_pemfile::read_all(&mut BufReader::new(file))
+        .map_err(|e| TlsError::Io(path.to_path_buf(), e))
+}
+
+fn read_certificates(path: &Path) -> Result<Vec<Certificate>, TlsError> {
+    let certs: Vec<Certificate> = read_pem(path)?
+        .into_iter()
+        .filter_map(|item| match item {
+            rustls_pemfile::Item::X509Certificate(der) => Some(Certificate(der)),
+            _ => None,
+        })
+        .collect();
+
+    match certs.is_empty() {
+        true => Err(TlsError::NoCertificate(path.to_path_buf())),
+        false => Ok(certs),
+    }
+}
+
+fn read_key(path: &Path) -> Result<PrivateKey, TlsError> {
+    read_pem(path)?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(der)
+            | rustls_pemfile::Item::RSAKey(der)
+            | rustls_pemfile::Item::ECKey(der) => Some(PrivateKey(der)),
+            _ => None,
+        })
+        .ok_or(TlsError::NoKey(path.to_path_buf()))
+}
+
+/// Builds a server configuration from PEM files.
+pub fn load_config(files: &TlsFiles) -> Result<ServerConfig, TlsError> {
+    let certs = read_certificates(&files.cert)?;
+    let key = read_key(&files.key)?;
+
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = match &files.client_ca {
+        Some(client_ca) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certificates(client_ca)? {
+                roots
+                    .add(&cert)
+                    .map_err(|e| TlsError::Config(e.to_string()))?;
+            }
+
+            let verifier = match files.client_auth_required {
+                true => AllowAnyAuthenticatedClient::new(roots).boxed(),
+                false => AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed(),
+            };
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| TlsError::Config(e.to_string()))?;
+    // gRPC runs over HTTP/2
+    config.alpn_protocols = vec![b"h2".to_vec()];
+
+    Ok(config)
+}
+
+/// A listener TLS configuration that can be reloaded from its files
+/// without restarting the listener. Only new connections use the
+/// reloaded certificates.
+#[derive(Clone)]
+pub struct ReloadableTls {
+    files: TlsFiles,
+    config: Arc<RwLock<Arc<ServerConfig>>>,
+}
+
+impl fmt::Debug for ReloadableTls {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ReloadableTls({:?})", self.files)
+    }
+}
+
+impl ReloadableTls {
+    pub fn new(files: TlsFiles) -> Result<Self, TlsError> {
+        let config = load_config(&files)?;
+
+        Ok(Self {
+            files,
+            config: Arc::new(RwLock::new(Arc::new(config))),
+        })
+    }
+
+    /// Reloads the configuration files. The current configuration
+    /// is kept when the files are invalid.
+    pub fn reload(&self) -> Result<(), TlsError> {
+        let config = load_config(&self.files)?;
+        *self.config.write().unwrap() = Arc::new(config);
+        Ok(())
+    }
+
+    fn acceptor(&self) -> TlsAcceptor {
+        TlsAcceptor::from(self.config.read().unwrap().clone())
+    }
+
+    /// Accepts TLS connections on a listener. Handshakes run concurrently
+    /// so a slow client can't hold the others back, and failed handshakes
+    /// are dropped.
+    pub fn incoming(
+        self,
+        listener: TcpListener,
+    ) -> ReceiverStream<io::Result<TlsStream<TcpStream>>> {
+        let (sender, receiver) = mpsc::channel(64);
+
+        tokio::spawn(async move {
+            while !sender.is_closed() {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("TLS listener: {}", e);
+                        continue;
+                    }
+                };
+                _ = stream.set_nodelay(true);
+
+                let acceptor = self.acceptor();
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    let handshake =
+                        tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream));
+                    if let Ok(Ok(stream)) = handshake.await {
+                        _ = sender.send(Ok(stream)).await;
+                    }
+                });
+            }
+        });
+
+        ReceiverStream::new(receiver)
+    }
+}
+
+/// Reloads every TLS configuration when the process receives SIGHUP.
+pub fn reload_on_sighup(configs: Vec<ReloadableTls>) -> io::Result<()> {
+    let mut hangup = signal(SignalKind::hangup())?;
+
+    tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            for tls in &configs {
+                match tls.reload() {
+                    Ok(_) => info!(path = %tls.files.cert.display(), "TLS certificate reloaded"),
+                    Err(e) => error!("TLS reload failed, keeping the current certificate: {}", e),
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use banhammer_grpc::grpc::{
+        authorization_client::AuthorizationClient, authorization_server::AuthorizationServer,
+        Decision, Event, EventRequest,
+    };
+    use rcgen::{BasicConstraints, CertificateParams, IsCa, SanType};
+    use tokio::sync::Mutex;
+    use tonic::transport::{self, Channel, ClientTlsConfig, Endpoint, Identity, Server};
+
+    use crate::config::Config;
+    use crate::hammer::{Authz, BanHammer};
+
+    /// A CA and a certificate it signed, PEM encoded.
+    struct Pki {
+        ca: String,
+        cert: String,
+        key: String,
+    }
+
+    fn generate(name: &str) -> Pki {
+        let mut params = CertificateParams::new(vec![]);
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = rcgen::Certificate::from_params(params).unwrap();
+
+        let mut params = CertificateParams::new(vec![name.to_string()]);
+        params
+            .subject_alt_names
+            .push(SanType::IpAddress("127.0.0.1".parse().unwrap()));
+        let cert = rcgen::Certificate::from_params(params).unwrap();
+
+        Pki {
+            ca: ca.serialize_pem().unwrap(),
+            cert: cert.serialize_pem_with_signer(&ca).unwrap(),
+            key: cert.serialize_private_key_pem(),
+        }
+    }
+
+    fn write(dir: &Path, server: &Pki, client: &Pki) -> TlsFiles {
+        fs::write(dir.join("server.pem"), &server.cert).unwrap();
+        fs::write(dir.join("server.key"), &server.key).unwrap();
+        fs::write(dir.join("client-ca.pem"), &client.ca).unwrap();
+
+        TlsFiles {
+            cert: dir.join("server.pem"),
+            key: dir.join("server.key"),
+            client_ca: Some(dir.join("client-ca.pem")),
+            client_auth_required: true,
+        }
+    }
+
+    async fn serve(tls: ReloadableTls) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let authz = Authz::new(
+            Arc::new(Mutex::new(BanHammer::default())),
+            &Config::default(),
+        );
+
+        tokio::spawn(
+            Server::builder()
+                .add_service(AuthorizationServer::new(authz))
+                .serve_with_incoming(tls.incoming(listener)),
+        );
+
+        port
+    }
+
+    async fn connect(
+        port: u16,
+        server: &Pki,
+        client: Option<&Pki>,
+    ) -> Result<Channel, transport::Error> {
+        let mut tls = ClientTlsConfig::new()
+            .ca_certificate(transport::Certificate::from_pem(&server.ca))
+            .domain_name("localhost");
+        if let Some(client) = client {
+            tls = tls.identity(Identity::from_pem(&client.cert, &client.key));
+        }
+
+        Endpoint::from_shared(format!("https://127.0.0.1:{}", port))?
+            .tls_config(tls)?
+            .connect()
+            .await
+    }
+
+    async fn admit(channel: Channel) -> Result<i32, tonic::Status> {
+        let request = EventRequest {
+            event: Some(Event {
+                pubkey: vec![0; 32],
+                content: "hello".to_string(),
+                ..Default::default()
+            }),
+            ip_addr: Some("127.0.0.1".to_string()),
+            ..Default::default()
+        };
+
+        let reply = AuthorizationClient::new(channel)
+            .event_admit(request)
+            .await?;
+        Ok(reply.into_inner().decision)
+    }
+
+    #[tokio::test]
+    async fn test_tls_and_client_certificates() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = generate("localhost");
+        let client = generate("relay");
+
+        let tls = ReloadableTls::new(write(dir.path(), &server, &client)).unwrap();
+        let port = serve(tls).await;
+
+        let channel = connect(port, &server, Some(&client)).await.unwrap();
+        assert_eq!(admit(channel).await.unwrap(), Decision::Permit as i32);
+
+        // Client certificates are required
+        let rejected = match connect(port, &server, None).await {
+            Ok(channel) => admit(channel).await.is_err(),
+            Err(_) => true,
+        };
+        assert!(rejected);
+
+        // Certificates signed by another CA are rejected
+        let rogue = generate("relay");
+        let rejected = match connect(port, &server, Some(&rogue)).await {
+            Ok(channel) => admit(channel).await.is_err(),
+            Err(_) => true,
+        };
+        assert!(rejected);
+    }
+
+    #[tokio::test]
+    async fn test_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = generate("localhost");
+        let client = generate("relay");
+
+        let mut files = write(dir.path(), &server, &client);
+        files.client_ca = None;
+        let tls = ReloadableTls::new(files).unwrap();
+        let port = serve(tls.clone()).await;
+
+        let channel = connect(port, &server, None).await.unwrap();
+        assert_eq!(admit(channel).await.unwrap(), Decision::Permit as i32);
+
+        // Invalid files keep the current certificate
+        fs::write(dir.path().join("server.pem"), "not a certificate").unwrap();
+        assert!(matches!(tls.reload(), Err(TlsError::NoCertificate(_))));
+        assert!(connect(port, &server, None).await.is_ok());
+
+        let renewed = generate("localhost");
+        write(dir.path(), &renewed, &client);
+        tls.reload().unwrap();
+
+        let channel = connect(port, &renewed, None).await.unwrap();
+        assert_eq!(admit(channel).await.unwrap(), Decision::Permit as i32);
+        let rejected = match connect(port, &server, None).await {
+            Ok(channel) => admit(channel).await.is_err(),
+            Err(_) => true,
+        };
+        assert!(rejected);
+    }
+}
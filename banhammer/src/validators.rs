@@ -0,0 +1,228 @@
+use banhammer_grpc::grpc::Event;
+use serde::{Deserialize, Serialize};
+
+/// Conformance checks, each disabled by default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConformanceSettings {
+    /// `p` and `e` tags reference 64 characters lowercase hex pubkeys and ids
+    pub references: bool,
+    /// `a` tags reference addressable events as `kind:pubkey:d`
+    pub addresses: bool,
+    /// Addressable events, of kinds 30000 to 39999, have a `d` tag
+    pub d_tags: bool,
+    /// `expiration` tags are timestamps
+    pub expiration: bool,
+    /// Kind 0 content is a JSON object
+    pub metadata: bool,
+}
+
+/// A NIP an event doesn't conform to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Nonconformity {
+    pub nip: &'static str,
+    pub message: String,
+}
+
+impl Nonconformity {
+    fn new(nip: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            nip,
+            message: message.into(),
+        }
+    }
+}
+
+/// Whether a string is 64 characters of lowercase hex, as pubkeys and ids
+/// are written in tags.
+fn is_hex64(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Whether a string is an address of an event, `kind:pubkey:d`.
+fn is_address(s: &str) -> bool {
+    let mut parts = s.splitn(3, ':');
+    let (Some(kind), Some(pubkey), Some(_)) = (parts.next(), parts.next(), parts.next()) else {
+        return false;
+    };
+    kind.parse::<u64>().is_ok() && is_hex64(pubkey)
+}
+
+/// Denies malformed events that confuse clients, reporting the NIP
+/// they don't conform to.
+#[derive(Debug, Default)]
+pub struct Validator {
+    settings: ConformanceSettings,
+}
+
+impl Validator {
+    pub fn new(settings: &ConformanceSettings) -> Self {
+        Self {
+            settings: settings.clone(),
+        }
+    }
+
+    /// Returns the first nonconformity of an event, if any.
+    pub fn check(&self, event: &Event) -> Option<Nonconformity> {
+        let settings = &self.settings;
+
+        for tag in &event.tags {
+            let (Some(name), value) = (tag.values.first(), tag.values.get(1)) else {
+                continue;
+            };
+            let value = value.map(String::as_str).unwrap_or_default();
+
+            match name.as_str() {
+                "p" if settings.references && !is_hex64(value) => {
+                    return Some(Nonconformity::new(
+                        "NIP-01",
+                        "p tag value is not a lowercase hex pubkey",
+                    ));
+                }
+                "e" if settings.references && !is_hex64(value) => {
+                    return Some(Nonconformity::new(
+                        "NIP-01",
+                        "e tag value is not a lowercase hex event id",
+                    ));
+                }
+                "a" if settings.addresses && !is_address(value) => {
+                    return Some(Nonconformity::new(
+                        "NIP-01",
+                        "a tag value is not an address, kind:pubkey:d",
+                    ));
+                }
+                "expiration" if settings.expiration && value.parse::<u64>().is_err() => {
+                    return Some(Nonconformity::new(
+                        "NIP-40",
+                        "expiration tag value is not a timestamp",
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        if settings.d_tags
+            && (30000..40000).contains(&event.kind)
+            && !event
+                .tags
+                .iter()
+                .any(|tag| tag.values.first().map(String::as_str) == Some("d"))
+        {
+            return Some(Nonconformity::new(
+                "NIP-01",
+                "addressable event without a d tag",
+            ));
+        }
+
+        if settings.metadata
+            && event.kind == 0
+            && serde_json::from_str::<serde_json::Map<String, serde_json::Value>>(&event.content)
+                .is_err()
+        {
+            return Some(Nonconformity::new(
+                "NIP-01",
+                "metadata content is not a JSON object",
+            ));
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use banhammer_grpc::grpc::event::TagEntry;
+
+    fn event(kind: u64, content: &str, tags: &[&[&str]]) -> Event {
+        Event {
+            kind,
+            content: content.to_string(),
+            tags: tags
+                .iter()
+                .map(|values| TagEntry {
+                    values: values.iter().map(|v| v.to_string()).collect(),
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn nip(validator: &Validator, event: &Event) -> Option<&'static str> {
+        validator.check(event).map(|n| n.nip)
+    }
+
+    #[test]
+    fn test_check() {
+        let validator = Validator::new(&ConformanceSettings {
+            references: true,
+            addresses: true,
+            d_tags: true,
+            expiration: true,
+            metadata: true,
+        });
+        let hex = "a".repeat(64);
+        let address = format!("30023:{}:my-article", hex);
+
+        let valid = event(
+            1,
+            "",
+            &[
+                &["p", &hex],
+                &["e", &hex, "", "root"],
+                &["a", &address],
+                &["a", &format!("30023:{}:", hex)],
+                &["expiration", "1700000000"],
+                &["t", "nostr"],
+                &[],
+            ],
+        );
+        assert_eq!(validator.check(&valid), None);
+
+        for tags in [
+            &[&["p", &hex.to_uppercase()][..]][..],
+            &[&["p", "npub1abc"]],
+            &[&["p"]],
+            &[&["e", &hex[1..]]],
+            &[&["a", &hex]],
+            &[&["a", &format!("note:{}:d", hex)]],
+        ] {
+            assert_eq!(nip(&validator, &event(1, "", tags)), Some("NIP-01"));
+        }
+        assert_eq!(
+            validator.check(&event(1, "", &[&["expiration", "tomorrow"]])),
+            Some(Nonconformity::new(
+                "NIP-40",
+                "expiration tag value is not a timestamp"
+            ))
+        );
+
+        assert_eq!(nip(&validator, &event(30023, "", &[])), Some("NIP-01"));
+        assert_eq!(nip(&validator, &event(30023, "", &[&["d", ""]])), None);
+
+        assert_eq!(nip(&validator, &event(0, r#"{"name":"bob"}"#, &[])), None);
+        assert_eq!(nip(&validator, &event(0, "bob", &[])), Some("NIP-01"));
+        assert_eq!(
+            nip(&validator, &event(0, r#"["bob"]"#, &[])),
+            Some("NIP-01")
+        );
+        assert_eq!(nip(&validator, &event(1, "bob", &[])), None);
+    }
+
+    #[test]
+    fn test_switches() {
+        let validator = Validator::new(&ConformanceSettings {
+            expiration: true,
+            ..Default::default()
+        });
+        assert_eq!(validator.check(&event(1, "", &[&["p", "bob"]])), None);
+        assert_eq!(validator.check(&event(30023, "", &[])), None);
+        assert!(validator
+            .check(&event(1, "", &[&["expiration", "-1"]]))
+            .is_some());
+
+        let validator = Validator::new(&ConformanceSettings::default());
+        assert_eq!(validator.check(&event(0, "bob", &[&["e", "1"]])), None);
+    }
+}
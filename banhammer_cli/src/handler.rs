@@ -1,22 +1,38 @@
-use banhammer_cli::{CommandsHandler, InputFormatter, InputValidators};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use banhammer_cli::{CommandsHandler, InputFormatter, InputValidators, MetadataInterceptor};
 use banhammer_grpc::{
+    banlist::{self, BanListFormat},
     grpc::{
-        validation_control_client::ValidationControlClient, AddBanRequest, BanItem,
-        ListBansRequest, RemoveBanRequest, StateRequest,
+        validation_control_client::ValidationControlClient, AddBanRequest, AuditEntry,
+        AuditLogRequest, BanItem, BanType, DecisionTrace, DiffRevisionsRequest, ExportNip51Request,
+        ImportNip51Request, ListBansRequest, ListRevisionsRequest, Offender, PruneBansRequest,
+        QueryRejectionsRequest, RejectionRecord, RemoveBanRequest, Revision, RollbackRequest,
+        SetLockdownRequest, StateRequest, StatsRequest, StatsResponse, StreamDecisionsRequest,
     },
-    BanTypesEnum,
 };
 use clap::{Parser, ValueEnum};
-use tonic::transport::Channel;
+use tonic::{codegen::InterceptedService, transport::Channel};
 
 use crate::{CliOptions, Subcommands};
 
+pub type Client = ValidationControlClient<InterceptedService<Channel, MetadataInterceptor>>;
+
 #[derive(Tabled)]
 struct BanTemplate {
     index: i32,
     content: String,
     regex: bool,
     reason: String,
+    source: String,
+    hits: u64,
+    first_hit: String,
+    last_hit: String,
+    expires: String,
 }
 
 impl From<(usize, &BanItem)> for BanTemplate {
@@ -26,10 +42,16 @@ impl From<(usize, &BanItem)> for BanTemplate {
             content: value.1.content.clone(),
             regex: value.1.regex,
             reason: value.1.reason.clone().unwrap_or("".to_string()),
+            source: value.1.source.clone().unwrap_or("".to_string()),
+            hits: value.1.hits,
+            first_hit: value.1.first_hit.clone().unwrap_or_default(),
+            last_hit: value.1.last_hit.clone().unwrap_or_default(),
+            expires: value.1.expires.clone().unwrap_or_default(),
         }
     }
 }
 
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
 #[derive(Clone, PartialEq, Parser, Debug, ValueEnum)]
 pub enum BanTypeOptionEnum {
     CONTENT = 0,
@@ -38,6 +60,20 @@ pub enum BanTypeOptionEnum {
     IP = 3,
     NIP05 = 4,
     LUD16 = 5,
+    ORIGIN = 6,
+    USER_AGENT = 7,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+pub enum DecisionOptionEnum {
+    Permit,
+    Deny,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, ValueEnum)]
+pub enum LockdownOptionEnum {
+    On,
+    Off,
 }
 
 use tabled::{Table, Tabled};
@@ -46,11 +82,11 @@ use tabled::{Table, Tabled};
 /// It provides a dispatcher that will send the command
 /// details to sub-handlers
 pub struct CliHandler {
-    pub client: ValidationControlClient<Channel>,
+    pub client: Client,
 }
 
 impl CliHandler {
-    pub async fn dispatcher(&mut self, command: Subcommands, opts: CliOptions) {
+    pub async fn dispatcher(&mut self, command: Subcommands, _opts: CliOptions) {
         match command {
             Subcommands::State => {
                 let request = tonic::Request::new(StateRequest {});
@@ -87,15 +123,151 @@ impl CliHandler {
 
                 add_handler.handle().await
             }
-            _ => {
-                panic!("Oops. Looks like the matrix broke ! :(")
+            Subcommands::ExportNip51 {
+                kind,
+                identifier,
+                output,
+            } => {
+                let mut export_handler = ExportNip51Handler {
+                    client: self.client.clone(),
+                };
+
+                export_handler.handle(kind, identifier, output).await
+            }
+            Subcommands::ImportNip51 { file } => {
+                let mut import_handler = ImportNip51Handler {
+                    client: self.client.clone(),
+                };
+
+                import_handler.handle(file).await
+            }
+            Subcommands::History { limit } => {
+                let mut history_handler = HistoryHandler {
+                    client: self.client.clone(),
+                };
+
+                history_handler.handle(limit).await
+            }
+            Subcommands::Diff { from, to } => {
+                // A single revision shows the changes it introduced
+                let (from, to) = match to {
+                    Some(to) => (from, to),
+                    None => (from.saturating_sub(1), from),
+                };
+
+                let mut diff_handler = DiffHandler {
+                    client: self.client.clone(),
+                };
+
+                diff_handler.handle(from, to).await
+            }
+            Subcommands::Rollback { revision } => {
+                let mut rollback_handler = RollbackHandler {
+                    client: self.client.clone(),
+                };
+
+                rollback_handler.handle(revision).await
+            }
+            Subcommands::Prune { days, dry_run } => {
+                let mut prune_handler = PruneHandler {
+                    client: self.client.clone(),
+                };
+
+                prune_handler.handle(days, dry_run).await
+            }
+            Subcommands::Stats {
+                watch,
+                interval,
+                top,
+            } => {
+                let mut stats_handler = StatsHandler {
+                    client: self.client.clone(),
+                };
+
+                stats_handler.handle(watch, interval, top).await
             }
+            Subcommands::Tail {
+                decision,
+                ban_type,
+                pubkey,
+                ip,
+                kind,
+            } => {
+                let request = StreamDecisionsRequest {
+                    decision: decision.map(|d| format!("{:?}", d).to_lowercase()),
+                    ban_type: ban_type.map(|t| t as i32),
+                    pubkey,
+                    ip,
+                    kind,
+                };
+
+                let mut tail_handler = TailHandler {
+                    client: self.client.clone(),
+                };
+
+                tail_handler.handle(request).await
+            }
+            Subcommands::Rejections {
+                pubkey,
+                event,
+                ip,
+                since,
+                until,
+                limit,
+                verbose,
+            } => {
+                let request = QueryRejectionsRequest {
+                    pubkey,
+                    event_id: event,
+                    ip,
+                    since,
+                    until,
+                    limit,
+                };
+
+                let mut rejections_handler = RejectionsHandler {
+                    client: self.client.clone(),
+                };
+
+                rejections_handler.handle(request, verbose).await
+            }
+            Subcommands::Audit {
+                since,
+                until,
+                actor,
+                limit,
+                verbose,
+            } => {
+                let request = AuditLogRequest {
+                    since,
+                    until,
+                    actor,
+                    limit,
+                };
+
+                let mut audit_handler = AuditHandler {
+                    client: self.client.clone(),
+                };
+
+                audit_handler.handle(request, verbose).await
+            }
+            Subcommands::Lockdown { state } => {
+                let mut lockdown_handler = LockdownHandler {
+                    client: self.client.clone(),
+                };
+
+                lockdown_handler
+                    .handle(state == LockdownOptionEnum::On)
+                    .await
+            }
+            // Handled before connecting to the core service
+            Subcommands::Convert { .. } => {}
         };
     }
 }
 
 pub struct ListHandler {
-    client: ValidationControlClient<Channel>,
+    client: Client,
 }
 
 impl CommandsHandler for ListHandler {}
@@ -117,7 +289,7 @@ impl ListHandler {
                     .map(|(i, ban)| BanTemplate::from((i, ban)))
                     .collect();
 
-                if rows.len() == 0 {
+                if rows.is_empty() {
                     println!("No ban listed.");
                     return;
                 };
@@ -137,7 +309,7 @@ impl ListHandler {
 }
 
 pub struct AddHandler {
-    client: ValidationControlClient<Channel>,
+    client: Client,
 }
 
 impl CommandsHandler for AddHandler {}
@@ -146,7 +318,7 @@ impl AddHandler {
     pub async fn handle(&mut self) {
         // Add cli input here
         let ban_type = InputFormatter::input_to_ban_type(self.get_input(
-            "Ban type (content/user/ip/tag) : ",
+            "Ban type (content/user/ip/tag/origin/user_agent) : ",
             Some(InputValidators::ban_type_validator),
         ));
         let regex = InputFormatter::input_to_boolean(self.get_input(
@@ -164,20 +336,24 @@ impl AddHandler {
             regex,
             reason: Some(reason),
             expires_in: None,
-            ban_type: ban_type,
+            ban_type,
         };
 
         let response = self.client.add_ban(ban).await;
 
         match response {
-            Ok(r) => {}
-            Err(e) => {}
+            Ok(_) => {
+                println!("Ban added.");
+            }
+            Err(e) => {
+                println!("Error | {}: {}", e.code(), e.message());
+            }
         }
     }
 }
 
 pub struct RemoveHandler {
-    pub client: ValidationControlClient<Channel>,
+    pub client: Client,
 }
 
 impl CommandsHandler for RemoveHandler {}
@@ -186,13 +362,752 @@ impl RemoveHandler {
     pub async fn handle(&mut self, index: u32, ban_type: i32) {
         let request = RemoveBanRequest { index, ban_type };
         let response = self.client.remove_ban(request).await;
-        println!("{}", "Ban removed.");
+
+        match response {
+            Ok(_) => {
+                println!("Ban removed.");
+            }
+            Err(e) => {
+                println!("Error | {}: {}", e.code(), e.message());
+            }
+        }
+    }
+}
+
+pub struct ExportNip51Handler {
+    pub client: Client,
+}
+
+impl CommandsHandler for ExportNip51Handler {}
+
+impl ExportNip51Handler {
+    pub async fn handle(&mut self, kind: u64, identifier: Option<String>, output: Option<PathBuf>) {
+        let request = ExportNip51Request { kind, identifier };
+        let event = match self.client.export_nip51(request).await {
+            Ok(r) => r.into_inner().event,
+            Err(e) => {
+                println!("Error | {}: {}", e.code(), e.message());
+                return;
+            }
+        };
+
+        match output {
+            Some(path) => match fs::write(&path, event) {
+                Ok(_) => println!("Event written to {}.", path.display()),
+                Err(e) => println!("Error | {}: {}", path.display(), e),
+            },
+            None => println!("{}", event),
+        }
+    }
+}
+
+pub struct ImportNip51Handler {
+    pub client: Client,
+}
+
+impl CommandsHandler for ImportNip51Handler {}
+
+impl ImportNip51Handler {
+    pub async fn handle(&mut self, file: PathBuf) {
+        let event = match fs::read_to_string(&file) {
+            Ok(event) => event,
+            Err(e) => {
+                println!("Error | {}: {}", file.display(), e);
+                return;
+            }
+        };
+
+        match self.client.import_nip51(ImportNip51Request { event }).await {
+            Ok(r) => {
+                let r = r.into_inner();
+                println!(
+                    "{} bans imported, {} already present.",
+                    r.imported, r.skipped
+                );
+            }
+            Err(e) => {
+                println!("Error | {}: {}", e.code(), e.message());
+            }
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct RevisionTemplate {
+    revision: u64,
+    author: String,
+    timestamp: String,
+    added: u32,
+    removed: u32,
+}
+
+impl From<&Revision> for RevisionTemplate {
+    fn from(value: &Revision) -> Self {
+        Self {
+            revision: value.number,
+            author: value.author.clone(),
+            timestamp: value.timestamp.clone(),
+            added: value.added,
+            removed: value.removed,
+        }
+    }
+}
+
+pub struct HistoryHandler {
+    pub client: Client,
+}
+
+impl CommandsHandler for HistoryHandler {}
+
+impl HistoryHandler {
+    pub async fn handle(&mut self, limit: Option<u32>) {
+        let response = self
+            .client
+            .list_revisions(ListRevisionsRequest { limit })
+            .await;
+
+        match response {
+            Ok(r) => {
+                let rows: Vec<RevisionTemplate> = r
+                    .into_inner()
+                    .revisions
+                    .iter()
+                    .map(RevisionTemplate::from)
+                    .collect();
+
+                if rows.is_empty() {
+                    println!("No revision recorded.");
+                    return;
+                }
+
+                self.print(rows)
+            }
+            Err(e) => {
+                println!("Error | {}: {}", e.code(), e.message());
+            }
+        }
     }
 }
+
+#[derive(Tabled)]
+struct ChangeTemplate {
+    change: &'static str,
+    ban_type: String,
+    content: String,
+    regex: bool,
+    reason: String,
+    source: String,
+}
+
+impl ChangeTemplate {
+    fn new(change: &'static str, ban: &BanItem) -> Self {
+        Self {
+            change,
+            ban_type: BanType::try_from(ban.ban_type)
+                .map(|t| t.as_str_name().to_lowercase())
+                .unwrap_or_default(),
+            content: ban.content.clone(),
+            regex: ban.regex,
+            reason: ban.reason.clone().unwrap_or_default(),
+            source: ban.source.clone().unwrap_or_default(),
+        }
+    }
+}
+
+pub struct DiffHandler {
+    pub client: Client,
+}
+
+impl CommandsHandler for DiffHandler {}
+
+impl DiffHandler {
+    pub async fn handle(&mut self, from: u64, to: u64) {
+        let response = self
+            .client
+            .diff_revisions(DiffRevisionsRequest { from, to })
+            .await;
+
+        match response {
+            Ok(r) => {
+                let diff = r.into_inner();
+                let rows: Vec<ChangeTemplate> = diff
+                    .removed
+                    .iter()
+                    .map(|ban| ChangeTemplate::new("-", ban))
+                    .chain(diff.added.iter().map(|ban| ChangeTemplate::new("+", ban)))
+                    .collect();
+
+                if rows.is_empty() {
+                    println!("No change between revisions {} and {}.", from, to);
+                    return;
+                }
+
+                self.print(rows)
+            }
+            Err(e) => {
+                println!("Error | {}: {}", e.code(), e.message());
+            }
+        }
+    }
+}
+
+pub struct RollbackHandler {
+    pub client: Client,
+}
+
+impl CommandsHandler for RollbackHandler {}
+
+impl RollbackHandler {
+    pub async fn handle(&mut self, revision: u64) {
+        let response = self.client.rollback(RollbackRequest { revision }).await;
+
+        match response {
+            Ok(r) => match r.into_inner().revision {
+                Some(new) => println!("Rolled back to revision {} as revision {}.", revision, new),
+                None => println!("Bans already match revision {}.", revision),
+            },
+            Err(e) => {
+                println!("Error | {}: {}", e.code(), e.message());
+            }
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct PruneTemplate {
+    ban_type: String,
+    content: String,
+    regex: bool,
+    source: String,
+    hits: u64,
+    last_hit: String,
+}
+
+impl From<&BanItem> for PruneTemplate {
+    fn from(value: &BanItem) -> Self {
+        Self {
+            ban_type: BanType::try_from(value.ban_type)
+                .map(|t| t.as_str_name().to_lowercase())
+                .unwrap_or_default(),
+            content: value.content.clone(),
+            regex: value.regex,
+            source: value.source.clone().unwrap_or_default(),
+            hits: value.hits,
+            last_hit: value.last_hit.clone().unwrap_or_default(),
+        }
+    }
+}
+
+pub struct PruneHandler {
+    pub client: Client,
+}
+
+impl CommandsHandler for PruneHandler {}
+
+impl PruneHandler {
+    pub async fn handle(&mut self, days: u32, dry_run: bool) {
+        let response = self
+            .client
+            .prune_bans(PruneBansRequest { days, dry_run })
+            .await;
+
+        let response = match response {
+            Ok(r) => r.into_inner(),
+            Err(e) => {
+                println!("Error | {}: {}", e.code(), e.message());
+                return;
+            }
+        };
+
+        if response.bans.is_empty() {
+            println!("No ban without hits for {} days.", days);
+            return;
+        }
+
+        self.print(response.bans.iter().map(PruneTemplate::from).collect());
+        match (dry_run, response.revision) {
+            (true, _) => println!("{} bans would be removed.", response.bans.len()),
+            (false, Some(revision)) => println!(
+                "{} bans removed, recorded as revision {}.",
+                response.bans.len(),
+                revision
+            ),
+            (false, None) => println!("{} bans removed.", response.bans.len()),
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct WindowTemplate {
+    window: String,
+    evaluated: u64,
+    denied: u64,
+    denial_ratio: String,
+}
+
+#[derive(Tabled)]
+struct RuleCountTemplate {
+    ban_type: String,
+    source: String,
+    rules: u64,
+}
+
+#[derive(Tabled)]
+struct TopRuleTemplate {
+    ban_type: String,
+    content: String,
+    hits: u64,
+    last_hit: String,
+}
+
+#[derive(Tabled)]
+struct OffenderTemplate {
+    key: String,
+    denials: u64,
+}
+
+fn ban_type_name(ban_type: i32) -> String {
+    BanType::try_from(ban_type)
+        .map(|t| t.as_str_name().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Formats a duration as `1d 2h 3m 4s`, leaving out leading zeros.
+fn format_uptime(seconds: u64) -> String {
+    let units = [
+        (seconds / 86400, "d"),
+        (seconds / 3600 % 24, "h"),
+        (seconds / 60 % 60, "m"),
+        (seconds % 60, "s"),
+    ];
+
+    let parts: Vec<String> = units
+        .iter()
+        .skip_while(|(value, unit)| *value == 0 && *unit != "s")
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect();
+    parts.join(" ")
+}
+
+pub struct LockdownHandler {
+    pub client: Client,
+}
+
+impl CommandsHandler for LockdownHandler {}
+
+impl LockdownHandler {
+    pub async fn handle(&mut self, active: bool) {
+        let response = match self
+            .client
+            .set_lockdown(SetLockdownRequest { active })
+            .await
+        {
+            Ok(r) => r.into_inner(),
+            Err(e) => {
+                println!("Error | {}: {}", e.code(), e.message());
+                return;
+            }
+        };
+
+        match (active, response.was_active) {
+            (true, false) => println!("Lockdown started."),
+            (true, true) => println!("Lockdown already active."),
+            (false, true) => println!("Lockdown lifted."),
+            (false, false) => println!("No lockdown active."),
+        }
+    }
+}
+
+pub struct StatsHandler {
+    pub client: Client,
+}
+
+impl CommandsHandler for StatsHandler {}
+
+impl StatsHandler {
+    pub async fn handle(&mut self, watch: bool, interval: u64, top: u32) {
+        loop {
+            let response = match self.client.stats(StatsRequest { top }).await {
+                Ok(r) => r.into_inner(),
+                Err(e) => {
+                    println!("Error | {}: {}", e.code(), e.message());
+                    return;
+                }
+            };
+
+            if watch {
+                // Clears the terminal before each refresh
+                print!("\x1B[2J\x1B[H");
+            }
+            self.render(response);
+
+            if !watch {
+                return;
+            }
+            tokio::time::sleep(Duration::from_secs(interval.max(1))).await;
+        }
+    }
+
+    fn render(&self, stats: StatsResponse) {
+        println!(
+            "banhammer {} | up {} | policy mode: {}{}",
+            stats.version,
+            format_uptime(stats.uptime_seconds),
+            stats.policy_mode,
+            match stats.lockdown {
+                true => " | LOCKDOWN",
+                false => "",
+            }
+        );
+
+        println!("\nDecisions");
+        self.print(
+            stats
+                .windows
+                .iter()
+                .map(|w| WindowTemplate {
+                    window: w.window.clone(),
+                    evaluated: w.evaluated,
+                    denied: w.denied,
+                    denial_ratio: match w.evaluated {
+                        0 => "-".to_string(),
+                        evaluated => {
+                            format!("{:.1}%", w.denied as f64 * 100.0 / evaluated as f64)
+                        }
+                    },
+                })
+                .collect(),
+        );
+
+        println!("\nRules");
+        self.print(
+            stats
+                .rules
+                .iter()
+                .map(|r| RuleCountTemplate {
+                    ban_type: ban_type_name(r.ban_type),
+                    source: r.source.clone(),
+                    rules: r.rules,
+                })
+                .collect(),
+        );
+
+        println!("\nTop rules");
+        self.print(
+            stats
+                .top_rules
+                .iter()
+                .map(|r| TopRuleTemplate {
+                    ban_type: ban_type_name(r.ban_type),
+                    content: r.content.clone(),
+                    hits: r.hits,
+                    last_hit: r.last_hit.clone().unwrap_or_default(),
+                })
+                .collect(),
+        );
+
+        let offenders = |offenders: &[Offender]| -> Vec<OffenderTemplate> {
+            offenders
+                .iter()
+                .map(|o| OffenderTemplate {
+                    key: o.key.clone(),
+                    denials: o.denials,
+                })
+                .collect()
+        };
+
+        println!("\nTop denied pubkeys (24h)");
+        self.print(offenders(&stats.top_pubkeys));
+
+        println!("\nTop denied IPs (24h)");
+        self.print(offenders(&stats.top_ips));
+    }
+}
+
+/// Formats a decision trace on a single line.
+fn format_trace(trace: &DecisionTrace) -> String {
+    let decision = match trace.denied {
+        true => "DENY",
+        false => "PERMIT",
+    };
+    let mut line = format!(
+        "{} {:<6} kind={} pubkey={} ip={} id={}",
+        trace.timestamp,
+        decision,
+        trace.kind,
+        trace.pubkey,
+        trace.ip.as_deref().unwrap_or("-"),
+        trace.event_id
+    );
+
+    for m in &trace.matches {
+        line.push_str(&format!(" {}", ban_type_name(m.ban_type)));
+        if let Some(content) = &m.content {
+            line.push_str(&format!(":{:?}", content));
+        }
+        if let Some(rule_id) = &m.rule_id {
+            line.push_str(&format!("[{}]", rule_id));
+        }
+    }
+    if let Some(message) = &trace.message {
+        line.push_str(&format!(" {:?}", message));
+    }
+
+    line.push_str(&format!(" {}us", trace.duration_us));
+    line
+}
+
+pub struct TailHandler {
+    pub client: Client,
+}
+
+impl CommandsHandler for TailHandler {}
+
+impl TailHandler {
+    pub async fn handle(&mut self, request: StreamDecisionsRequest) {
+        let mut stream = match self.client.stream_decisions(request).await {
+            Ok(r) => r.into_inner(),
+            Err(e) => {
+                println!("Error | {}: {}", e.code(), e.message());
+                return;
+            }
+        };
+
+        loop {
+            match stream.message().await {
+                Ok(Some(trace)) => println!("{}", format_trace(&trace)),
+                Ok(None) => return,
+                Err(e) => {
+                    println!("Error | {}: {}", e.code(), e.message());
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct RejectionTemplate {
+    timestamp: String,
+    decision: String,
+    event_id: String,
+    pubkey: String,
+    ip: String,
+    kind: u64,
+    rules: String,
+}
+
+impl From<&RejectionRecord> for RejectionTemplate {
+    fn from(value: &RejectionRecord) -> Self {
+        let trace = value.decision.clone().unwrap_or_default();
+        let mut rules: Vec<String> = trace
+            .matches
+            .iter()
+            .map(|m| {
+                let mut rule = ban_type_name(m.ban_type);
+                if let Some(content) = &m.content {
+                    rule.push_str(&format!(": {}", content));
+                }
+                if let Some(reason) = &m.reason {
+                    rule.push_str(&format!(" ({})", reason));
+                }
+                rule
+            })
+            .collect();
+        rules.extend(trace.message.clone());
+
+        Self {
+            timestamp: trace.timestamp,
+            decision: match trace.denied {
+                true => "deny".to_string(),
+                false => "permit".to_string(),
+            },
+            event_id: trace.event_id,
+            pubkey: trace.pubkey,
+            ip: trace.ip.unwrap_or_default(),
+            kind: trace.kind,
+            rules: rules.join("\n"),
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct RejectedEventTemplate {
+    event_id: String,
+    event: String,
+}
+
+pub struct RejectionsHandler {
+    pub client: Client,
+}
+
+impl CommandsHandler for RejectionsHandler {}
+
+impl RejectionsHandler {
+    pub async fn handle(&mut self, request: QueryRejectionsRequest, verbose: bool) {
+        let response = match self.client.query_rejections(request).await {
+            Ok(r) => r.into_inner(),
+            Err(e) => {
+                println!("Error | {}: {}", e.code(), e.message());
+                return;
+            }
+        };
+
+        if response.rejections.is_empty() {
+            println!("No rejection found.");
+            return;
+        }
+
+        self.print(
+            response
+                .rejections
+                .iter()
+                .map(RejectionTemplate::from)
+                .collect(),
+        );
+
+        if verbose {
+            self.print(
+                response
+                    .rejections
+                    .iter()
+                    .filter_map(|r| {
+                        Some(RejectedEventTemplate {
+                            event_id: r.decision.as_ref()?.event_id.clone(),
+                            event: r.event.clone()?,
+                        })
+                    })
+                    .collect(),
+            );
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct AuditTemplate {
+    sequence: u64,
+    timestamp: String,
+    actor: String,
+    address: String,
+    action: String,
+    result: String,
+}
+
+impl From<&AuditEntry> for AuditTemplate {
+    fn from(value: &AuditEntry) -> Self {
+        Self {
+            sequence: value.sequence,
+            timestamp: value.timestamp.clone(),
+            actor: value.actor.clone(),
+            address: value.address.clone().unwrap_or_default(),
+            action: value.action.clone(),
+            result: value.result.clone(),
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct AuditDetailTemplate {
+    sequence: u64,
+    payload: String,
+    hash: String,
+}
+
+pub struct AuditHandler {
+    pub client: Client,
+}
+
+impl CommandsHandler for AuditHandler {}
+
+impl AuditHandler {
+    pub async fn handle(&mut self, request: AuditLogRequest, verbose: bool) {
+        let response = match self.client.audit_log(request).await {
+            Ok(r) => r.into_inner(),
+            Err(e) => {
+                println!("Error | {}: {}", e.code(), e.message());
+                return;
+            }
+        };
+
+        match response.entries.is_empty() {
+            true => println!("No action recorded."),
+            false => {
+                self.print(response.entries.iter().map(AuditTemplate::from).collect());
+
+                if verbose {
+                    self.print(
+                        response
+                            .entries
+                            .iter()
+                            .map(|e| AuditDetailTemplate {
+                                sequence: e.sequence,
+                                payload: e.payload.clone(),
+                                hash: e.hash.clone(),
+                            })
+                            .collect(),
+                    );
+                }
+            }
+        }
+
+        match response.verified {
+            true => println!("Hash chain verified."),
+            false => println!(
+                "Warning | hash chain broken: {}",
+                response.error.unwrap_or_default()
+            ),
+        }
+    }
+}
+
+/// Converts ban list files between the supported formats.
+/// Does not require the core service to be up.
+pub struct ConvertHandler {}
+
+impl ConvertHandler {
+    pub fn handle(
+        input: &Path,
+        output: &Path,
+        from: Option<BanListFormat>,
+        to: Option<BanListFormat>,
+    ) -> bool {
+        // Converted as documents so that include directives are kept
+        let document = match from {
+            Some(format) => fs::read_to_string(input)
+                .map_err(banlist::BanListError::from)
+                .and_then(|content| banlist::parse_document(&content, format)),
+            None => banlist::read_document(input),
+        };
+
+        let document = match document {
+            Ok(document) => document,
+            Err(e) => {
+                println!("Error | {}: {}", input.display(), e);
+                return false;
+            }
+        };
+
+        match banlist::write_document(output, &document, to) {
+            Ok(format) => {
+                println!(
+                    "{} bans written to {} ({}).",
+                    document.bans.len(),
+                    output.display(),
+                    format
+                );
+                true
+            }
+            Err(e) => {
+                println!("Error | {}: {}", output.display(), e);
+                false
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
-    use super::*;
-
+    #[allow(dead_code)]
     fn test_list_handler() {}
 }
@@ -1,19 +1,55 @@
-use std::io::{self, stdin, Write};
+use std::{
+    io::{self, stdin, Write},
+    str::FromStr,
+};
+
+use banhammer_grpc::{BanTypesEnum, AUTHOR_METADATA};
 
 use tabled::{Table, Tabled};
-use tonic::async_trait;
+use tonic::{
+    async_trait,
+    metadata::{Ascii, MetadataValue},
+    service::Interceptor,
+    Request, Status,
+};
+
+/// Adds the CLI metadata to every request sent to the core service.
+#[derive(Debug, Clone, Default)]
+pub struct MetadataInterceptor {
+    /// Recorded as the author of ban changes, unless authenticated
+    pub author: Option<MetadataValue<Ascii>>,
+    /// `Bearer <token>` authorization of the moderator credential
+    pub authorization: Option<MetadataValue<Ascii>>,
+}
 
-struct Input;
+impl MetadataInterceptor {
+    /// Values that can't be sent as metadata are ignored.
+    pub fn new(author: Option<String>, token: Option<String>) -> Self {
+        Self {
+            author: author.and_then(|author| author.parse().ok()),
+            authorization: token.and_then(|token| format!("Bearer {}", token).parse().ok()),
+        }
+    }
+}
 
-impl Input {
-    pub fn get(label: &str, validators: Option<Vec<fn(String) -> bool>>) -> String {
-        let mut data = String::new();
-        print!("{}", label);
-        _ = io::stdout().flush();
-        _ = stdin().read_line(&mut data);
-        "".to_string()
+impl Interceptor for MetadataInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Some(author) = &self.author {
+            request
+                .metadata_mut()
+                .insert(AUTHOR_METADATA, author.clone());
+        }
+
+        if let Some(authorization) = &self.authorization {
+            request
+                .metadata_mut()
+                .insert("authorization", authorization.clone());
+        }
+
+        Ok(request)
     }
 }
+
 /// Common trait for sub-handlers.
 #[async_trait]
 pub trait CommandsHandler {
@@ -70,7 +106,7 @@ impl InputValidators {
     };
 
     pub fn default_guard_validator(value: String) -> bool {
-        if value == "default".to_string() {
+        if value == "default" {
             return false;
         }
 
@@ -79,7 +115,7 @@ impl InputValidators {
 
     pub const BAN_TYPE: InputValidator = InputValidator {
         validator: Self::ban_type_validator,
-        error_message: Some("Invalid value. Must be one of the following: ip, tag, user, content"),
+        error_message: Some("Invalid value. Must be one of the following: ip, tag, user, content, origin, user_agent"),
     };
 
     pub fn ban_type_validator(value: String) -> bool {
@@ -88,6 +124,8 @@ impl InputValidators {
             "content".to_string(),
             "tag".to_string(),
             "user".to_string(),
+            "origin".to_string(),
+            "user_agent".to_string(),
         ]
         .to_vec();
         ban_types.contains(&value)
@@ -133,12 +171,35 @@ impl InputFormatter {
     }
 
     pub fn input_to_ban_type(value: String) -> i32 {
-        match value.as_str() {
-            "ip" => 0,
-            "content" => 1,
-            "word" => 2,
-            "user" => 3,
-            _ => -1,
+        match BanTypesEnum::from_str(&value) {
+            Ok(ban_type) => ban_type as i32,
+            Err(_) => -1,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ban_type() {
+        for (value, ban_type) in [
+            ("ip", BanTypesEnum::IP),
+            ("content", BanTypesEnum::CONTENT),
+            ("tag", BanTypesEnum::TAG),
+            ("user", BanTypesEnum::USER),
+            ("origin", BanTypesEnum::ORIGIN),
+            ("user_agent", BanTypesEnum::USER_AGENT),
+        ] {
+            assert!(InputValidators::ban_type_validator(value.to_string()));
+            assert_eq!(
+                InputFormatter::input_to_ban_type(value.to_string()),
+                ban_type as i32
+            );
+        }
+
+        assert!(!InputValidators::ban_type_validator("nip05".to_string()));
+        assert_eq!(InputFormatter::input_to_ban_type("bot".to_string()), -1);
+    }
+}
@@ -1,13 +1,18 @@
 mod handler;
-use handler::{BanTypeOptionEnum, CliHandler};
-use std::{env, process::exit};
+use handler::{
+    BanTypeOptionEnum, CliHandler, ConvertHandler, DecisionOptionEnum, LockdownOptionEnum,
+};
+use std::{env, error::Error, fs, path::PathBuf, process::exit};
 
+use banhammer_cli::MetadataInterceptor;
 use banhammer_grpc::{
-    grpc::{validation_control_client::ValidationControlClient, BanType},
-    BanTypesEnum,
+    banlist::BanListFormat, grpc::validation_control_client::ValidationControlClient,
 };
 use clap::Parser;
 use dotenv::dotenv;
+use tokio::net::UnixStream;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint, Identity, Uri};
+use tower::service_fn;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -18,8 +23,61 @@ struct Cli {
     #[arg(long, short)]
     /// Save the modifications to config files
     pub save: bool,
+    #[arg(long, global = true)]
+    /// Name recorded in the ban history, defaults to BANHAMMER_AUTHOR or USER
+    pub author: Option<String>,
+    #[arg(long, global = true, env = "BANHAMMER_TOKEN", hide_env_values = true)]
+    /// Bearer token of the moderator credential
+    pub token: Option<String>,
+    #[arg(long, global = true, env = "BANHAMMER_CERT", requires = "key")]
+    /// PEM client certificate of the moderator credential, connects over TLS
+    pub cert: Option<PathBuf>,
+    #[arg(long, global = true, env = "BANHAMMER_KEY", requires = "cert")]
+    /// PEM private key of the client certificate
+    pub key: Option<PathBuf>,
+    #[arg(long, global = true, env = "BANHAMMER_CA")]
+    /// PEM CA used to verify the service certificate, connects over TLS
+    pub ca: Option<PathBuf>,
+    #[arg(long, global = true, env = "BANHAMMER_TLS_DOMAIN")]
+    /// Name expected in the service certificate, defaults to the address host
+    pub tls_domain: Option<String>,
 }
 
+impl Cli {
+    /// Connects to the core service, over TLS when a certificate is provided.
+    /// Addresses starting with `unix:` are Unix socket paths.
+    async fn connect(&self, address: &str) -> Result<Channel, Box<dyn Error>> {
+        if let Some(path) = address.strip_prefix("unix:") {
+            let path = path.to_string();
+            // The URI is required but unused, the connector dials the socket
+            let channel = Endpoint::from_static("http://localhost")
+                .connect_with_connector(service_fn(move |_: Uri| UnixStream::connect(path.clone())))
+                .await?;
+            return Ok(channel);
+        }
+
+        if self.cert.is_none() && self.ca.is_none() && self.tls_domain.is_none() {
+            let endpoint = Endpoint::from_shared(format!("http://{}", address))?;
+            return Ok(endpoint.connect().await?);
+        }
+
+        let mut tls = ClientTlsConfig::new();
+        if let Some(domain) = &self.tls_domain {
+            tls = tls.domain_name(domain);
+        }
+        if let Some(ca) = &self.ca {
+            tls = tls.ca_certificate(Certificate::from_pem(fs::read(ca)?));
+        }
+        if let (Some(cert), Some(key)) = (&self.cert, &self.key) {
+            tls = tls.identity(Identity::from_pem(fs::read(cert)?, fs::read(key)?));
+        }
+
+        let endpoint = Endpoint::from_shared(format!("https://{}", address))?.tls_config(tls)?;
+        Ok(endpoint.connect().await?)
+    }
+}
+
+#[allow(dead_code)]
 #[derive(Debug, Default)]
 pub struct CliOptions {
     save: bool,
@@ -42,30 +100,221 @@ pub enum Subcommands {
         index: i32,
         ban_type: BanTypeOptionEnum,
     },
+    #[clap(
+        about = "Converts a ban list file to another format (yaml, json, toml, jsonl)",
+        long_about = "Converts a ban list file to another format (yaml, json, toml, jsonl). \
+        Formats are guessed from the file extensions when not provided."
+    )]
+    Convert {
+        /// Ban list file to read
+        input: PathBuf,
+        /// Ban list file to write
+        output: PathBuf,
+        /// Format of the input file
+        #[arg(long)]
+        from: Option<BanListFormat>,
+        /// Format of the output file
+        #[arg(long)]
+        to: Option<BanListFormat>,
+    },
+    #[clap(about = "Exports bans as a signed NIP-51 list event")]
+    ExportNip51 {
+        /// List kind, 10000 (mute list) or 30000 (list set)
+        #[arg(long, default_value_t = 10000)]
+        kind: u64,
+        /// List identifier (`d` tag), required for kind 30000
+        #[arg(long)]
+        identifier: Option<String>,
+        /// File to write the event to, printed when omitted
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    #[clap(about = "Imports bans from a signed NIP-51 list event")]
+    ImportNip51 {
+        /// File containing the event JSON
+        file: PathBuf,
+    },
+    #[clap(about = "Shows the revisions of the ban set")]
+    History {
+        /// Only shows the latest revisions
+        #[arg(long, short)]
+        limit: Option<u32>,
+    },
+    #[clap(
+        about = "Shows the bans added and removed between two revisions",
+        long_about = "Shows the bans added and removed between two revisions. \
+        When only one revision is given, shows the changes it introduced."
+    )]
+    Diff {
+        from: u64,
+        to: Option<u64>,
+    },
+    #[clap(
+        about = "Restores the bans of a previous revision",
+        long_about = "Restores the bans of a previous revision. The rollback is \
+        recorded as a new revision, bans from read-only sources are kept."
+    )]
+    Rollback {
+        revision: u64,
+    },
+    #[clap(
+        about = "Removes the bans without hits for a number of days",
+        long_about = "Removes the bans without hits for a number of days. Bans that \
+        never matched are kept until they are that old. Bans from read-only \
+        sources are never pruned."
+    )]
+    Prune {
+        days: u32,
+        /// Only lists the bans that would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+    #[clap(
+        about = "Shows the service statistics",
+        long_about = "Shows the service statistics: uptime, rules loaded, recent \
+        decisions, and the rules, pubkeys and IPs matched the most."
+    )]
+    Stats {
+        /// Refreshes the statistics until interrupted
+        #[arg(long, short)]
+        watch: bool,
+        /// Seconds between two refreshes
+        #[arg(long, default_value_t = 2)]
+        interval: u64,
+        /// Length of the top lists
+        #[arg(long, default_value_t = 10)]
+        top: u32,
+    },
+    #[clap(
+        about = "Follows the decisions of the authorization service",
+        long_about = "Follows the decisions of the authorization service as they \
+        are taken. Filters can be combined, every decision is shown when none \
+        is provided. The stream is closed by the service if decisions are not \
+        read fast enough."
+    )]
+    Tail {
+        /// Only shows permitted or denied events
+        #[arg(long)]
+        decision: Option<DecisionOptionEnum>,
+        /// Only shows events rejected by a ban of this type
+        #[arg(long, value_enum)]
+        ban_type: Option<BanTypeOptionEnum>,
+        /// Only shows events of this author, npub or hex encoded
+        #[arg(long)]
+        pubkey: Option<String>,
+        /// Only shows events sent from this IP
+        #[arg(long)]
+        ip: Option<String>,
+        /// Only shows events of this kind
+        #[arg(long)]
+        kind: Option<u64>,
+    },
+    #[clap(
+        about = "Searches the stored rejections",
+        long_about = "Searches the rejections stored by the service, the latest \
+        first. Dates are UTC, either YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS."
+    )]
+    Rejections {
+        /// Only shows rejections of this author, npub or hex encoded
+        #[arg(long)]
+        pubkey: Option<String>,
+        /// Only shows rejections of this event id
+        #[arg(long)]
+        event: Option<String>,
+        /// Only shows rejections of events sent from this IP
+        #[arg(long)]
+        ip: Option<String>,
+        /// Only shows rejections from this date
+        #[arg(long)]
+        since: Option<String>,
+        /// Only shows rejections until this date
+        #[arg(long)]
+        until: Option<String>,
+        /// Only shows the latest rejections, defaults to 100
+        #[arg(long, short)]
+        limit: Option<u32>,
+        /// Also shows the rejected events, when stored
+        #[arg(long, short)]
+        verbose: bool,
+    },
+    #[clap(
+        about = "Shows the moderation actions recorded in the audit log",
+        long_about = "Shows the moderation actions recorded in the audit log, \
+        and whether the hash chain is intact. Dates are UTC, either \
+        YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS."
+    )]
+    Audit {
+        /// Only shows actions from this date
+        #[arg(long)]
+        since: Option<String>,
+        /// Only shows actions until this date
+        #[arg(long)]
+        until: Option<String>,
+        /// Only shows actions of this actor
+        #[arg(long)]
+        actor: Option<String>,
+        /// Only shows the latest actions
+        #[arg(long, short)]
+        limit: Option<u32>,
+        /// Also shows the request payloads and hashes
+        #[arg(long, short)]
+        verbose: bool,
+    },
+    #[clap(
+        about = "Starts or lifts a lockdown",
+        long_about = "Starts or lifts a lockdown. During a lockdown, the checks \
+        of the authorization service are tightened, such as the proof of work \
+        required."
+    )]
+    Lockdown {
+        state: LockdownOptionEnum,
+    },
 }
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
 
-    let grpc_address = env::var("GRPC_RELAY_ADDRESS").unwrap_or("[::1]:50051".to_string());
-    let grpc_full_address = format!("{}{}", "http://", grpc_address);
+    // Get CLI arguments and parameters
+    let cli = Cli::parse();
+
+    // Offline commands do not need the core service
+    if let Subcommands::Convert {
+        input,
+        output,
+        from,
+        to,
+    } = &cli.subcommand
+    {
+        match ConvertHandler::handle(input, output, *from, *to) {
+            true => exit(0),
+            false => exit(1),
+        }
+    }
+
+    let grpc_address = env::var("ADMIN_ADDRESS")
+        .or(env::var("GRPC_RELAY_ADDRESS"))
+        .unwrap_or("[::1]:50051".to_string());
 
     // Creates the gRPC client
-    let client = match ValidationControlClient::connect(grpc_full_address).await {
+    let channel = match cli.connect(&grpc_address).await {
         Ok(c) => c,
         Err(e) => {
             log::error!("Could not connect to core service. Are you sure it is up ?");
             panic!("{}", e);
         }
     };
-    // Get CLI arguments and parameters
-    let cli = Cli::parse();
 
-    let opts = CliOptions {
-        save: cli.save,
-        ..Default::default()
-    };
+    let author = cli
+        .author
+        .clone()
+        .or(env::var("BANHAMMER_AUTHOR").ok())
+        .or(env::var("USER").ok());
+    let client = ValidationControlClient::with_interceptor(
+        channel,
+        MetadataInterceptor::new(author, cli.token.clone()),
+    );
+    let opts = CliOptions { save: cli.save };
     let mut handler = CliHandler { client };
     handler.dispatcher(cli.subcommand, opts).await;
 
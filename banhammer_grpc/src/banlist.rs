@@ -0,0 +1,536 @@
+use std::{fmt, fs, path::Path, str::FromStr};
+
+use chrono::Utc;
+use num_traits::FromPrimitive;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    grpc::{AddBanRequest, BanItem},
+    BanTypesEnum,
+};
+
+/// A single ban rule, as stored in a ban list file.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Ban {
+    pub content: String,
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    pub ban_type: BanTypesEnum,
+    /// Where the ban was loaded from. Set at runtime, never serialized.
+    #[serde(skip)]
+    pub source: Option<String>,
+}
+
+impl Ban {
+    /// Two bans are duplicates when they apply the same rule,
+    /// whatever their reason, date or source.
+    pub fn is_duplicate_of(&self, other: &Ban) -> bool {
+        self.ban_type == other.ban_type
+            && self.regex == other.regex
+            && self.content == other.content
+    }
+}
+
+// Unknown ban types are refused rather than guessed, clients built
+// against another numbering would otherwise create the wrong bans.
+impl TryFrom<AddBanRequest> for Ban {
+    type Error = String;
+
+    fn try_from(value: AddBanRequest) -> Result<Self, Self::Error> {
+        let ban_type = BanTypesEnum::from_i32(value.ban_type)
+            .ok_or_else(|| format!("unknown ban type: {}", value.ban_type))?;
+
+        Ok(Self {
+            content: value.content,
+            regex: value.regex,
+            reason: value.reason,
+            date: Some(Utc::now().format("%Y-%m-%dT%H:%M:%S").to_string()),
+            ban_type,
+            source: None,
+        })
+    }
+}
+
+impl From<&Ban> for BanItem {
+    fn from(value: &Ban) -> Self {
+        Self {
+            content: value.content.clone(),
+            regex: value.regex,
+            reason: value.reason.clone(),
+            ban_type: value.ban_type as i32,
+            source: value.source.clone(),
+            hits: 0,
+            first_hit: None,
+            last_hit: None,
+            expires: None,
+        }
+    }
+}
+
+/// Supported serialization formats for ban lists.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BanListFormat {
+    Yaml,
+    Json,
+    Toml,
+    JsonLines,
+}
+
+impl BanListFormat {
+    /// Guesses the format from the file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+
+        match extension.as_str() {
+            "yaml" | "yml" => Some(Self::Yaml),
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            "jsonl" | "ndjson" => Some(Self::JsonLines),
+            _ => None,
+        }
+    }
+
+    /// Guesses the format by sniffing the file content.
+    /// Falls back on YAML, which was historically the only supported format.
+    pub fn detect(content: &str) -> Self {
+        let lines: Vec<&str> = content
+            .lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .collect();
+
+        let first = match lines.first() {
+            Some(line) => *line,
+            None => return Self::Yaml,
+        };
+
+        if first.starts_with('[') && !Self::is_toml_header(first) {
+            return Self::Json;
+        }
+
+        if first.starts_with('{') {
+            let is_json_lines =
+                lines.len() > 1 && lines.iter().all(|l| l.starts_with('{') && l.ends_with('}'));
+
+            return match is_json_lines {
+                true => Self::JsonLines,
+                false => Self::Json,
+            };
+        }
+
+        let is_toml = lines.iter().filter(|l| !l.starts_with('#')).any(|l| {
+            Self::is_toml_header(l)
+                || l.split_once('=')
+                    .is_some_and(|(key, _)| Self::is_toml_key(key.trim()))
+        });
+
+        match is_toml {
+            true => Self::Toml,
+            false => Self::Yaml,
+        }
+    }
+
+    // Matches `[table]` and `[[array]]` headers.
+    fn is_toml_header(line: &str) -> bool {
+        let name = line.trim_start_matches('[').trim_end_matches(']');
+        line.starts_with('[') && line.ends_with(']') && Self::is_toml_key(name)
+    }
+
+    fn is_toml_key(key: &str) -> bool {
+        !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+    }
+}
+
+impl FromStr for BanListFormat {
+    type Err = BanListError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "yaml" | "yml" => Ok(Self::Yaml),
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            "jsonl" | "ndjson" | "json-lines" => Ok(Self::JsonLines),
+            _ => Err(BanListError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for BanListFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let variant = match self {
+            BanListFormat::Yaml => "yaml",
+            BanListFormat::Json => "json",
+            BanListFormat::Toml => "toml",
+            BanListFormat::JsonLines => "jsonl",
+        };
+
+        write!(f, "{}", variant)
+    }
+}
+
+#[derive(Debug)]
+pub enum BanListError {
+    Io(std::io::Error),
+    Parse(BanListFormat, String),
+    Serialize(BanListFormat, String),
+    UnknownFormat(String),
+}
+
+impl fmt::Display for BanListError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BanListError::Io(e) => write!(f, "{}", e),
+            BanListError::Parse(format, e) => {
+                write!(f, "could not parse {} ban list: {}", format, e)
+            }
+            BanListError::Serialize(format, e) => {
+                write!(f, "could not serialize {} ban list: {}", format, e)
+            }
+            BanListError::UnknownFormat(format) => write!(f, "unknown ban list format: {}", format),
+        }
+    }
+}
+
+impl std::error::Error for BanListError {}
+
+impl From<std::io::Error> for BanListError {
+    fn from(value: std::io::Error) -> Self {
+        BanListError::Io(value)
+    }
+}
+
+/// A ban list can either be a bare sequence of bans or a document
+/// holding them under a `bans` key. TOML only supports the latter.
+/// Documents may also `include` other ban list files.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BanListDocument {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub bans: Vec<Ban>,
+}
+
+impl From<Vec<Ban>> for BanListDocument {
+    fn from(bans: Vec<Ban>) -> Self {
+        Self {
+            include: Vec::new(),
+            bans,
+        }
+    }
+}
+
+/// Parses a ban list in the given format.
+pub fn parse(content: &str, format: BanListFormat) -> Result<Vec<Ban>, BanListError> {
+    parse_document(content, format).map(|document| document.bans)
+}
+
+/// Parses a ban list in the given format, keeping its directives.
+pub fn parse_document(
+    content: &str,
+    format: BanListFormat,
+) -> Result<BanListDocument, BanListError> {
+    let parse_error = |e: &dyn fmt::Display| BanListError::Parse(format, e.to_string());
+
+    match format {
+        BanListFormat::Yaml => {
+            let value: serde_yaml::Value =
+                serde_yaml::from_str(content).map_err(|e| parse_error(&e))?;
+            match value {
+                serde_yaml::Value::Null => Ok(BanListDocument::default()),
+                serde_yaml::Value::Sequence(_) => serde_yaml::from_value::<Vec<Ban>>(value)
+                    .map(BanListDocument::from)
+                    .map_err(|e| parse_error(&e)),
+                _ => serde_yaml::from_value(value).map_err(|e| parse_error(&e)),
+            }
+        }
+        BanListFormat::Json => {
+            let value: serde_json::Value =
+                serde_json::from_str(content).map_err(|e| parse_error(&e))?;
+            match value.is_array() {
+                true => serde_json::from_value::<Vec<Ban>>(value)
+                    .map(BanListDocument::from)
+                    .map_err(|e| parse_error(&e)),
+                false => serde_json::from_value(value).map_err(|e| parse_error(&e)),
+            }
+        }
+        BanListFormat::Toml => toml::from_str(content).map_err(|e| parse_error(&e)),
+        BanListFormat::JsonLines => {
+            let mut bans = Vec::new();
+            for (index, line) in content.lines().enumerate() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let ban: Ban = serde_json::from_str(line)
+                    .map_err(|e| parse_error(&format!("line {}: {}", index + 1, e)))?;
+                bans.push(ban);
+            }
+            Ok(BanListDocument::from(bans))
+        }
+    }
+}
+
+/// Serializes a ban list in the given format.
+pub fn serialize(bans: &[Ban], format: BanListFormat) -> Result<String, BanListError> {
+    let serialize_error = |e: &dyn fmt::Display| BanListError::Serialize(format, e.to_string());
+
+    match format {
+        BanListFormat::Yaml => serde_yaml::to_string(bans).map_err(|e| serialize_error(&e)),
+        BanListFormat::Json => serde_json::to_string_pretty(bans)
+            .map(|s| s + "\n")
+            .map_err(|e| serialize_error(&e)),
+        BanListFormat::Toml => {
+            toml::to_string(&BanListDocument::from(bans.to_vec())).map_err(|e| serialize_error(&e))
+        }
+        BanListFormat::JsonLines => {
+            let mut output = String::new();
+            for ban in bans {
+                output += &serde_json::to_string(ban).map_err(|e| serialize_error(&e))?;
+                output += "\n";
+            }
+            Ok(output)
+        }
+    }
+}
+
+/// Serializes a ban list in the given format, keeping its directives.
+/// JSON Lines only holds bans, so documents with includes can't be written
+/// in that format.
+pub fn serialize_document(
+    document: &BanListDocument,
+    format: BanListFormat,
+) -> Result<String, BanListError> {
+    let serialize_error = |e: &dyn fmt::Display| BanListError::Serialize(format, e.to_string());
+
+    if document.include.is_empty() {
+        return serialize(&document.bans, format);
+    }
+
+    match format {
+        BanListFormat::Yaml => serde_yaml::to_string(document).map_err(|e| serialize_error(&e)),
+        BanListFormat::Json => serde_json::to_string_pretty(document)
+            .map(|s| s + "\n")
+            .map_err(|e| serialize_error(&e)),
+        BanListFormat::Toml => toml::to_string(document).map_err(|e| serialize_error(&e)),
+        BanListFormat::JsonLines => Err(serialize_error(&"include directives are not supported")),
+    }
+}
+
+/// Reads a ban list file. The format is guessed from the file extension
+/// or, when it is not conclusive, from the file content.
+pub fn read(path: &Path) -> Result<Vec<Ban>, BanListError> {
+    read_document(path).map(|document| document.bans)
+}
+
+/// Reads a ban list file, keeping its directives.
+pub fn read_document(path: &Path) -> Result<BanListDocument, BanListError> {
+    let content = fs::read_to_string(path)?;
+    let format = BanListFormat::from_path(path).unwrap_or_else(|| BanListFormat::detect(&content));
+
+    parse_document(&content, format)
+}
+
+/// Writes a ban list file in the given format,
+/// or in the format matching the file extension if none is provided.
+pub fn write(
+    path: &Path,
+    bans: &[Ban],
+    format: Option<BanListFormat>,
+) -> Result<BanListFormat, BanListError> {
+    write_document(path, &BanListDocument::from(bans.to_vec()), format)
+}
+
+/// Writes a ban list file, keeping its directives. See `write`.
+pub fn write_document(
+    path: &Path,
+    document: &BanListDocument,
+    format: Option<BanListFormat>,
+) -> Result<BanListFormat, BanListError> {
+    let format = format
+        .or_else(|| BanListFormat::from_path(path))
+        .unwrap_or(BanListFormat::Yaml);
+
+    fs::write(path, serialize_document(document, format)?)?;
+
+    Ok(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<Ban> {
+        vec![
+            Ban {
+                content: "hello".to_string(),
+                regex: false,
+                reason: Some("spam".to_string()),
+                date: Some("2023-01-01T00:01:23".to_string()),
+                ban_type: BanTypesEnum::CONTENT,
+                source: None,
+            },
+            Ban {
+                content: "^w[o0]rld$".to_string(),
+                regex: true,
+                reason: None,
+                date: None,
+                ban_type: BanTypesEnum::TAG,
+                source: None,
+            },
+            Ban {
+                content: "192.168.0.255".to_string(),
+                regex: false,
+                reason: None,
+                date: Some("2023-01-01T00:01:23".to_string()),
+                ban_type: BanTypesEnum::IP,
+                source: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let formats = [
+            BanListFormat::Yaml,
+            BanListFormat::Json,
+            BanListFormat::Toml,
+            BanListFormat::JsonLines,
+        ];
+
+        for format in formats {
+            let serialized = serialize(&sample(), format).unwrap();
+            assert_eq!(BanListFormat::detect(&serialized), format);
+            assert_eq!(parse(&serialized, format).unwrap(), sample());
+
+            for target in formats {
+                let converted = serialize(&parse(&serialized, format).unwrap(), target).unwrap();
+                assert_eq!(parse(&converted, target).unwrap(), sample());
+            }
+        }
+    }
+
+    #[test]
+    fn test_symbolic_and_numeric_ban_types() {
+        let yaml = "- content: hello\n  ban_type: content\n- content: world\n  ban_type: 1\n";
+        let bans = parse(yaml, BanListFormat::Yaml).unwrap();
+        assert_eq!(bans[0].ban_type, BanTypesEnum::CONTENT);
+        assert_eq!(bans[1].ban_type, BanTypesEnum::TAG);
+
+        let toml = "[[bans]]\ncontent = \"hello\"\nban_type = \"USER\"\n";
+        let bans = parse(toml, BanListFormat::Toml).unwrap();
+        assert_eq!(bans[0].ban_type, BanTypesEnum::USER);
+
+        let json = r#"{"bans": [{"content": "1.2.3.4", "ban_type": "Ip"}]}"#;
+        let bans = parse(json, BanListFormat::Json).unwrap();
+        assert_eq!(bans[0].ban_type, BanTypesEnum::IP);
+
+        let invalid = "- content: hello\n  ban_type: nope\n";
+        assert!(parse(invalid, BanListFormat::Yaml).is_err());
+    }
+
+    #[test]
+    fn test_format_detection() {
+        assert_eq!(
+            BanListFormat::from_path(Path::new("bans.yml")),
+            Some(BanListFormat::Yaml)
+        );
+        assert_eq!(
+            BanListFormat::from_path(Path::new("bans.ndjson")),
+            Some(BanListFormat::JsonLines)
+        );
+        assert_eq!(BanListFormat::from_path(Path::new("bans")), None);
+
+        assert_eq!(BanListFormat::detect(""), BanListFormat::Yaml);
+        assert_eq!(
+            BanListFormat::detect("- content: hello\n  ban_type: CONTENT\n"),
+            BanListFormat::Yaml
+        );
+        assert_eq!(
+            BanListFormat::detect("[{\"content\": \"hello\", \"ban_type\": 0}]"),
+            BanListFormat::Json
+        );
+        assert_eq!(
+            BanListFormat::detect(
+                "{\"content\": \"a\", \"ban_type\": 0}\n{\"content\": \"b\", \"ban_type\": 0}\n"
+            ),
+            BanListFormat::JsonLines
+        );
+        assert_eq!(
+            BanListFormat::detect("# comment\n[[bans]]\ncontent = \"hello\"\n"),
+            BanListFormat::Toml
+        );
+    }
+
+    #[test]
+    fn test_include_directive() {
+        let yaml = "include:\n  - spam.yaml\nbans:\n  - content: hello\n    ban_type: CONTENT\n";
+        let document = parse_document(yaml, BanListFormat::Yaml).unwrap();
+        assert_eq!(document.include, vec!["spam.yaml".to_string()]);
+        assert_eq!(document.bans.len(), 1);
+
+        let toml = "include = [\"ips.json\"]\n";
+        let document = parse_document(toml, BanListFormat::Toml).unwrap();
+        assert_eq!(document.include, vec!["ips.json".to_string()]);
+        assert!(document.bans.is_empty());
+    }
+
+    #[test]
+    fn test_json_fixture() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../banhammer/fixtures/bans.json");
+        let bans = read(&path).unwrap();
+
+        assert_eq!(bans.len(), 2);
+        assert_eq!(bans[0].ban_type, BanTypesEnum::CONTENT);
+        assert_eq!(bans[1].ban_type, BanTypesEnum::TAG);
+    }
+
+    #[test]
+    fn test_include_round_trip() {
+        let document = BanListDocument {
+            include: vec!["spam.yaml".to_string()],
+            bans: sample(),
+        };
+
+        for format in [
+            BanListFormat::Yaml,
+            BanListFormat::Json,
+            BanListFormat::Toml,
+        ] {
+            let content = serialize_document(&document, format).unwrap();
+            let parsed = parse_document(&content, format).unwrap();
+            assert_eq!(parsed.include, document.include);
+            assert_eq!(parsed.bans, document.bans);
+        }
+        assert!(serialize_document(&document, BanListFormat::JsonLines).is_err());
+
+        // Without includes, bans are written as a bare sequence
+        let bans = BanListDocument::from(sample());
+        assert_eq!(
+            serialize_document(&bans, BanListFormat::Yaml).unwrap(),
+            serialize(&sample(), BanListFormat::Yaml).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_add_ban_request() {
+        let request = |ban_type| AddBanRequest {
+            ban_type,
+            content: "curl".to_string(),
+            ..Default::default()
+        };
+
+        let ban = Ban::try_from(request(BanTypesEnum::USER_AGENT as i32)).unwrap();
+        assert_eq!(ban.ban_type, BanTypesEnum::USER_AGENT);
+        assert_eq!(
+            Ban::try_from(request(42)),
+            Err("unknown ban type: 42".to_string())
+        );
+        assert!(Ban::try_from(request(-1)).is_err());
+    }
+}
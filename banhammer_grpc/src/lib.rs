@@ -1,14 +1,21 @@
-use serde::{Deserialize, Serialize};
-use std::fmt;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use std::{fmt, str::FromStr};
 
 use num_derive::*;
+use num_traits::FromPrimitive;
+
+pub mod banlist;
 
 pub mod grpc {
     include!("nauthz.rs");
     include!("validationcontrol.rs");
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, FromPrimitive)]
+/// Metadata key carrying the name recorded as the author of admin changes.
+pub const AUTHOR_METADATA: &str = "x-banhammer-author";
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Hash, FromPrimitive)]
 pub enum BanTypesEnum {
     CONTENT = 0,
     TAG = 1,
@@ -16,6 +23,8 @@ pub enum BanTypesEnum {
     IP = 3,
     NIP05 = 4,
     LUD16 = 5,
+    ORIGIN = 6,
+    USER_AGENT = 7,
 }
 
 impl fmt::Display for BanTypesEnum {
@@ -27,8 +36,63 @@ impl fmt::Display for BanTypesEnum {
             BanTypesEnum::IP => "ip",
             BanTypesEnum::NIP05 => "nip05",
             BanTypesEnum::LUD16 => "lud16",
+            BanTypesEnum::ORIGIN => "origin",
+            BanTypesEnum::USER_AGENT => "user_agent",
         };
 
         write!(f, "{}", variant)
     }
 }
+
+impl FromStr for BanTypesEnum {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "content" => Ok(BanTypesEnum::CONTENT),
+            "tag" => Ok(BanTypesEnum::TAG),
+            "user" => Ok(BanTypesEnum::USER),
+            "ip" => Ok(BanTypesEnum::IP),
+            "nip05" => Ok(BanTypesEnum::NIP05),
+            "lud16" => Ok(BanTypesEnum::LUD16),
+            "origin" => Ok(BanTypesEnum::ORIGIN),
+            "user_agent" | "user-agent" => Ok(BanTypesEnum::USER_AGENT),
+            _ => Err(format!("unknown ban type: {}", s)),
+        }
+    }
+}
+
+// Ban types are accepted either by name, case insensitive,
+// or by their numeric value.
+impl<'de> Deserialize<'de> for BanTypesEnum {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct BanTypeVisitor;
+
+        impl<'de> de::Visitor<'de> for BanTypeVisitor {
+            type Value = BanTypesEnum;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a ban type name or number")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                BanTypesEnum::from_str(v).map_err(E::custom)
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                BanTypesEnum::from_u64(v)
+                    .ok_or_else(|| E::custom(format!("unknown ban type: {}", v)))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                BanTypesEnum::from_i64(v)
+                    .ok_or_else(|| E::custom(format!("unknown ban type: {}", v)))
+            }
+        }
+
+        deserializer.deserialize_any(BanTypeVisitor)
+    }
+}
@@ -18,6 +18,18 @@ pub struct BanItem {
     pub reason: ::core::option::Option<::prost::alloc::string::String>,
     #[prost(enumeration = "BanType", tag = "4")]
     pub ban_type: i32,
+    #[prost(string, optional, tag = "5")]
+    pub source: ::core::option::Option<::prost::alloc::string::String>,
+    /// Events matched by the rule
+    #[prost(uint64, tag = "6")]
+    pub hits: u64,
+    #[prost(string, optional, tag = "7")]
+    pub first_hit: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "8")]
+    pub last_hit: ::core::option::Option<::prost::alloc::string::String>,
+    /// Temporary bans are removed after this date
+    #[prost(string, optional, tag = "9")]
+    pub expires: ::core::option::Option<::prost::alloc::string::String>,
 }
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -62,13 +74,354 @@ pub struct RemoveBanResponse {
     #[prost(bool, tag = "1")]
     pub result: bool,
 }
+/// Exports USER, TAG and CONTENT bans as a NIP-51 list event
+/// signed with the moderator key.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportNip51Request {
+    /// 10000 (mute list) or 30000 (list set)
+    #[prost(uint64, tag = "1")]
+    pub kind: u64,
+    /// `d` tag, required for list sets
+    #[prost(string, optional, tag = "2")]
+    pub identifier: ::core::option::Option<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExportNip51Response {
+    /// The signed event, JSON encoded
+    #[prost(string, tag = "1")]
+    pub event: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImportNip51Request {
+    /// A signed NIP-51 list event, JSON encoded
+    #[prost(string, tag = "1")]
+    pub event: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImportNip51Response {
+    #[prost(uint32, tag = "1")]
+    pub imported: u32,
+    #[prost(uint32, tag = "2")]
+    pub skipped: u32,
+}
+/// A numbered change of the ban set
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Revision {
+    #[prost(uint64, tag = "1")]
+    pub number: u64,
+    #[prost(string, tag = "2")]
+    pub author: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub timestamp: ::prost::alloc::string::String,
+    #[prost(uint32, tag = "4")]
+    pub added: u32,
+    #[prost(uint32, tag = "5")]
+    pub removed: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListRevisionsRequest {
+    /// Only returns the latest revisions
+    #[prost(uint32, optional, tag = "1")]
+    pub limit: ::core::option::Option<u32>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListRevisionsResponse {
+    /// Oldest first
+    #[prost(message, repeated, tag = "1")]
+    pub revisions: ::prost::alloc::vec::Vec<Revision>,
+}
+/// Revision 0 is the empty ban set
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DiffRevisionsRequest {
+    #[prost(uint64, tag = "1")]
+    pub from: u64,
+    #[prost(uint64, tag = "2")]
+    pub to: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DiffRevisionsResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub added: ::prost::alloc::vec::Vec<BanItem>,
+    #[prost(message, repeated, tag = "2")]
+    pub removed: ::prost::alloc::vec::Vec<BanItem>,
+}
+/// Restores the bans of a revision, bans from read-only sources are kept.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RollbackRequest {
+    #[prost(uint64, tag = "1")]
+    pub revision: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RollbackResponse {
+    /// The revision recorded by the rollback, absent if nothing changed
+    #[prost(uint64, optional, tag = "1")]
+    pub revision: ::core::option::Option<u64>,
+}
+/// A recorded moderation action
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AuditEntry {
+    #[prost(uint64, tag = "1")]
+    pub sequence: u64,
+    #[prost(string, tag = "2")]
+    pub timestamp: ::prost::alloc::string::String,
+    #[prost(string, tag = "3")]
+    pub actor: ::prost::alloc::string::String,
+    #[prost(string, optional, tag = "4")]
+    pub address: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, tag = "5")]
+    pub action: ::prost::alloc::string::String,
+    #[prost(string, tag = "6")]
+    pub payload: ::prost::alloc::string::String,
+    #[prost(string, tag = "7")]
+    pub result: ::prost::alloc::string::String,
+    #[prost(string, tag = "8")]
+    pub prev_hash: ::prost::alloc::string::String,
+    #[prost(string, tag = "9")]
+    pub hash: ::prost::alloc::string::String,
+}
+/// Dates are UTC, either YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AuditLogRequest {
+    #[prost(string, optional, tag = "1")]
+    pub since: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "2")]
+    pub until: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "3")]
+    pub actor: ::core::option::Option<::prost::alloc::string::String>,
+    /// Only returns the latest matching entries
+    #[prost(uint32, optional, tag = "4")]
+    pub limit: ::core::option::Option<u32>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AuditLogResponse {
+    /// Oldest first
+    #[prost(message, repeated, tag = "1")]
+    pub entries: ::prost::alloc::vec::Vec<AuditEntry>,
+    /// Whether the whole hash chain is valid
+    #[prost(bool, tag = "2")]
+    pub verified: bool,
+    /// Why the chain is not valid
+    #[prost(string, optional, tag = "3")]
+    pub error: ::core::option::Option<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PruneBansRequest {
+    /// Rules without hits for this many days are pruned
+    #[prost(uint32, tag = "1")]
+    pub days: u32,
+    /// Only lists the rules that would be pruned
+    #[prost(bool, tag = "2")]
+    pub dry_run: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PruneBansResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub bans: ::prost::alloc::vec::Vec<BanItem>,
+    /// The revision recorded by the prune, absent on dry runs or if nothing changed
+    #[prost(uint64, optional, tag = "2")]
+    pub revision: ::core::option::Option<u64>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StatsRequest {
+    /// Length of the top lists, defaults to 10
+    #[prost(uint32, tag = "1")]
+    pub top: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RuleCount {
+    #[prost(enumeration = "BanType", tag = "1")]
+    pub ban_type: i32,
+    /// Empty for bans added through the admin API
+    #[prost(string, tag = "2")]
+    pub source: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "3")]
+    pub rules: u64,
+}
+/// Decisions over a sliding window, such as `1m`, `1h` or `24h`
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DecisionWindow {
+    #[prost(string, tag = "1")]
+    pub window: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub evaluated: u64,
+    #[prost(uint64, tag = "3")]
+    pub denied: u64,
+}
+/// A pubkey (npub) or IP and its denials over the last 24 hours
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Offender {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "2")]
+    pub denials: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StatsResponse {
+    #[prost(uint64, tag = "1")]
+    pub uptime_seconds: u64,
+    #[prost(string, tag = "2")]
+    pub version: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "3")]
+    pub rules: ::prost::alloc::vec::Vec<RuleCount>,
+    #[prost(message, repeated, tag = "4")]
+    pub windows: ::prost::alloc::vec::Vec<DecisionWindow>,
+    /// Rules with the most hits
+    #[prost(message, repeated, tag = "5")]
+    pub top_rules: ::prost::alloc::vec::Vec<BanItem>,
+    #[prost(message, repeated, tag = "6")]
+    pub top_pubkeys: ::prost::alloc::vec::Vec<Offender>,
+    #[prost(message, repeated, tag = "7")]
+    pub top_ips: ::prost::alloc::vec::Vec<Offender>,
+    /// `enforce` or `monitor`
+    #[prost(string, tag = "8")]
+    pub policy_mode: ::prost::alloc::string::String,
+    #[prost(bool, tag = "9")]
+    pub lockdown: bool,
+}
+/// Unset filters match every decision
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct StreamDecisionsRequest {
+    /// `permit` or `deny`
+    #[prost(string, optional, tag = "1")]
+    pub decision: ::core::option::Option<::prost::alloc::string::String>,
+    /// Events rejected by a ban of this type
+    #[prost(enumeration = "BanType", optional, tag = "2")]
+    pub ban_type: ::core::option::Option<i32>,
+    /// Events of this author, npub or hex encoded
+    #[prost(string, optional, tag = "3")]
+    pub pubkey: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "4")]
+    pub ip: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(uint64, optional, tag = "5")]
+    pub kind: ::core::option::Option<u64>,
+}
+/// A ban rejecting an event
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DecisionMatch {
+    #[prost(enumeration = "BanType", tag = "1")]
+    pub ban_type: i32,
+    /// Absent when the event is rejected without a matching ban,
+    /// such as an invalid pubkey
+    #[prost(string, optional, tag = "2")]
+    pub rule_id: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "3")]
+    pub content: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "4")]
+    pub reason: ::core::option::Option<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DecisionTrace {
+    #[prost(string, tag = "1")]
+    pub timestamp: ::prost::alloc::string::String,
+    /// Whether the event was denied, rejected events are permitted
+    /// in monitor mode
+    #[prost(bool, tag = "2")]
+    pub denied: bool,
+    #[prost(string, tag = "3")]
+    pub event_id: ::prost::alloc::string::String,
+    #[prost(string, tag = "4")]
+    pub pubkey: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "5")]
+    pub kind: u64,
+    #[prost(string, optional, tag = "6")]
+    pub ip: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(message, repeated, tag = "7")]
+    pub matches: ::prost::alloc::vec::Vec<DecisionMatch>,
+    /// Time taken to decide, in microseconds
+    #[prost(uint64, tag = "8")]
+    pub duration_us: u64,
+    /// Message sent to the relay, such as the rate limit reached
+    #[prost(string, optional, tag = "9")]
+    pub message: ::core::option::Option<::prost::alloc::string::String>,
+}
+/// Unset filters match every rejection. Dates are UTC, either
+/// YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS.
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryRejectionsRequest {
+    /// npub or hex encoded
+    #[prost(string, optional, tag = "1")]
+    pub pubkey: ::core::option::Option<::prost::alloc::string::String>,
+    /// Hex encoded
+    #[prost(string, optional, tag = "2")]
+    pub event_id: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "3")]
+    pub ip: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "4")]
+    pub since: ::core::option::Option<::prost::alloc::string::String>,
+    #[prost(string, optional, tag = "5")]
+    pub until: ::core::option::Option<::prost::alloc::string::String>,
+    /// Only returns the latest rejections, defaults to 100
+    #[prost(uint32, optional, tag = "6")]
+    pub limit: ::core::option::Option<u32>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RejectionRecord {
+    #[prost(message, optional, tag = "1")]
+    pub decision: ::core::option::Option<DecisionTrace>,
+    /// The event as NIP-01 JSON, when events are stored
+    #[prost(string, optional, tag = "2")]
+    pub event: ::core::option::Option<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryRejectionsResponse {
+    /// The latest first
+    #[prost(message, repeated, tag = "1")]
+    pub rejections: ::prost::alloc::vec::Vec<RejectionRecord>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SetLockdownRequest {
+    #[prost(bool, tag = "1")]
+    pub active: bool,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SetLockdownResponse {
+    /// Whether a lockdown was active before the request
+    #[prost(bool, tag = "1")]
+    pub was_active: bool,
+}
+/// Must be kept in sync with `BanTypesEnum`.
+/// The values used to be IP = 0, USER = 1, TAG = 2 and CONTENT = 3,
+/// clients generated from an older copy of this file must be upgraded.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum BanType {
-    Ip = 0,
-    User = 1,
-    Tag = 2,
-    Content = 3,
+    Content = 0,
+    Tag = 1,
+    User = 2,
+    Ip = 3,
+    Nip05 = 4,
+    Lud16 = 5,
+    Origin = 6,
+    UserAgent = 7,
 }
 impl BanType {
     /// String value of the enum field names used in the ProtoBuf definition.
@@ -77,19 +430,27 @@ impl BanType {
     /// (if the ProtoBuf definition does not change) and safe for programmatic use.
     pub fn as_str_name(&self) -> &'static str {
         match self {
-            BanType::Ip => "IP",
-            BanType::User => "USER",
-            BanType::Tag => "TAG",
             BanType::Content => "CONTENT",
+            BanType::Tag => "TAG",
+            BanType::User => "USER",
+            BanType::Ip => "IP",
+            BanType::Nip05 => "NIP05",
+            BanType::Lud16 => "LUD16",
+            BanType::Origin => "ORIGIN",
+            BanType::UserAgent => "USER_AGENT",
         }
     }
     /// Creates an enum from field names used in the ProtoBuf definition.
     pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
         match value {
-            "IP" => Some(Self::Ip),
-            "USER" => Some(Self::User),
-            "TAG" => Some(Self::Tag),
             "CONTENT" => Some(Self::Content),
+            "TAG" => Some(Self::Tag),
+            "USER" => Some(Self::User),
+            "IP" => Some(Self::Ip),
+            "NIP05" => Some(Self::Nip05),
+            "LUD16" => Some(Self::Lud16),
+            "ORIGIN" => Some(Self::Origin),
+            "USER_AGENT" => Some(Self::UserAgent),
             _ => None,
         }
     }
@@ -279,81 +640,465 @@ pub mod validation_control_client {
                 );
             self.inner.unary(req, path, codec).await
         }
-    }
-}
-/// Generated server implementations.
-pub mod validation_control_server {
-    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
-    use tonic::codegen::*;
-    /// Generated trait containing gRPC methods that should be implemented for use with ValidationControlServer.
-    #[async_trait]
-    pub trait ValidationControl: Send + Sync + 'static {
-        async fn state(
-            &self,
-            request: tonic::Request<super::StateRequest>,
-        ) -> std::result::Result<tonic::Response<super::StateResponse>, tonic::Status>;
-        async fn add_ban(
-            &self,
-            request: tonic::Request<super::AddBanRequest>,
-        ) -> std::result::Result<tonic::Response<super::AddBanResponse>, tonic::Status>;
-        async fn list_bans(
-            &self,
-            request: tonic::Request<super::ListBansRequest>,
+        pub async fn export_nip51(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ExportNip51Request>,
         ) -> std::result::Result<
-            tonic::Response<super::ListBansResponse>,
+            tonic::Response<super::ExportNip51Response>,
             tonic::Status,
-        >;
-        async fn remove_ban(
-            &self,
-            request: tonic::Request<super::RemoveBanRequest>,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/validationcontrol.ValidationControl/ExportNip51",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("validationcontrol.ValidationControl", "ExportNip51"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn import_nip51(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ImportNip51Request>,
         ) -> std::result::Result<
-            tonic::Response<super::RemoveBanResponse>,
+            tonic::Response<super::ImportNip51Response>,
             tonic::Status,
-        >;
-    }
-    #[derive(Debug)]
-    pub struct ValidationControlServer<T: ValidationControl> {
-        inner: _Inner<T>,
-        accept_compression_encodings: EnabledCompressionEncodings,
-        send_compression_encodings: EnabledCompressionEncodings,
-        max_decoding_message_size: Option<usize>,
-        max_encoding_message_size: Option<usize>,
-    }
-    struct _Inner<T>(Arc<T>);
-    impl<T: ValidationControl> ValidationControlServer<T> {
-        pub fn new(inner: T) -> Self {
-            Self::from_arc(Arc::new(inner))
-        }
-        pub fn from_arc(inner: Arc<T>) -> Self {
-            let inner = _Inner(inner);
-            Self {
-                inner,
-                accept_compression_encodings: Default::default(),
-                send_compression_encodings: Default::default(),
-                max_decoding_message_size: None,
-                max_encoding_message_size: None,
-            }
-        }
-        pub fn with_interceptor<F>(
-            inner: T,
-            interceptor: F,
-        ) -> InterceptedService<Self, F>
-        where
-            F: tonic::service::Interceptor,
-        {
-            InterceptedService::new(Self::new(inner), interceptor)
-        }
-        /// Enable decompressing requests with the given encoding.
-        #[must_use]
-        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.accept_compression_encodings.enable(encoding);
-            self
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/validationcontrol.ValidationControl/ImportNip51",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("validationcontrol.ValidationControl", "ImportNip51"),
+                );
+            self.inner.unary(req, path, codec).await
         }
-        /// Compress responses with the given encoding, if the client supports it.
-        #[must_use]
-        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
-            self.send_compression_encodings.enable(encoding);
-            self
+        pub async fn list_revisions(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListRevisionsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListRevisionsResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/validationcontrol.ValidationControl/ListRevisions",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "validationcontrol.ValidationControl",
+                        "ListRevisions",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn diff_revisions(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DiffRevisionsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::DiffRevisionsResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/validationcontrol.ValidationControl/DiffRevisions",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "validationcontrol.ValidationControl",
+                        "DiffRevisions",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn rollback(
+            &mut self,
+            request: impl tonic::IntoRequest<super::RollbackRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RollbackResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/validationcontrol.ValidationControl/Rollback",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("validationcontrol.ValidationControl", "Rollback"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn audit_log(
+            &mut self,
+            request: impl tonic::IntoRequest<super::AuditLogRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::AuditLogResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/validationcontrol.ValidationControl/AuditLog",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("validationcontrol.ValidationControl", "AuditLog"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn prune_bans(
+            &mut self,
+            request: impl tonic::IntoRequest<super::PruneBansRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::PruneBansResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/validationcontrol.ValidationControl/PruneBans",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("validationcontrol.ValidationControl", "PruneBans"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn stats(
+            &mut self,
+            request: impl tonic::IntoRequest<super::StatsRequest>,
+        ) -> std::result::Result<tonic::Response<super::StatsResponse>, tonic::Status> {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/validationcontrol.ValidationControl/Stats",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("validationcontrol.ValidationControl", "Stats"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn stream_decisions(
+            &mut self,
+            request: impl tonic::IntoRequest<super::StreamDecisionsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<tonic::codec::Streaming<super::DecisionTrace>>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/validationcontrol.ValidationControl/StreamDecisions",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "validationcontrol.ValidationControl",
+                        "StreamDecisions",
+                    ),
+                );
+            self.inner.server_streaming(req, path, codec).await
+        }
+        pub async fn query_rejections(
+            &mut self,
+            request: impl tonic::IntoRequest<super::QueryRejectionsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::QueryRejectionsResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/validationcontrol.ValidationControl/QueryRejections",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new(
+                        "validationcontrol.ValidationControl",
+                        "QueryRejections",
+                    ),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn set_lockdown(
+            &mut self,
+            request: impl tonic::IntoRequest<super::SetLockdownRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::SetLockdownResponse>,
+            tonic::Status,
+        > {
+            self.inner
+                .ready()
+                .await
+                .map_err(|e| {
+                    tonic::Status::new(
+                        tonic::Code::Unknown,
+                        format!("Service was not ready: {}", e.into()),
+                    )
+                })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static(
+                "/validationcontrol.ValidationControl/SetLockdown",
+            );
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(
+                    GrpcMethod::new("validationcontrol.ValidationControl", "SetLockdown"),
+                );
+            self.inner.unary(req, path, codec).await
+        }
+    }
+}
+/// Generated server implementations.
+pub mod validation_control_server {
+    #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
+    use tonic::codegen::*;
+    /// Generated trait containing gRPC methods that should be implemented for use with ValidationControlServer.
+    #[async_trait]
+    pub trait ValidationControl: Send + Sync + 'static {
+        async fn state(
+            &self,
+            request: tonic::Request<super::StateRequest>,
+        ) -> std::result::Result<tonic::Response<super::StateResponse>, tonic::Status>;
+        async fn add_ban(
+            &self,
+            request: tonic::Request<super::AddBanRequest>,
+        ) -> std::result::Result<tonic::Response<super::AddBanResponse>, tonic::Status>;
+        async fn list_bans(
+            &self,
+            request: tonic::Request<super::ListBansRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListBansResponse>,
+            tonic::Status,
+        >;
+        async fn remove_ban(
+            &self,
+            request: tonic::Request<super::RemoveBanRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RemoveBanResponse>,
+            tonic::Status,
+        >;
+        async fn export_nip51(
+            &self,
+            request: tonic::Request<super::ExportNip51Request>,
+        ) -> std::result::Result<
+            tonic::Response<super::ExportNip51Response>,
+            tonic::Status,
+        >;
+        async fn import_nip51(
+            &self,
+            request: tonic::Request<super::ImportNip51Request>,
+        ) -> std::result::Result<
+            tonic::Response<super::ImportNip51Response>,
+            tonic::Status,
+        >;
+        async fn list_revisions(
+            &self,
+            request: tonic::Request<super::ListRevisionsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::ListRevisionsResponse>,
+            tonic::Status,
+        >;
+        async fn diff_revisions(
+            &self,
+            request: tonic::Request<super::DiffRevisionsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::DiffRevisionsResponse>,
+            tonic::Status,
+        >;
+        async fn rollback(
+            &self,
+            request: tonic::Request<super::RollbackRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::RollbackResponse>,
+            tonic::Status,
+        >;
+        async fn audit_log(
+            &self,
+            request: tonic::Request<super::AuditLogRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::AuditLogResponse>,
+            tonic::Status,
+        >;
+        async fn prune_bans(
+            &self,
+            request: tonic::Request<super::PruneBansRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::PruneBansResponse>,
+            tonic::Status,
+        >;
+        async fn stats(
+            &self,
+            request: tonic::Request<super::StatsRequest>,
+        ) -> std::result::Result<tonic::Response<super::StatsResponse>, tonic::Status>;
+        /// Server streaming response type for the StreamDecisions method.
+        type StreamDecisionsStream: tonic::codegen::tokio_stream::Stream<
+                Item = std::result::Result<super::DecisionTrace, tonic::Status>,
+            >
+            + Send
+            + 'static;
+        async fn stream_decisions(
+            &self,
+            request: tonic::Request<super::StreamDecisionsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<Self::StreamDecisionsStream>,
+            tonic::Status,
+        >;
+        async fn query_rejections(
+            &self,
+            request: tonic::Request<super::QueryRejectionsRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::QueryRejectionsResponse>,
+            tonic::Status,
+        >;
+        async fn set_lockdown(
+            &self,
+            request: tonic::Request<super::SetLockdownRequest>,
+        ) -> std::result::Result<
+            tonic::Response<super::SetLockdownResponse>,
+            tonic::Status,
+        >;
+    }
+    #[derive(Debug)]
+    pub struct ValidationControlServer<T: ValidationControl> {
+        inner: _Inner<T>,
+        accept_compression_encodings: EnabledCompressionEncodings,
+        send_compression_encodings: EnabledCompressionEncodings,
+        max_decoding_message_size: Option<usize>,
+        max_encoding_message_size: Option<usize>,
+    }
+    struct _Inner<T>(Arc<T>);
+    impl<T: ValidationControl> ValidationControlServer<T> {
+        pub fn new(inner: T) -> Self {
+            Self::from_arc(Arc::new(inner))
+        }
+        pub fn from_arc(inner: Arc<T>) -> Self {
+            let inner = _Inner(inner);
+            Self {
+                inner,
+                accept_compression_encodings: Default::default(),
+                send_compression_encodings: Default::default(),
+                max_decoding_message_size: None,
+                max_encoding_message_size: None,
+            }
+        }
+        pub fn with_interceptor<F>(
+            inner: T,
+            interceptor: F,
+        ) -> InterceptedService<Self, F>
+        where
+            F: tonic::service::Interceptor,
+        {
+            InterceptedService::new(Self::new(inner), interceptor)
+        }
+        /// Enable decompressing requests with the given encoding.
+        #[must_use]
+        pub fn accept_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.accept_compression_encodings.enable(encoding);
+            self
+        }
+        /// Compress responses with the given encoding, if the client supports it.
+        #[must_use]
+        pub fn send_compressed(mut self, encoding: CompressionEncoding) -> Self {
+            self.send_compression_encodings.enable(encoding);
+            self
         }
         /// Limits the maximum size of a decoded message.
         ///
@@ -392,22 +1137,254 @@ pub mod validation_control_server {
             match req.uri().path() {
                 "/validationcontrol.ValidationControl/State" => {
                     #[allow(non_camel_case_types)]
-                    struct StateSvc<T: ValidationControl>(pub Arc<T>);
+                    struct StateSvc<T: ValidationControl>(pub Arc<T>);
+                    impl<
+                        T: ValidationControl,
+                    > tonic::server::UnaryService<super::StateRequest> for StateSvc<T> {
+                        type Response = super::StateResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::StateRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ValidationControl>::state(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = StateSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/validationcontrol.ValidationControl/AddBan" => {
+                    #[allow(non_camel_case_types)]
+                    struct AddBanSvc<T: ValidationControl>(pub Arc<T>);
+                    impl<
+                        T: ValidationControl,
+                    > tonic::server::UnaryService<super::AddBanRequest>
+                    for AddBanSvc<T> {
+                        type Response = super::AddBanResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::AddBanRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ValidationControl>::add_ban(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = AddBanSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/validationcontrol.ValidationControl/ListBans" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListBansSvc<T: ValidationControl>(pub Arc<T>);
+                    impl<
+                        T: ValidationControl,
+                    > tonic::server::UnaryService<super::ListBansRequest>
+                    for ListBansSvc<T> {
+                        type Response = super::ListBansResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListBansRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ValidationControl>::list_bans(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ListBansSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/validationcontrol.ValidationControl/RemoveBan" => {
+                    #[allow(non_camel_case_types)]
+                    struct RemoveBanSvc<T: ValidationControl>(pub Arc<T>);
+                    impl<
+                        T: ValidationControl,
+                    > tonic::server::UnaryService<super::RemoveBanRequest>
+                    for RemoveBanSvc<T> {
+                        type Response = super::RemoveBanResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::RemoveBanRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ValidationControl>::remove_ban(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = RemoveBanSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/validationcontrol.ValidationControl/ExportNip51" => {
+                    #[allow(non_camel_case_types)]
+                    struct ExportNip51Svc<T: ValidationControl>(pub Arc<T>);
+                    impl<
+                        T: ValidationControl,
+                    > tonic::server::UnaryService<super::ExportNip51Request>
+                    for ExportNip51Svc<T> {
+                        type Response = super::ExportNip51Response;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ExportNip51Request>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ValidationControl>::export_nip51(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ExportNip51Svc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/validationcontrol.ValidationControl/ImportNip51" => {
+                    #[allow(non_camel_case_types)]
+                    struct ImportNip51Svc<T: ValidationControl>(pub Arc<T>);
                     impl<
                         T: ValidationControl,
-                    > tonic::server::UnaryService<super::StateRequest> for StateSvc<T> {
-                        type Response = super::StateResponse;
+                    > tonic::server::UnaryService<super::ImportNip51Request>
+                    for ImportNip51Svc<T> {
+                        type Response = super::ImportNip51Response;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::StateRequest>,
+                            request: tonic::Request<super::ImportNip51Request>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as ValidationControl>::state(&inner, request).await
+                                <T as ValidationControl>::import_nip51(&inner, request)
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -419,7 +1396,7 @@ pub mod validation_control_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = StateSvc(inner);
+                        let method = ImportNip51Svc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -435,25 +1412,26 @@ pub mod validation_control_server {
                     };
                     Box::pin(fut)
                 }
-                "/validationcontrol.ValidationControl/AddBan" => {
+                "/validationcontrol.ValidationControl/ListRevisions" => {
                     #[allow(non_camel_case_types)]
-                    struct AddBanSvc<T: ValidationControl>(pub Arc<T>);
+                    struct ListRevisionsSvc<T: ValidationControl>(pub Arc<T>);
                     impl<
                         T: ValidationControl,
-                    > tonic::server::UnaryService<super::AddBanRequest>
-                    for AddBanSvc<T> {
-                        type Response = super::AddBanResponse;
+                    > tonic::server::UnaryService<super::ListRevisionsRequest>
+                    for ListRevisionsSvc<T> {
+                        type Response = super::ListRevisionsResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::AddBanRequest>,
+                            request: tonic::Request<super::ListRevisionsRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as ValidationControl>::add_ban(&inner, request).await
+                                <T as ValidationControl>::list_revisions(&inner, request)
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -465,7 +1443,7 @@ pub mod validation_control_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = AddBanSvc(inner);
+                        let method = ListRevisionsSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -481,25 +1459,26 @@ pub mod validation_control_server {
                     };
                     Box::pin(fut)
                 }
-                "/validationcontrol.ValidationControl/ListBans" => {
+                "/validationcontrol.ValidationControl/DiffRevisions" => {
                     #[allow(non_camel_case_types)]
-                    struct ListBansSvc<T: ValidationControl>(pub Arc<T>);
+                    struct DiffRevisionsSvc<T: ValidationControl>(pub Arc<T>);
                     impl<
                         T: ValidationControl,
-                    > tonic::server::UnaryService<super::ListBansRequest>
-                    for ListBansSvc<T> {
-                        type Response = super::ListBansResponse;
+                    > tonic::server::UnaryService<super::DiffRevisionsRequest>
+                    for DiffRevisionsSvc<T> {
+                        type Response = super::DiffRevisionsResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::ListBansRequest>,
+                            request: tonic::Request<super::DiffRevisionsRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as ValidationControl>::list_bans(&inner, request).await
+                                <T as ValidationControl>::diff_revisions(&inner, request)
+                                    .await
                             };
                             Box::pin(fut)
                         }
@@ -511,7 +1490,7 @@ pub mod validation_control_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = ListBansSvc(inner);
+                        let method = DiffRevisionsSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(
@@ -527,25 +1506,25 @@ pub mod validation_control_server {
                     };
                     Box::pin(fut)
                 }
-                "/validationcontrol.ValidationControl/RemoveBan" => {
+                "/validationcontrol.ValidationControl/Rollback" => {
                     #[allow(non_camel_case_types)]
-                    struct RemoveBanSvc<T: ValidationControl>(pub Arc<T>);
+                    struct RollbackSvc<T: ValidationControl>(pub Arc<T>);
                     impl<
                         T: ValidationControl,
-                    > tonic::server::UnaryService<super::RemoveBanRequest>
-                    for RemoveBanSvc<T> {
-                        type Response = super::RemoveBanResponse;
+                    > tonic::server::UnaryService<super::RollbackRequest>
+                    for RollbackSvc<T> {
+                        type Response = super::RollbackResponse;
                         type Future = BoxFuture<
                             tonic::Response<Self::Response>,
                             tonic::Status,
                         >;
                         fn call(
                             &mut self,
-                            request: tonic::Request<super::RemoveBanRequest>,
+                            request: tonic::Request<super::RollbackRequest>,
                         ) -> Self::Future {
                             let inner = Arc::clone(&self.0);
                             let fut = async move {
-                                <T as ValidationControl>::remove_ban(&inner, request).await
+                                <T as ValidationControl>::rollback(&inner, request).await
                             };
                             Box::pin(fut)
                         }
@@ -557,7 +1536,287 @@ pub mod validation_control_server {
                     let inner = self.inner.clone();
                     let fut = async move {
                         let inner = inner.0;
-                        let method = RemoveBanSvc(inner);
+                        let method = RollbackSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/validationcontrol.ValidationControl/AuditLog" => {
+                    #[allow(non_camel_case_types)]
+                    struct AuditLogSvc<T: ValidationControl>(pub Arc<T>);
+                    impl<
+                        T: ValidationControl,
+                    > tonic::server::UnaryService<super::AuditLogRequest>
+                    for AuditLogSvc<T> {
+                        type Response = super::AuditLogResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::AuditLogRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ValidationControl>::audit_log(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = AuditLogSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/validationcontrol.ValidationControl/PruneBans" => {
+                    #[allow(non_camel_case_types)]
+                    struct PruneBansSvc<T: ValidationControl>(pub Arc<T>);
+                    impl<
+                        T: ValidationControl,
+                    > tonic::server::UnaryService<super::PruneBansRequest>
+                    for PruneBansSvc<T> {
+                        type Response = super::PruneBansResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::PruneBansRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ValidationControl>::prune_bans(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = PruneBansSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/validationcontrol.ValidationControl/Stats" => {
+                    #[allow(non_camel_case_types)]
+                    struct StatsSvc<T: ValidationControl>(pub Arc<T>);
+                    impl<
+                        T: ValidationControl,
+                    > tonic::server::UnaryService<super::StatsRequest> for StatsSvc<T> {
+                        type Response = super::StatsResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::StatsRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ValidationControl>::stats(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = StatsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/validationcontrol.ValidationControl/StreamDecisions" => {
+                    #[allow(non_camel_case_types)]
+                    struct StreamDecisionsSvc<T: ValidationControl>(pub Arc<T>);
+                    impl<
+                        T: ValidationControl,
+                    > tonic::server::ServerStreamingService<
+                        super::StreamDecisionsRequest,
+                    > for StreamDecisionsSvc<T> {
+                        type Response = super::DecisionTrace;
+                        type ResponseStream = T::StreamDecisionsStream;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::ResponseStream>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::StreamDecisionsRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ValidationControl>::stream_decisions(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = StreamDecisionsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.server_streaming(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/validationcontrol.ValidationControl/QueryRejections" => {
+                    #[allow(non_camel_case_types)]
+                    struct QueryRejectionsSvc<T: ValidationControl>(pub Arc<T>);
+                    impl<
+                        T: ValidationControl,
+                    > tonic::server::UnaryService<super::QueryRejectionsRequest>
+                    for QueryRejectionsSvc<T> {
+                        type Response = super::QueryRejectionsResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::QueryRejectionsRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ValidationControl>::query_rejections(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = QueryRejectionsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/validationcontrol.ValidationControl/SetLockdown" => {
+                    #[allow(non_camel_case_types)]
+                    struct SetLockdownSvc<T: ValidationControl>(pub Arc<T>);
+                    impl<
+                        T: ValidationControl,
+                    > tonic::server::UnaryService<super::SetLockdownRequest>
+                    for SetLockdownSvc<T> {
+                        type Response = super::SetLockdownResponse;
+                        type Future = BoxFuture<
+                            tonic::Response<Self::Response>,
+                            tonic::Status,
+                        >;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::SetLockdownRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as ValidationControl>::set_lockdown(&inner, request)
+                                    .await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = SetLockdownSvc(inner);
                         let codec = tonic::codec::ProstCodec::default();
                         let mut grpc = tonic::server::Grpc::new(codec)
                             .apply_compression_config(